@@ -10,6 +10,9 @@ use ipis::{
 };
 use ipnis_common::{
     model::Model,
+    nlp::session::{
+        GenerationRequest, GenerationSession, GenerationSessions, GenerationStep, TokenDelta,
+    },
     onnxruntime::{environment::Environment, session::Session, tensor::OrtOwnedTensor},
     tensor::{dynamic::DynamicTensorData, Tensor},
     Ipnis,
@@ -31,6 +34,8 @@ pub struct IpnisClientInner<IpiisClient> {
     ///
     /// * Source: https://github.com/microsoft/onnxruntime/issues/114#issuecomment-444725508
     sessions: Mutex<HashMap<Path, Arc<Session>>>,
+    /// The generations of the clients, kept between the steps.
+    generations: GenerationSessions,
 }
 
 impl<IpiisClient> AsRef<::ipiis_api::client::IpiisClient> for IpnisClientInner<IpiisClient>
@@ -90,9 +95,15 @@ impl<IpiisClient> IpnisClientInner<IpiisClient> {
                 .with_log_level(log_level)
                 .build()?,
             sessions: Default::default(),
+            generations: Default::default(),
         })
     }
 
+    /// Returns the generations of the clients, kept between the steps.
+    pub fn generations(&self) -> &GenerationSessions {
+        &self.generations
+    }
+
     async fn load_session(&self, path: &Path) -> Result<Arc<Session>>
     where
         IpiisClient: Ipsis + Send + Sync,
@@ -196,4 +207,16 @@ where
                 .collect::<Result<_>>()?,
         })
     }
+
+    async fn start_generation(&self, request: GenerationRequest) -> Result<GenerationSession> {
+        self.generations.start(None, request)
+    }
+
+    async fn next_generation(&self, step: GenerationStep) -> Result<Vec<TokenDelta>> {
+        self.generations.next(self, None, step).await
+    }
+
+    async fn stop_generation(&self, session: GenerationSession) -> Result<()> {
+        self.generations.stop(None, session)
+    }
 }
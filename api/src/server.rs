@@ -48,6 +48,9 @@ handle_external_call!(
         Protocol => handle_protocol,
         Call => handle_call,
         LoadModel => handle_load_model,
+        StartGeneration => handle_start_generation,
+        NextGeneration => handle_next_generation,
+        StopGeneration => handle_stop_generation,
     },
 );
 
@@ -124,4 +127,84 @@ impl IpnisServer {
             model: ::ipis::stream::DynStream::Owned(model),
         })
     }
+
+    async fn handle_start_generation(
+        client: &IpnisClientInner<IpiisServer>,
+        req: ::ipnis_common::io::request::StartGeneration<'static>,
+    ) -> Result<::ipnis_common::io::response::StartGeneration<'static>> {
+        // unpack sign
+        let sign_as_guarantee = req.__sign.into_owned().await?;
+
+        // unpack data
+        let account = sign_as_guarantee.metadata.guarantee;
+        let request = req.request.into_owned().await?;
+
+        // handle data
+        let session = client.generations().start(Some(account), request)?;
+
+        // sign data
+        let server: &IpiisServer = client.as_ref();
+        let sign = server.sign_as_guarantor(sign_as_guarantee)?;
+
+        // pack data
+        Ok(::ipnis_common::io::response::StartGeneration {
+            __lifetime: Default::default(),
+            __sign: ::ipis::stream::DynStream::Owned(sign),
+            session: ::ipis::stream::DynStream::Owned(session),
+        })
+    }
+
+    async fn handle_next_generation(
+        client: &IpnisClientInner<IpiisServer>,
+        req: ::ipnis_common::io::request::NextGeneration<'static>,
+    ) -> Result<::ipnis_common::io::response::NextGeneration<'static>> {
+        // unpack sign
+        let sign_as_guarantee = req.__sign.into_owned().await?;
+
+        // unpack data
+        let account = sign_as_guarantee.metadata.guarantee;
+        let step = sign_as_guarantee.data.clone();
+
+        // handle data
+        let deltas = client
+            .generations()
+            .next(client, Some(account), step)
+            .await?;
+
+        // sign data
+        let server: &IpiisServer = client.as_ref();
+        let sign = server.sign_as_guarantor(sign_as_guarantee)?;
+
+        // pack data
+        Ok(::ipnis_common::io::response::NextGeneration {
+            __lifetime: Default::default(),
+            __sign: ::ipis::stream::DynStream::Owned(sign),
+            deltas: ::ipis::stream::DynStream::Owned(deltas),
+        })
+    }
+
+    async fn handle_stop_generation(
+        client: &IpnisClientInner<IpiisServer>,
+        req: ::ipnis_common::io::request::StopGeneration<'static>,
+    ) -> Result<::ipnis_common::io::response::StopGeneration<'static>> {
+        // unpack sign
+        let sign_as_guarantee = req.__sign.into_owned().await?;
+
+        // unpack data
+        let account = sign_as_guarantee.metadata.guarantee;
+        let session = sign_as_guarantee.data;
+
+        // handle data
+        client.generations().stop(Some(account), session)?;
+
+        // sign data
+        let server: &IpiisServer = client.as_ref();
+        let sign = server.sign_as_guarantor(sign_as_guarantee)?;
+
+        // pack data
+        Ok(::ipnis_common::io::response::StopGeneration {
+            __lifetime: Default::default(),
+            __sign: ::ipis::stream::DynStream::Owned(sign),
+        })
+    }
 }
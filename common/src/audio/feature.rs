@@ -0,0 +1,392 @@
+use std::f32::consts::PI;
+
+use ipis::core::{
+    anyhow::{bail, Result},
+    ndarray,
+    value::array::Array,
+};
+use rustfft::{num_complex::Complex, FftPlanner};
+
+use crate::tensor::{shape::Shape, ty::TensorType, Tensor, ToTensor};
+
+use super::{tensor::AudioTensorData, waveform::Audio};
+
+/// The features which the model consumes.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AudioFeature {
+    /// The raw samples, e.g. of wav2vec2.
+    Waveform(WaveformConfig),
+    /// The log-mel spectrogram, e.g. of Whisper.
+    LogMel(MelConfig),
+    /// The mel-frequency cepstral coefficients.
+    Mfcc(MfccConfig),
+}
+
+impl Default for AudioFeature {
+    fn default() -> Self {
+        Self::Waveform(Default::default())
+    }
+}
+
+impl AudioFeature {
+    /// Returns the sample rate which the model expects.
+    pub fn sample_rate(&self) -> u32 {
+        match self {
+            Self::Waveform(config) => config.sample_rate,
+            Self::LogMel(config) => config.sample_rate,
+            Self::Mfcc(config) => config.mel.sample_rate,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WaveformConfig {
+    pub sample_rate: u32,
+    /// Normalizes each waveform into the zero mean and the unit variance.
+    pub normalize: bool,
+}
+
+impl Default for WaveformConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: 16_000,
+            normalize: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MelConfig {
+    pub sample_rate: u32,
+    pub n_fft: usize,
+    pub hop_length: usize,
+    pub n_mels: usize,
+    pub f_min: f32,
+    /// Defaults to the nyquist frequency.
+    pub f_max: Option<f32>,
+    /// Pads or trims the frames to this length, unless the model fixes it.
+    pub num_frames: Option<usize>,
+    /// Clamps the log-mel energies to this range below the maximum, in `log10` units.
+    pub dynamic_range: Option<f32>,
+    /// Rescales the log-mel energies into about `[-1, 1]`, as Whisper does.
+    pub rescale: bool,
+}
+
+impl Default for MelConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: 16_000,
+            n_fft: 400,
+            hop_length: 160,
+            n_mels: 80,
+            f_min: 0.0,
+            f_max: None,
+            num_frames: None,
+            dynamic_range: None,
+            rescale: false,
+        }
+    }
+}
+
+impl MelConfig {
+    /// The 30 seconds of the 80 mel bins.
+    pub fn whisper() -> Self {
+        Self {
+            num_frames: Some(3000),
+            dynamic_range: Some(8.0),
+            rescale: true,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MfccConfig {
+    pub mel: MelConfig,
+    pub n_mfcc: usize,
+}
+
+impl Default for MfccConfig {
+    fn default() -> Self {
+        Self {
+            mel: Default::default(),
+            n_mfcc: 13,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AudioInputs {
+    pub audios: Vec<Audio>,
+    pub feature: AudioFeature,
+}
+
+impl ToTensor for AudioInputs {
+    fn to_tensor(&self, shape: &Shape) -> Result<Tensor> {
+        if shape.ty != TensorType::F32 {
+            let ty = shape.ty;
+            bail!("unsupported TensorType for audio: {ty:?}");
+        }
+        if self.audios.is_empty() {
+            bail!("empty audios are given.");
+        }
+
+        let data = match (&self.feature, &shape.to_vec()[..]) {
+            (AudioFeature::Waveform(config), &[_, num_samples]) => {
+                waveforms_to_data(&self.audios, config, num_samples)
+            }
+            (AudioFeature::LogMel(config), &[_, num_features, num_frames]) => {
+                check_num_features(config.n_mels, num_features)?;
+                let num_frames = num_frames.or(config.num_frames);
+                AudioTensorData::Features(Array(
+                    log_mel_spectrograms(&self.audios, config, num_frames).into(),
+                ))
+            }
+            (AudioFeature::Mfcc(config), &[_, num_features, num_frames]) => {
+                check_num_features(config.n_mfcc, num_features)?;
+                let num_frames = num_frames.or(config.mel.num_frames);
+                AudioTensorData::Features(Array(mfcc(&self.audios, config, num_frames).into()))
+            }
+            _ => bail!("unsupported audio shape: {shape:?}"),
+        };
+
+        Ok(Tensor {
+            name: shape.name.to_string(),
+            data: data.into(),
+        })
+    }
+}
+
+fn check_num_features(given: usize, expected: Option<usize>) -> Result<()> {
+    match expected {
+        Some(expected) if expected != given => {
+            bail!("unexpected audio features: Expected {expected}, Given {given}")
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Stacks the mono waveforms, padding them with silence.
+fn waveforms_to_data(
+    audios: &[Audio],
+    config: &WaveformConfig,
+    num_samples: Option<usize>,
+) -> AudioTensorData {
+    let waveforms: Vec<_> = audios
+        .iter()
+        .map(|audio| {
+            let mut samples = audio.to_mono_samples(config.sample_rate);
+            if config.normalize && !samples.is_empty() {
+                let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+                let var =
+                    samples.iter().map(|x| (x - mean).powi(2)).sum::<f32>() / samples.len() as f32;
+                let std = (var + 1e-7).sqrt();
+                samples.iter_mut().for_each(|x| *x = (*x - mean) / std);
+            }
+            samples
+        })
+        .collect();
+
+    let num_samples =
+        num_samples.unwrap_or_else(|| waveforms.iter().map(Vec::len).max().unwrap_or_default());
+    AudioTensorData::Waveform(Array(
+        ndarray::Array::from_shape_fn((waveforms.len(), num_samples), |(batch, index)| {
+            waveforms[batch].get(index).copied().unwrap_or_default()
+        })
+        .into(),
+    ))
+}
+
+/// Computes the `[batch, n_mels, frames]` log-mel spectrograms, in `log10` units.
+fn log_mel_spectrograms(
+    audios: &[Audio],
+    config: &MelConfig,
+    num_frames: Option<usize>,
+) -> ndarray::Array3<f32> {
+    let waveforms: Vec<_> = audios
+        .iter()
+        .map(|audio| audio.to_mono_samples(config.sample_rate))
+        .collect();
+
+    // pad the waveforms with silence, so that all the spectrograms have the same frames
+    let num_samples = match num_frames {
+        Some(num_frames) => num_frames * config.hop_length,
+        None => waveforms.iter().map(Vec::len).max().unwrap_or_default(),
+    };
+    let num_frames = num_frames.unwrap_or(1 + num_samples / config.hop_length);
+
+    let filters = mel_filters(config);
+    let window = hann_window(config.n_fft);
+    let fft = FftPlanner::new().plan_fft_forward(config.n_fft);
+
+    let mut spectrograms = ndarray::Array3::zeros((audios.len(), config.n_mels, num_frames));
+    for (mut spectrogram, mut samples) in spectrograms.outer_iter_mut().zip(waveforms) {
+        samples.resize(num_samples, 0.0);
+
+        // compute the power spectrum of each frame
+        let samples = center(&samples, config.n_fft / 2);
+        let mut buffer = vec![Complex::new(0.0, 0.0); config.n_fft];
+        for (frame, mut energies) in spectrogram.axis_iter_mut(ndarray::Axis(1)).enumerate() {
+            let offset = frame * config.hop_length;
+            for (index, value) in buffer.iter_mut().enumerate() {
+                let sample = samples.get(offset + index).copied().unwrap_or_default();
+                *value = Complex::new(sample * window[index], 0.0);
+            }
+            fft.process(&mut buffer);
+
+            let power = ndarray::Array::from_iter(
+                buffer[..filters.ncols()]
+                    .iter()
+                    .map(|value| value.norm_sqr()),
+            );
+            energies.assign(&filters.dot(&power).mapv(|energy| energy.max(1e-10).log10()));
+        }
+
+        // clamp and rescale the energies
+        if let Some(dynamic_range) = config.dynamic_range {
+            let max = spectrogram.fold(f32::NEG_INFINITY, |a, &b| a.max(b));
+            spectrogram.mapv_inplace(|energy| energy.max(max - dynamic_range));
+        }
+        if config.rescale {
+            spectrogram.mapv_inplace(|energy| (energy + 4.0) / 4.0);
+        }
+    }
+    spectrograms
+}
+
+/// Computes the MFCCs with the orthonormal DCT-II of the log-mel spectrograms in decibels.
+fn mfcc(audios: &[Audio], config: &MfccConfig, num_frames: Option<usize>) -> ndarray::Array3<f32> {
+    let spectrograms = log_mel_spectrograms(audios, &config.mel, num_frames) * 10.0;
+
+    let n_mels = config.mel.n_mels;
+    let dct = ndarray::Array::from_shape_fn((config.n_mfcc, n_mels), |(k, n)| {
+        let scale = if k == 0 { 1.0 } else { 2.0 } / n_mels as f32;
+        scale.sqrt() * (PI / n_mels as f32 * (n as f32 + 0.5) * k as f32).cos()
+    });
+
+    let mut outputs = ndarray::Array3::zeros((audios.len(), config.n_mfcc, spectrograms.dim().2));
+    for (mut output, spectrogram) in outputs.outer_iter_mut().zip(spectrograms.outer_iter()) {
+        output.assign(&dct.dot(&spectrogram));
+    }
+    outputs
+}
+
+/// Pads the samples on both sides with their reflections, centering the frames.
+fn center(samples: &[f32], pad: usize) -> Vec<f32> {
+    if samples.len() <= pad {
+        let mut padded = vec![0.0; pad];
+        padded.extend_from_slice(samples);
+        padded.resize(samples.len() + 2 * pad, 0.0);
+        return padded;
+    }
+
+    let len = samples.len();
+    samples[1..=pad]
+        .iter()
+        .rev()
+        .chain(samples)
+        .chain(samples[len - 1 - pad..len - 1].iter().rev())
+        .copied()
+        .collect()
+}
+
+/// The periodic Hann window.
+fn hann_window(len: usize) -> Vec<f32> {
+    (0..len)
+        .map(|index| 0.5 - 0.5 * (2.0 * PI * index as f32 / len as f32).cos())
+        .collect()
+}
+
+/// The `[n_mels, n_fft / 2 + 1]` triangular filters on the Slaney mel scale, normalized by their areas.
+fn mel_filters(config: &MelConfig) -> ndarray::Array2<f32> {
+    let num_bins = config.n_fft / 2 + 1;
+    let f_max = config.f_max.unwrap_or(config.sample_rate as f32 / 2.0);
+
+    let mel_min = hz_to_mel(config.f_min);
+    let mel_max = hz_to_mel(f_max);
+    let points: Vec<_> = (0..config.n_mels + 2)
+        .map(|index| {
+            mel_to_hz(mel_min + (mel_max - mel_min) * index as f32 / (config.n_mels + 1) as f32)
+        })
+        .collect();
+
+    ndarray::Array::from_shape_fn((config.n_mels, num_bins), |(mel, bin)| {
+        let hz = bin as f32 * config.sample_rate as f32 / config.n_fft as f32;
+        let (lower, center, upper) = (points[mel], points[mel + 1], points[mel + 2]);
+
+        let weight = ((hz - lower) / (center - lower))
+            .min((upper - hz) / (upper - center))
+            .max(0.0);
+        weight * 2.0 / (upper - lower)
+    })
+}
+
+const MEL_F_SP: f32 = 200.0 / 3.0;
+const MEL_MIN_LOG_HZ: f32 = 1000.0;
+const MEL_MIN_LOG_MEL: f32 = MEL_MIN_LOG_HZ / MEL_F_SP;
+
+fn mel_log_step() -> f32 {
+    6.4f32.ln() / 27.0
+}
+
+fn hz_to_mel(hz: f32) -> f32 {
+    if hz >= MEL_MIN_LOG_HZ {
+        MEL_MIN_LOG_MEL + (hz / MEL_MIN_LOG_HZ).ln() / mel_log_step()
+    } else {
+        hz / MEL_F_SP
+    }
+}
+
+fn mel_to_hz(mel: f32) -> f32 {
+    if mel >= MEL_MIN_LOG_MEL {
+        MEL_MIN_LOG_HZ * (mel_log_step() * (mel - MEL_MIN_LOG_MEL)).exp()
+    } else {
+        mel * MEL_F_SP
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(sample_rate: u32, frequency: f32, len: usize) -> Audio {
+        Audio::new_mono(
+            sample_rate,
+            (0..len)
+                .map(|index| (2.0 * PI * frequency * index as f32 / sample_rate as f32).sin())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn mel_scale_roundtrips() {
+        for hz in [0.0, 440.0, 1000.0, 4000.0, 8000.0] {
+            assert!((mel_to_hz(hz_to_mel(hz)) - hz).abs() < 1e-2);
+        }
+    }
+
+    #[test]
+    fn log_mel_peaks_at_the_tone() {
+        let config = MelConfig {
+            n_fft: 64,
+            hop_length: 16,
+            n_mels: 8,
+            ..Default::default()
+        };
+        let audios = [sine(16_000, 2_000.0, 640), sine(16_000, 2_000.0, 320)];
+
+        let spectrograms = log_mel_spectrograms(&audios, &config, None);
+        assert_eq!(spectrograms.dim(), (2, 8, 41));
+
+        // the tone lies in the filter centered nearest to it
+        let filters = mel_filters(&config);
+        let bin = 2_000 * config.n_fft / 16_000;
+        let expected = (0..config.n_mels)
+            .max_by(|&a, &b| filters[[a, bin]].total_cmp(&filters[[b, bin]]))
+            .unwrap();
+        let given = (0..config.n_mels)
+            .max_by(|&a, &b| spectrograms[[0, a, 20]].total_cmp(&spectrograms[[0, b, 20]]))
+            .unwrap();
+        assert_eq!(given, expected);
+    }
+}
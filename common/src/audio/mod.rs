@@ -0,0 +1,4 @@
+#[cfg(feature = "audio")]
+pub mod feature;
+pub mod tensor;
+pub mod waveform;
@@ -0,0 +1,62 @@
+use bytecheck::CheckBytes;
+use ipis::core::{ndarray, signed::IsSigned, value::array::Array};
+#[cfg(feature = "onnxruntime")]
+use onnxruntime::{
+    session::Session,
+    tensor::{AsOrtTensorDyn, OrtTensorDyn},
+};
+use rkyv::{Archive, Deserialize, Serialize};
+
+use crate::tensor::{dimension::Dimensions, ty::TensorType, AsTensorData, TensorData};
+
+#[derive(Clone, Debug, PartialEq, Archive, Serialize, Deserialize)]
+#[archive_attr(derive(CheckBytes, Debug, PartialEq))]
+pub enum AudioTensorData {
+    /// The raw waveforms, shaped as `[batch, samples]`.
+    Waveform(Array<f32, ndarray::Ix2>),
+    /// The features of each frame, shaped as `[batch, features, frames]`.
+    Features(Array<f32, ndarray::Ix3>),
+}
+
+impl IsSigned for AudioTensorData {}
+
+impl From<AudioTensorData> for TensorData {
+    fn from(value: AudioTensorData) -> Self {
+        Self::Audio(value)
+    }
+}
+
+#[cfg(feature = "onnxruntime")]
+impl<'t> AsOrtTensorDyn<'t> for AudioTensorData {
+    fn as_ort_tensor_dyn<'m>(&self, session: &'m Session) -> ::onnxruntime::Result<OrtTensorDyn<'t>>
+    where
+        'm: 't,
+    {
+        match self {
+            Self::Waveform(v) => v.as_ort_tensor_dyn(session),
+            Self::Features(v) => v.as_ort_tensor_dyn(session),
+        }
+    }
+}
+
+impl AsTensorData for AudioTensorData {
+    fn ty(&self) -> TensorType {
+        match self {
+            Self::Waveform(_) => TensorType::F32,
+            Self::Features(_) => TensorType::F32,
+        }
+    }
+
+    fn dimensions(&self) -> Dimensions {
+        match self {
+            Self::Waveform(v) => Dimensions::Audio {
+                num_features: None,
+                num_frames: Some(v.shape()[1]),
+            },
+            Self::Features(v) => Dimensions::Audio {
+                num_features: Some(v.shape()[1]),
+                num_frames: Some(v.shape()[2]),
+            },
+        }
+    }
+}
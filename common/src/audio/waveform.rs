@@ -0,0 +1,190 @@
+use std::f64::consts::PI;
+
+#[cfg(feature = "audio")]
+use ipis::core::anyhow::{bail, Result};
+
+/// The number of the zero crossings on each side of the resampling filter.
+const RESAMPLE_ZERO_CROSSINGS: f64 = 16.0;
+
+/// The decoded audio, holding the samples of each channel in `[-1, 1]`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Audio {
+    pub sample_rate: u32,
+    pub channels: Vec<Vec<f32>>,
+}
+
+impl Audio {
+    pub fn new_mono(sample_rate: u32, samples: Vec<f32>) -> Self {
+        Self {
+            sample_rate,
+            channels: vec![samples],
+        }
+    }
+
+    /// Decodes a WAV stream of the integer or float PCM samples.
+    #[cfg(feature = "audio")]
+    pub fn from_wav<R>(reader: R) -> Result<Self>
+    where
+        R: ::std::io::Read,
+    {
+        let mut reader = ::hound::WavReader::new(reader)?;
+        let spec = reader.spec();
+
+        let num_channels = spec.channels as usize;
+        if num_channels == 0 {
+            bail!("no channels in the WAV stream");
+        }
+
+        let samples: Vec<f32> = match spec.sample_format {
+            ::hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+            ::hound::SampleFormat::Int => {
+                let scale = 1.0 / (1u64 << (spec.bits_per_sample.max(1) - 1)) as f32;
+                reader
+                    .samples::<i32>()
+                    .map(|sample| sample.map(|sample| sample as f32 * scale))
+                    .collect::<Result<_, _>>()?
+            }
+        };
+
+        // deinterleave the samples
+        Ok(Self {
+            sample_rate: spec.sample_rate,
+            channels: (0..num_channels)
+                .map(|channel| {
+                    samples
+                        .iter()
+                        .skip(channel)
+                        .step_by(num_channels)
+                        .copied()
+                        .collect()
+                })
+                .collect(),
+        })
+    }
+
+    #[cfg(feature = "audio")]
+    pub fn open_wav(path: impl AsRef<::std::path::Path>) -> Result<Self> {
+        Self::from_wav(::std::io::BufReader::new(::std::fs::File::open(path)?))
+    }
+
+    /// Returns the number of the samples of each channel.
+    pub fn len(&self) -> usize {
+        self.channels.iter().map(Vec::len).max().unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the duration in seconds.
+    pub fn duration(&self) -> f32 {
+        self.len() as f32 / self.sample_rate as f32
+    }
+
+    /// Mixes down the channels into a single channel.
+    pub fn to_mono(&self) -> Self {
+        let mut samples = vec![0.0; self.len()];
+        for channel in &self.channels {
+            for (sum, sample) in samples.iter_mut().zip(channel) {
+                *sum += sample / self.channels.len() as f32;
+            }
+        }
+        Self::new_mono(self.sample_rate, samples)
+    }
+
+    /// Resamples each channel with a windowed sinc filter, suppressing the aliasing.
+    pub fn resample(&self, sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            channels: self
+                .channels
+                .iter()
+                .map(|samples| resample(samples, self.sample_rate, sample_rate))
+                .collect(),
+        }
+    }
+
+    /// Returns the mono samples in the sample rate, as the models expect.
+    pub fn to_mono_samples(&self, sample_rate: u32) -> Vec<f32> {
+        self.to_mono()
+            .resample(sample_rate)
+            .channels
+            .pop()
+            .unwrap_or_default()
+    }
+}
+
+fn resample(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+    if from == to || from == 0 || samples.is_empty() {
+        return samples.to_vec();
+    }
+
+    // cut off above the lower nyquist frequency
+    let ratio = to as f64 / from as f64;
+    let cutoff = ratio.min(1.0);
+    let width = RESAMPLE_ZERO_CROSSINGS / cutoff;
+
+    let len = (samples.len() as f64 * ratio).round() as usize;
+    (0..len)
+        .map(|index| {
+            let time = index as f64 / ratio;
+            let start = (time - width).ceil().max(0.0) as usize;
+            let end = ((time + width).floor() as usize).min(samples.len() - 1);
+
+            (start..=end)
+                .map(|source| {
+                    let offset = time - source as f64;
+                    let window = 0.5 * (1.0 + (PI * offset / width).cos());
+                    samples[source] as f64 * cutoff * sinc(cutoff * offset) * window
+                })
+                .sum::<f64>() as f32
+        })
+        .collect()
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(sample_rate: u32, frequency: f32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|index| {
+                let time = index as f32 / sample_rate as f32;
+                (2.0 * ::core::f32::consts::PI * frequency * time).sin()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn resample_keeps_the_frequency() {
+        let audio = Audio::new_mono(48_000, sine(48_000, 440.0, 48_000));
+        let resampled = audio.resample(16_000);
+        assert_eq!(resampled.len(), 16_000);
+
+        // skip the edges, which are faded by the filter
+        let expected = sine(16_000, 440.0, 16_000);
+        for (given, expected) in resampled.channels[0][100..15_900]
+            .iter()
+            .zip(&expected[100..15_900])
+        {
+            assert!((given - expected).abs() < 1e-2);
+        }
+    }
+
+    #[test]
+    fn to_mono_averages_the_channels() {
+        let audio = Audio {
+            sample_rate: 16_000,
+            channels: vec![vec![1.0, 0.5], vec![0.0, -0.5, 1.0]],
+        };
+        assert_eq!(audio.to_mono().channels, vec![vec![0.5, 0.0, 0.5]]);
+    }
+}
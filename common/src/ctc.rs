@@ -0,0 +1,384 @@
+use std::collections::HashMap;
+
+use ipis::core::ndarray;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CtcDecoder {
+    #[default]
+    Greedy,
+    BeamSearch {
+        beam_width: usize,
+    },
+}
+
+/// An emitted class, with the `start` and `end` frames where it is emitted.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CtcToken {
+    pub index: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CtcOutput {
+    pub tokens: Vec<CtcToken>,
+    pub score: f32,
+}
+
+impl CtcOutput {
+    pub fn indices(&self) -> Vec<usize> {
+        self.tokens.iter().map(|token| token.index).collect()
+    }
+}
+
+impl CtcDecoder {
+    /// Decodes the `[steps, classes]` probabilities.
+    ///
+    /// The beam search keeps only the words in the lexicon, if given.
+    pub fn decode(
+        &self,
+        probs: ndarray::ArrayView2<f32>,
+        blank: usize,
+        lexicon: Option<&Lexicon>,
+    ) -> CtcOutput {
+        match self {
+            Self::Greedy => decode_greedy(probs, blank),
+            Self::BeamSearch { beam_width } => {
+                let mut output = decode_beam_search(probs, blank, (*beam_width).max(1), lexicon);
+                extend_tokens(probs, &mut output.tokens);
+                output
+            }
+        }
+    }
+}
+
+/// The words spelled with the classes, separated by the delimiter class.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lexicon {
+    /// The children and whether a word ends here, of each node.
+    nodes: Vec<(HashMap<usize, usize>, bool)>,
+    delimiter: usize,
+}
+
+impl Lexicon {
+    pub fn new<I>(words: I, delimiter: usize) -> Self
+    where
+        I: IntoIterator<Item = Vec<usize>>,
+    {
+        let mut nodes = vec![(HashMap::default(), false)];
+        for word in words {
+            let mut node = 0;
+            for index in word {
+                node = match nodes[node].0.get(&index) {
+                    Some(&child) => child,
+                    None => {
+                        let child = nodes.len();
+                        nodes.push((HashMap::default(), false));
+                        nodes[node].0.insert(index, child);
+                        child
+                    }
+                };
+            }
+            nodes[node].1 = true;
+        }
+        Self { nodes, delimiter }
+    }
+
+    pub fn delimiter(&self) -> usize {
+        self.delimiter
+    }
+
+    fn find(&self, word: &[usize]) -> Option<&(HashMap<usize, usize>, bool)> {
+        let mut node = 0;
+        for index in word {
+            node = *self.nodes[node].0.get(index)?;
+        }
+        self.nodes.get(node)
+    }
+
+    /// Returns whether the prefix can be extended with the class.
+    fn accepts(&self, prefix: &[usize], index: usize) -> bool {
+        let word = self.last_word(prefix);
+        if index == self.delimiter {
+            word.is_empty() || self.is_complete(prefix)
+        } else {
+            self.find(word)
+                .map(|(children, _)| children.contains_key(&index))
+                .unwrap_or_default()
+        }
+    }
+
+    /// Returns whether the last word of the prefix is finished.
+    fn is_complete(&self, prefix: &[usize]) -> bool {
+        let word = self.last_word(prefix);
+        word.is_empty()
+            || self
+                .find(word)
+                .map(|(_, is_word)| *is_word)
+                .unwrap_or_default()
+    }
+
+    fn last_word<'a>(&self, prefix: &'a [usize]) -> &'a [usize] {
+        match prefix.iter().rposition(|&index| index == self.delimiter) {
+            Some(position) => &prefix[position + 1..],
+            None => prefix,
+        }
+    }
+}
+
+fn argmax(row: ndarray::ArrayView1<f32>) -> (usize, f32) {
+    row.iter()
+        .copied()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .unwrap_or_default()
+}
+
+/// Collapses the repeated classes and removes the blanks, scoring with the mean confidence.
+fn decode_greedy(probs: ndarray::ArrayView2<f32>, blank: usize) -> CtcOutput {
+    let mut tokens: Vec<CtcToken> = vec![];
+    let mut confidences = vec![];
+
+    let mut last = blank;
+    for (step, row) in probs.rows().into_iter().enumerate() {
+        let (index, prob) = argmax(row);
+        if index != blank {
+            if index == last {
+                if let Some(token) = tokens.last_mut() {
+                    token.end = step + 1;
+                }
+            } else {
+                tokens.push(CtcToken {
+                    index,
+                    start: step,
+                    end: step + 1,
+                });
+                confidences.push(prob);
+            }
+        }
+        last = index;
+    }
+
+    let score = if confidences.is_empty() {
+        0.0
+    } else {
+        confidences.iter().sum::<f32>() / confidences.len() as f32
+    };
+    CtcOutput { tokens, score }
+}
+
+/// Extends each class over the following frames which predict the same class.
+fn extend_tokens(probs: ndarray::ArrayView2<f32>, tokens: &mut [CtcToken]) {
+    for order in 0..tokens.len() {
+        let limit = tokens
+            .get(order + 1)
+            .map(|token| token.start)
+            .unwrap_or(probs.nrows());
+        let token = &mut tokens[order];
+        while token.end < limit && argmax(probs.row(token.end)).0 == token.index {
+            token.end += 1;
+        }
+    }
+}
+
+/// A prefix of the beam search, following the frames of its most probable path.
+#[derive(Clone)]
+struct Beam {
+    /// The log probability ending in blank.
+    prob_blank: f32,
+    /// The log probability ending in non-blank.
+    prob_non_blank: f32,
+    frames: Vec<usize>,
+    prob_path: f32,
+}
+
+impl Default for Beam {
+    fn default() -> Self {
+        Self {
+            prob_blank: f32::NEG_INFINITY,
+            prob_non_blank: f32::NEG_INFINITY,
+            frames: vec![],
+            prob_path: f32::NEG_INFINITY,
+        }
+    }
+}
+
+impl Beam {
+    fn total(&self) -> f32 {
+        log_add(self.prob_blank, self.prob_non_blank)
+    }
+
+    fn follow(&mut self, prob: f32, frames: impl FnOnce() -> Vec<usize>) {
+        if prob > self.prob_path {
+            self.prob_path = prob;
+            self.frames = frames();
+        }
+    }
+}
+
+fn log_add(a: f32, b: f32) -> f32 {
+    match (a, b) {
+        (a, b) if a == f32::NEG_INFINITY => b,
+        (a, b) if b == f32::NEG_INFINITY => a,
+        (a, b) => a.max(b) + (-(a - b).abs()).exp().ln_1p(),
+    }
+}
+
+/// Prefix beam search in log space, scoring with the per-step geometric mean probability.
+fn decode_beam_search(
+    probs: ndarray::ArrayView2<f32>,
+    blank: usize,
+    beam_width: usize,
+    lexicon: Option<&Lexicon>,
+) -> CtcOutput {
+    let mut beams: Vec<(Vec<usize>, Beam)> = vec![(
+        vec![],
+        Beam {
+            prob_blank: 0.0,
+            ..Default::default()
+        },
+    )];
+
+    for (step, row) in probs.rows().into_iter().enumerate() {
+        // consider the most probable classes only
+        let mut candidates: Vec<_> = row.iter().copied().enumerate().collect();
+        candidates.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        candidates.truncate(beam_width.max(2));
+
+        let mut next: HashMap<Vec<usize>, Beam> = HashMap::new();
+        for (prefix, beam) in &beams {
+            let total = beam.total();
+
+            for &(index, prob) in &candidates {
+                let prob = prob.max(f32::MIN_POSITIVE).ln();
+
+                if index == blank {
+                    let entry = next.entry(prefix.clone()).or_default();
+                    entry.prob_blank = log_add(entry.prob_blank, total + prob);
+                    entry.follow(total + prob, || beam.frames.clone());
+                    continue;
+                }
+
+                if prefix.last() == Some(&index) {
+                    // otherwise, it is collapsed into the same prefix
+                    let entry = next.entry(prefix.clone()).or_default();
+                    entry.prob_non_blank =
+                        log_add(entry.prob_non_blank, beam.prob_non_blank + prob);
+                    entry.follow(beam.prob_non_blank + prob, || beam.frames.clone());
+                }
+
+                // skip the words out of the lexicon
+                if let Some(lexicon) = lexicon {
+                    if !lexicon.accepts(prefix, index) {
+                        continue;
+                    }
+                }
+
+                let mut extended = prefix.clone();
+                extended.push(index);
+
+                // the repeated class is emitted again only after a blank
+                let prob = if prefix.last() == Some(&index) {
+                    beam.prob_blank + prob
+                } else {
+                    total + prob
+                };
+                let entry = next.entry(extended).or_default();
+                entry.prob_non_blank = log_add(entry.prob_non_blank, prob);
+                entry.follow(prob, || [beam.frames.as_slice(), &[step]].concat());
+            }
+        }
+
+        beams = next.into_iter().collect();
+        beams.sort_by(|(_, a), (_, b)| b.total().total_cmp(&a.total()));
+        beams.truncate(beam_width);
+    }
+
+    // prefer the beams which finish their last words
+    if let Some(lexicon) = lexicon {
+        if let Some(position) = beams
+            .iter()
+            .position(|(prefix, _)| lexicon.is_complete(prefix))
+        {
+            beams.swap(0, position);
+        }
+    }
+
+    let num_steps = probs.nrows().max(1) as f32;
+    beams
+        .into_iter()
+        .next()
+        .map(|(prefix, beam)| CtcOutput {
+            tokens: prefix
+                .into_iter()
+                .zip(beam.frames.iter().copied())
+                .map(|(index, start)| CtcToken {
+                    index,
+                    start,
+                    end: start + 1,
+                })
+                .collect(),
+            score: (beam.total() / num_steps).exp(),
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds the probabilities which predict each class with the confidence.
+    fn probs(indices: &[usize], num_classes: usize, confidence: f32) -> ndarray::Array2<f32> {
+        let rest = (1.0 - confidence) / (num_classes - 1) as f32;
+        ndarray::Array::from_shape_fn((indices.len(), num_classes), |(step, index)| {
+            if indices[step] == index {
+                confidence
+            } else {
+                rest
+            }
+        })
+    }
+
+    #[test]
+    fn greedy_collapses_the_repeated_classes() {
+        let probs = probs(&[0, 1, 1, 0, 1, 2, 2, 0], 3, 0.9);
+        let output = CtcDecoder::Greedy.decode(probs.view(), 0, None);
+
+        assert_eq!(output.indices(), vec![1, 1, 2]);
+        let spans: Vec<_> = output
+            .tokens
+            .iter()
+            .map(|token| (token.start, token.end))
+            .collect();
+        assert_eq!(spans, vec![(1, 3), (4, 5), (5, 7)]);
+    }
+
+    #[test]
+    fn beam_search_matches_greedy_on_confident_inputs() {
+        let probs = probs(&[0, 1, 1, 0, 1, 2, 2, 0], 3, 0.9);
+        let greedy = CtcDecoder::Greedy.decode(probs.view(), 0, None);
+        let beam_search = CtcDecoder::BeamSearch { beam_width: 4 }.decode(probs.view(), 0, None);
+
+        assert_eq!(beam_search.indices(), greedy.indices());
+        assert_eq!(beam_search.tokens, greedy.tokens);
+    }
+
+    #[test]
+    fn beam_search_keeps_the_lexicon_words() {
+        // the classes: blank, delimiter, a, b
+        let mut probs = probs(&[2, 0, 2, 0, 1], 4, 0.9);
+        // `b` is less probable than `a` at the third step
+        probs[[2, 2]] = 0.6;
+        probs[[2, 3]] = 0.3;
+
+        let decoder = CtcDecoder::BeamSearch { beam_width: 8 };
+        assert_eq!(
+            decoder.decode(probs.view(), 0, None).indices(),
+            vec![2, 2, 1]
+        );
+
+        let lexicon = Lexicon::new([vec![2, 3]], 1);
+        let output = decoder.decode(probs.view(), 0, Some(&lexicon));
+        assert_eq!(output.indices(), vec![2, 3, 1]);
+    }
+}
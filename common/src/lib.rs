@@ -1,10 +1,18 @@
+#[cfg(feature = "audio")]
+pub extern crate hound;
 #[cfg(feature = "image")]
 pub extern crate image;
 #[cfg(feature = "onnxruntime")]
 pub extern crate onnxruntime;
 #[cfg(feature = "rust_tokenizers")]
 pub extern crate rust_tokenizers;
+#[cfg(feature = "audio")]
+pub extern crate rustfft;
+#[cfg(feature = "tokenizers")]
+pub extern crate tokenizers;
 
+pub mod audio;
+pub mod ctc;
 pub mod model;
 pub mod nlp;
 pub mod tensor;
@@ -25,6 +33,7 @@ use ipis::{
 
 use self::{
     model::Model,
+    nlp::session::{GenerationRequest, GenerationSession, GenerationStep, TokenDelta},
     tensor::{Tensor, ToTensor},
 };
 
@@ -55,6 +64,17 @@ pub trait Ipnis {
     async fn call_raw(&self, model: &Model, inputs: Vec<Tensor>) -> Result<Vec<Tensor>>;
 
     async fn load_model(&self, path: &Path) -> Result<Model>;
+
+    /// Starts generating step by step, keeping the state between the steps.
+    async fn start_generation(&self, request: GenerationRequest) -> Result<GenerationSession>;
+
+    /// Runs a step of the generation, returning the new tokens of each input.
+    ///
+    /// The session is closed when every input is finished.
+    async fn next_generation(&self, step: GenerationStep) -> Result<Vec<TokenDelta>>;
+
+    /// Closes the generation before it is finished, releasing its state.
+    async fn stop_generation(&self, session: GenerationSession) -> Result<()>;
 }
 
 #[async_trait]
@@ -118,6 +138,62 @@ where
         // unpack response
         Ok(model)
     }
+
+    async fn start_generation(&self, request: GenerationRequest) -> Result<GenerationSession> {
+        // next target
+        let target = self.get_account_primary(KIND.as_ref()).await?;
+
+        // external call
+        let (session,) = external_call!(
+            client: self,
+            target: KIND.as_ref() => &target,
+            request: crate::io => StartGeneration,
+            sign: self.sign_owned(target, ())?,
+            inputs: {
+                request: request,
+            },
+            outputs: { session, },
+        );
+
+        // unpack response
+        Ok(session)
+    }
+
+    async fn next_generation(&self, step: GenerationStep) -> Result<Vec<TokenDelta>> {
+        // next target
+        let target = self.get_account_primary(KIND.as_ref()).await?;
+
+        // external call
+        let (deltas,) = external_call!(
+            client: self,
+            target: KIND.as_ref() => &target,
+            request: crate::io => NextGeneration,
+            sign: self.sign_owned(target, step)?,
+            inputs: { },
+            outputs: { deltas, },
+        );
+
+        // unpack response
+        Ok(deltas)
+    }
+
+    async fn stop_generation(&self, session: GenerationSession) -> Result<()> {
+        // next target
+        let target = self.get_account_primary(KIND.as_ref()).await?;
+
+        // external call
+        let () = external_call!(
+            client: self,
+            target: KIND.as_ref() => &target,
+            request: crate::io => StopGeneration,
+            sign: self.sign_owned(target, session)?,
+            inputs: { },
+            outputs: { },
+        );
+
+        // unpack response
+        Ok(())
+    }
 }
 
 define_io! {
@@ -151,6 +227,33 @@ define_io! {
         output_sign: Data<GuarantorSigned, Path>,
         generics: { },
     },
+    StartGeneration {
+        inputs: {
+            request: GenerationRequest,
+        },
+        input_sign: Data<GuaranteeSigned, ()>,
+        outputs: {
+            session: GenerationSession,
+        },
+        output_sign: Data<GuarantorSigned, ()>,
+        generics: { },
+    },
+    NextGeneration {
+        inputs: { },
+        input_sign: Data<GuaranteeSigned, GenerationStep>,
+        outputs: {
+            deltas: Vec<TokenDelta>,
+        },
+        output_sign: Data<GuarantorSigned, GenerationStep>,
+        generics: { },
+    },
+    StopGeneration {
+        inputs: { },
+        input_sign: Data<GuaranteeSigned, GenerationSession>,
+        outputs: { },
+        output_sign: Data<GuarantorSigned, GenerationSession>,
+        generics: { },
+    },
 }
 
 ::ipis::lazy_static::lazy_static! {
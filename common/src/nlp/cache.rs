@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+
+use ipis::core::{
+    anyhow::{bail, Result},
+    ndarray,
+    value::array::Array,
+};
+
+use crate::{
+    model::Model,
+    tensor::{dynamic::DynamicTensorData, Tensor, TensorData, ToTensor},
+};
+
+use super::tensor::StringTensorData;
+
+/// The past key-values of a decoder, kept between the decoding steps
+/// so that each step only processes the new tokens.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct KvCache {
+    /// The cached tensors, named after the inputs of the decoder.
+    pub tensors: HashMap<String, TensorData>,
+}
+
+impl KvCache {
+    /// The prefix of the outputs to be cached, e.g. `present.0.decoder.key`.
+    pub const OUTPUT_PREFIX: &'static str = "present";
+    /// The prefix of the inputs to be fed, e.g. `past_key_values.0.decoder.key`.
+    pub const INPUT_PREFIX: &'static str = "past_key_values";
+
+    /// Prepares the empty past key-values, for the models which always consume them.
+    ///
+    /// The dynamic dimensions except the batch, e.g. the past sequence length, are set to `0`.
+    pub fn with_model(model: &Model, batch_size: usize) -> Self {
+        Self {
+            tensors: model
+                .inputs
+                .iter()
+                .filter(|shape| shape.name.starts_with(Self::INPUT_PREFIX))
+                .map(|shape| {
+                    let dimensions: Vec<_> = shape
+                        .to_vec()
+                        .into_iter()
+                        .enumerate()
+                        .map(|(axis, dimension)| match (axis, dimension) {
+                            (0, _) => batch_size,
+                            (_, dimension) => dimension.unwrap_or(0),
+                        })
+                        .collect();
+                    let data = ndarray::ArrayD::zeros(dimensions);
+                    (
+                        shape.name.clone(),
+                        DynamicTensorData::F32(Array(data.into())).into(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns whether the model consumes the past key-values.
+    pub fn is_supported(model: &Model) -> bool {
+        model
+            .inputs
+            .iter()
+            .any(|shape| shape.name.starts_with(Self::INPUT_PREFIX))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tensors.is_empty()
+    }
+
+    /// Stores the past key-values, returning the other outputs.
+    ///
+    /// The cached tensors which are not updated, e.g. the cross-attention key-values, are kept.
+    pub fn update(&mut self, outputs: Vec<Tensor>) -> Vec<Tensor> {
+        outputs
+            .into_iter()
+            .filter_map(
+                |tensor| match tensor.name.strip_prefix(Self::OUTPUT_PREFIX) {
+                    Some(suffix) => {
+                        let name = format!("{}{suffix}", Self::INPUT_PREFIX);
+                        self.tensors.insert(name, tensor.data);
+                        None
+                    }
+                    None => Some(tensor),
+                },
+            )
+            .collect()
+    }
+
+    /// Rearranges the batch to follow the parents of the hypotheses, e.g. the beams.
+    pub fn reorder(&mut self, parents: &[usize]) -> Result<()> {
+        for data in self.tensors.values_mut() {
+            *data = select(data, parents)?;
+        }
+        Ok(())
+    }
+
+    /// Feeds the cached tensors into the inputs.
+    pub fn extend_inputs(&self, inputs: &mut HashMap<String, Box<dyn ToTensor + Send + Sync>>) {
+        inputs.extend(self.tensors.iter().map(|(name, data)| {
+            (
+                name.clone(),
+                Box::new(data.clone()) as Box<dyn ToTensor + Send + Sync>,
+            )
+        }));
+    }
+}
+
+/// Selects the rows of the batch.
+pub fn select(data: &TensorData, indices: &[usize]) -> Result<TensorData> {
+    let axis = ndarray::Axis(0);
+    Ok(match data {
+        TensorData::Dynamic(DynamicTensorData::U8(data)) => {
+            DynamicTensorData::U8(Array(data.0.select(axis, indices).into())).into()
+        }
+        TensorData::Dynamic(DynamicTensorData::F32(data)) => {
+            DynamicTensorData::F32(Array(data.0.select(axis, indices).into())).into()
+        }
+        TensorData::String(StringTensorData::I64(data)) => {
+            StringTensorData::I64(Array(data.0.select(axis, indices).into())).into()
+        }
+        TensorData::String(StringTensorData::F32(data)) => {
+            StringTensorData::F32(Array(data.0.select(axis, indices).into())).into()
+        }
+        TensorData::String(StringTensorData::F32Embedding(data)) => {
+            StringTensorData::F32Embedding(Array(data.0.select(axis, indices).into())).into()
+        }
+        _ => bail!("selecting the rows of the tensor is not supported"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tensor(name: &str, rows: &[f32]) -> Tensor {
+        let data = ndarray::Array::from_shape_vec((rows.len(), 1), rows.to_vec()).unwrap();
+        Tensor {
+            name: name.into(),
+            data: DynamicTensorData::F32(Array(data.into_dyn().into())).into(),
+        }
+    }
+
+    #[test]
+    fn cache_follows_the_parents() {
+        let mut cache = KvCache::default();
+        let outputs = cache.update(vec![
+            tensor("logits", &[0.0, 0.0]),
+            tensor("present.0.decoder.key", &[1.0, 2.0]),
+        ]);
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].name, "logits");
+
+        cache.reorder(&[1, 1, 0]).unwrap();
+        assert_eq!(
+            cache.tensors["past_key_values.0.decoder.key"],
+            tensor("", &[2.0, 2.0, 1.0]).data,
+        );
+    }
+}
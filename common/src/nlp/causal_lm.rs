@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+
+use ipis::core::{
+    anyhow::{bail, Result},
+    ndarray,
+    value::array::Array,
+};
+
+use crate::{
+    model::Model,
+    tensor::{Tensor, TensorData, ToTensor},
+    Ipnis,
+};
+
+use super::{
+    cache::KvCache,
+    generation::{GenerationConfig, Generator, Hypothesis},
+    seq2seq::stack,
+    tensor::StringTensorData,
+};
+
+/// Continues the inputs of a decoder-only model step by step.
+///
+/// The past key-values are reused between the steps if the model consumes them.
+pub struct CausalLm<'a, C: ?Sized> {
+    client: &'a C,
+    model: &'a Model,
+    state: CausalLmState,
+}
+
+/// The state of the generation between the steps, which does not borrow the client and the model.
+pub struct CausalLmState {
+    input_ids: ndarray::Array2<i64>,
+    attention_mask: ndarray::Array2<i64>,
+    generator: Generator,
+    cache: Option<KvCache>,
+    num_cached_tokens: usize,
+}
+
+impl CausalLmState {
+    /// Starts generating after the inputs, which are aligned to the right.
+    ///
+    /// The prefixes are the inputs without the padding.
+    pub fn new(
+        model: &Model,
+        config: &GenerationConfig,
+        input_ids: ndarray::Array2<i64>,
+        attention_mask: ndarray::Array2<i64>,
+        prefixes: Vec<Vec<i64>>,
+        token_eos: i64,
+    ) -> Result<Self> {
+        if prefixes.len() != input_ids.nrows() {
+            let expected = input_ids.nrows();
+            let given = prefixes.len();
+            bail!("unexpected prefixes: Expected {expected}, Given {given}");
+        }
+
+        Ok(Self {
+            cache: match KvCache::is_supported(model) {
+                true => Some(KvCache::with_model(model, input_ids.nrows())),
+                false => None,
+            },
+            input_ids,
+            attention_mask,
+            generator: Generator::new(config, prefixes, token_eos)?,
+            num_cached_tokens: 0,
+        })
+    }
+
+    /// Continues generating with the client and the model.
+    pub fn resume<'a, C: ?Sized>(self, client: &'a C, model: &'a Model) -> CausalLm<'a, C> {
+        CausalLm {
+            client,
+            model,
+            state: self,
+        }
+    }
+
+    pub fn generator(&self) -> &Generator {
+        &self.generator
+    }
+
+    /// Finishes the active hypotheses matching the condition, e.g. the stop sequences.
+    pub fn stop_if(&mut self, f: impl FnMut(&Hypothesis) -> bool) -> Result<()> {
+        let parents = self.generator.stop_if(f);
+        match &mut self.cache {
+            Some(cache) => cache.reorder(&parents),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a, C> CausalLm<'a, C>
+where
+    C: Ipnis + ?Sized + Sync,
+{
+    /// Starts generating after the inputs, which are aligned to the right.
+    ///
+    /// The prefixes are the inputs without the padding.
+    pub fn new(
+        client: &'a C,
+        model: &'a Model,
+        config: &GenerationConfig,
+        input_ids: ndarray::Array2<i64>,
+        attention_mask: ndarray::Array2<i64>,
+        prefixes: Vec<Vec<i64>>,
+        token_eos: i64,
+    ) -> Result<Self> {
+        CausalLmState::new(
+            model,
+            config,
+            input_ids,
+            attention_mask,
+            prefixes,
+            token_eos,
+        )
+        .map(|state| state.resume(client, model))
+    }
+
+    pub fn generator(&self) -> &Generator {
+        &self.state.generator
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.state.generator.is_done()
+    }
+
+    /// Finishes the active hypotheses matching the condition, e.g. the stop sequences.
+    pub fn stop_if(&mut self, f: impl FnMut(&Hypothesis) -> bool) -> Result<()> {
+        self.state.stop_if(f)
+    }
+
+    pub fn finish(self) -> Vec<Hypothesis> {
+        self.state.generator.finish()
+    }
+
+    /// Releases the client and the model, keeping the state to be resumed later.
+    pub fn into_state(self) -> CausalLmState {
+        self.state
+    }
+
+    /// Generates the next tokens of the active hypotheses.
+    pub async fn step(&mut self) -> Result<()> {
+        let state = &mut self.state;
+        let active = state.generator.active();
+        let mut inputs = HashMap::default();
+        let len = {
+            // the whole sequences of the active hypotheses
+            let indices: Vec<_> = active.iter().map(|hypothesis| hypothesis.input).collect();
+            let generated: Vec<_> = active
+                .iter()
+                .map(|hypothesis| hypothesis.generated().to_vec())
+                .collect();
+            let generated = stack(&generated)?;
+            let sequences = ndarray::concatenate![
+                ndarray::Axis(1),
+                state.input_ids.select(ndarray::Axis(0), &indices).view(),
+                generated.view(),
+            ];
+            let attention_mask = ndarray::concatenate![
+                ndarray::Axis(1),
+                state
+                    .attention_mask
+                    .select(ndarray::Axis(0), &indices)
+                    .view(),
+                ndarray::Array::ones(generated.dim()).view(),
+            ];
+            let position_ids = find_position_ids(&attention_mask);
+
+            // feed the new tokens only if the past key-values are cached
+            let len = sequences.ncols();
+            let new_tokens = match state.cache {
+                Some(_) => ndarray::s![.., state.num_cached_tokens..],
+                None => ndarray::s![.., ..],
+            };
+
+            inputs.insert(
+                "input_ids".into(),
+                Box::new(TensorData::from(StringTensorData::I64(Array(
+                    sequences.slice(new_tokens).to_owned().into(),
+                )))) as Box<dyn ToTensor + Send + Sync>,
+            );
+            inputs.insert(
+                "position_ids".into(),
+                Box::new(TensorData::from(StringTensorData::I64(Array(
+                    position_ids.slice(new_tokens).to_owned().into(),
+                )))) as Box<dyn ToTensor + Send + Sync>,
+            );
+            inputs.insert(
+                "attention_mask".into(),
+                Box::new(TensorData::from(StringTensorData::I64(Array(
+                    attention_mask.into(),
+                )))) as Box<dyn ToTensor + Send + Sync>,
+            );
+            if let Some(cache) = &state.cache {
+                cache.extend_inputs(&mut inputs);
+            }
+            len
+        };
+
+        let outputs = self.client.call(self.model, &inputs).await?;
+        let mut outputs = match &mut state.cache {
+            Some(cache) => cache.update(outputs),
+            None => outputs,
+        };
+        state.num_cached_tokens = len;
+
+        let logits: Tensor<StringTensorData> = Tensor::find(&mut outputs, "logits")?.try_into()?;
+
+        let parents = match &logits.data {
+            StringTensorData::F32Embedding(logits) => {
+                let position = logits.shape()[1] - 1;
+                state
+                    .generator
+                    .step(logits.index_axis(ndarray::Axis(1), position))?
+            }
+            _ => {
+                let logits = logits.shape();
+                bail!("unexpected StringTensorData: {logits:?}")
+            }
+        };
+        match &mut state.cache {
+            Some(cache) => cache.reorder(&parents),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Counts the positions from the first real token of each row, skipping the padding.
+fn find_position_ids(attention_mask: &ndarray::Array2<i64>) -> ndarray::Array2<i64> {
+    let mut position_ids = attention_mask.clone();
+    for mut row in position_ids.rows_mut() {
+        let mut position = 0;
+        for value in row.iter_mut() {
+            if *value == 0 {
+                continue;
+            }
+            *value = position;
+            position += 1;
+        }
+    }
+    position_ids
+}
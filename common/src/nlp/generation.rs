@@ -0,0 +1,561 @@
+use bytecheck::CheckBytes;
+use rkyv::{Archive, Deserialize, Serialize};
+#[cfg(feature = "generation")]
+use {
+    ipis::core::{
+        anyhow::{bail, Result},
+        ndarray,
+    },
+    rand::{rngs::StdRng, Rng, SeedableRng},
+};
+
+#[derive(Clone, Debug, PartialEq, Archive, Serialize, Deserialize)]
+#[archive_attr(derive(CheckBytes, Debug, PartialEq))]
+pub struct GenerationConfig {
+    /// Stops generating after this number of tokens, even if no EOS token is generated.
+    pub max_new_tokens: usize,
+    /// Suppresses the EOS token until this number of tokens are generated.
+    pub min_new_tokens: usize,
+    /// Keeps this number of the hypotheses per input, where `1` disables the beam search.
+    pub num_beams: usize,
+    /// Divides the beam scores by `length ^ length_penalty`,
+    /// so that the positive values prefer the longer sequences.
+    pub length_penalty: f32,
+    /// Samples the tokens instead of picking the most probable ones.
+    pub sampling: Option<Sampling>,
+    /// Penalizes the logits of the tokens already in the sequence, where `1.0` disables it.
+    pub repetition_penalty: f32,
+    /// Bans the n-grams already in the sequence, where `0` disables it.
+    pub no_repeat_ngram_size: usize,
+}
+
+impl Default for GenerationConfig {
+    fn default() -> Self {
+        Self {
+            max_new_tokens: 256,
+            min_new_tokens: 0,
+            num_beams: 1,
+            length_penalty: 1.0,
+            sampling: None,
+            repetition_penalty: 1.0,
+            no_repeat_ngram_size: 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Archive, Serialize, Deserialize)]
+#[archive_attr(derive(CheckBytes, Debug, PartialEq))]
+pub struct Sampling {
+    pub temperature: f32,
+    /// Samples among the `top_k` most probable tokens, where `0` disables it.
+    pub top_k: usize,
+    /// Samples among the most probable tokens whose total probability reaches `top_p`.
+    pub top_p: f32,
+    /// Makes the sampling reproducible.
+    pub seed: Option<u64>,
+}
+
+impl Default for Sampling {
+    fn default() -> Self {
+        Self {
+            temperature: 1.0,
+            top_k: 0,
+            top_p: 1.0,
+            seed: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hypothesis {
+    /// The index of the input.
+    pub input: usize,
+    /// The tokens, including the prefix.
+    pub tokens: Vec<i64>,
+    pub num_prefix_tokens: usize,
+    /// The log-probability of each generated token.
+    pub logprobs: Vec<f32>,
+}
+
+impl Hypothesis {
+    /// Returns the generated tokens, excluding the prefix.
+    pub fn generated(&self) -> &[i64] {
+        &self.tokens[self.num_prefix_tokens..]
+    }
+
+    pub fn score(&self) -> f32 {
+        self.logprobs.iter().sum()
+    }
+
+    #[cfg(feature = "generation")]
+    fn normalized_score(&self, length_penalty: f32) -> f32 {
+        self.score() / (self.logprobs.len().max(1) as f32).powf(length_penalty)
+    }
+}
+
+/// Selects the next tokens step by step, given the logits of the last positions.
+#[cfg(feature = "generation")]
+pub struct Generator {
+    config: GenerationConfig,
+    token_eos: i64,
+    /// The prefix of each input, which is kept when no hypothesis survives.
+    prefixes: Vec<Vec<i64>>,
+    active: Vec<Hypothesis>,
+    finished: Vec<Vec<Hypothesis>>,
+    rng: StdRng,
+}
+
+#[cfg(feature = "generation")]
+impl Generator {
+    /// Starts generating from the prefix of each input.
+    pub fn new(config: &GenerationConfig, prefixes: Vec<Vec<i64>>, token_eos: i64) -> Result<Self> {
+        if config.num_beams == 0 {
+            bail!("the number of beams should be positive");
+        }
+        if config.num_beams > 1 && config.sampling.is_some() {
+            bail!("sampling with the beam search is not supported yet");
+        }
+
+        let rng = match config.sampling.as_ref().and_then(|sampling| sampling.seed) {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+
+        Ok(Self {
+            config: config.clone(),
+            token_eos,
+            finished: vec![vec![]; prefixes.len()],
+            active: prefixes
+                .iter()
+                .cloned()
+                .enumerate()
+                .map(|(input, tokens)| Hypothesis {
+                    input,
+                    num_prefix_tokens: tokens.len(),
+                    tokens,
+                    logprobs: vec![],
+                })
+                .collect(),
+            prefixes,
+            rng,
+        })
+    }
+
+    /// Returns the unfinished hypotheses, which are fed into the model in order.
+    pub fn active(&self) -> &[Hypothesis] {
+        &self.active
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.active.is_empty()
+    }
+
+    /// Returns the best hypothesis of the input so far, and whether it is finished.
+    ///
+    /// The hypothesis may be replaced by the other beams later.
+    pub fn current(&self, input: usize) -> Option<(&Hypothesis, bool)> {
+        let length_penalty = self.config.length_penalty;
+        let best = |a: &&Hypothesis, b: &&Hypothesis| {
+            a.normalized_score(length_penalty)
+                .total_cmp(&b.normalized_score(length_penalty))
+        };
+
+        match self
+            .active
+            .iter()
+            .filter(|hypothesis| hypothesis.input == input)
+            .max_by(best)
+        {
+            Some(hypothesis) => Some((hypothesis, false)),
+            None => self
+                .finished
+                .get(input)?
+                .iter()
+                .max_by(best)
+                .map(|hypothesis| (hypothesis, true)),
+        }
+    }
+
+    /// Selects the next tokens with the logits of each active hypothesis.
+    ///
+    /// Returns the index of the parent of each new active hypothesis,
+    /// e.g. to reorder the cached states of the model.
+    pub fn step(&mut self, logits: ndarray::ArrayView2<f32>) -> Result<Vec<usize>> {
+        if logits.nrows() != self.active.len() {
+            let expected = self.active.len();
+            let given = logits.nrows();
+            bail!("unexpected logits: Expected {expected}, Given {given}");
+        }
+
+        let logprobs: Vec<_> = self
+            .active
+            .iter()
+            .zip(logits.rows())
+            .map(|(hypothesis, logits)| self.process(hypothesis, logits))
+            .collect();
+
+        let candidates = if self.config.num_beams == 1 {
+            let mut candidates = Vec::with_capacity(logprobs.len());
+            for (parent, logprobs) in logprobs.iter().enumerate() {
+                let token = match &self.config.sampling {
+                    Some(sampling) => sample(&mut self.rng, sampling, logprobs),
+                    None => argmax(logprobs),
+                };
+                candidates.push((parent, token, logprobs[token]));
+            }
+            candidates
+        } else {
+            self.search_beams(&logprobs)
+        };
+
+        let mut parents = vec![];
+        let mut active = vec![];
+        for (parent, token, logprob) in candidates {
+            let mut hypothesis = self.active[parent].clone();
+            hypothesis.tokens.push(token as i64);
+            hypothesis.logprobs.push(logprob);
+
+            if token as i64 == self.token_eos
+                || hypothesis.logprobs.len() >= self.config.max_new_tokens
+            {
+                self.finished[hypothesis.input].push(hypothesis);
+            } else {
+                parents.push(parent);
+                active.push(hypothesis);
+            }
+        }
+
+        Ok(self.retain(parents, active))
+    }
+
+    /// Finishes the active hypotheses matching the condition, e.g. the stop sequences.
+    ///
+    /// Returns the indices of the remaining active hypotheses.
+    pub fn stop_if(&mut self, mut f: impl FnMut(&Hypothesis) -> bool) -> Vec<usize> {
+        let mut parents = vec![];
+        let mut active = vec![];
+        for (parent, hypothesis) in self.active.drain(..).enumerate() {
+            if f(&hypothesis) {
+                self.finished[hypothesis.input].push(hypothesis);
+            } else {
+                parents.push(parent);
+                active.push(hypothesis);
+            }
+        }
+        self.retain(parents, active)
+    }
+
+    /// Replaces the active hypotheses, dropping the beams which cannot be improved anymore.
+    fn retain(&mut self, parents: Vec<usize>, active: Vec<Hypothesis>) -> Vec<usize> {
+        self.active = active;
+        let done: Vec<_> = (0..self.finished.len())
+            .map(|input| self.is_finished(input))
+            .collect();
+        let (parents, active) = parents
+            .into_iter()
+            .zip(self.active.drain(..))
+            .filter(|(_, hypothesis)| !done[hypothesis.input])
+            .unzip();
+        self.active = active;
+        parents
+    }
+
+    /// Returns the best hypothesis of each input, in order.
+    ///
+    /// An input without any hypothesis is given an empty one, keeping its prefix.
+    pub fn finish(mut self) -> Vec<Hypothesis> {
+        // the unfinished hypotheses are candidates as well
+        for hypothesis in self.active.drain(..) {
+            self.finished[hypothesis.input].push(hypothesis);
+        }
+
+        let length_penalty = self.config.length_penalty;
+        self.finished
+            .into_iter()
+            .zip(self.prefixes)
+            .enumerate()
+            .map(|(input, (hypotheses, prefix))| {
+                hypotheses
+                    .into_iter()
+                    .max_by(|a, b| {
+                        a.normalized_score(length_penalty)
+                            .total_cmp(&b.normalized_score(length_penalty))
+                    })
+                    .unwrap_or_else(|| Hypothesis {
+                        input,
+                        num_prefix_tokens: prefix.len(),
+                        tokens: prefix,
+                        logprobs: vec![],
+                    })
+            })
+            .collect()
+    }
+
+    /// Converts the logits into the log-probabilities, applying the constraints.
+    fn process(&self, hypothesis: &Hypothesis, logits: ndarray::ArrayView1<f32>) -> Vec<f32> {
+        let mut logits = logits.to_vec();
+
+        // penalize the repetitions
+        if self.config.repetition_penalty != 1.0 {
+            let mut tokens = hypothesis.tokens.clone();
+            tokens.sort_unstable();
+            tokens.dedup();
+            for token in tokens {
+                if let Some(logit) = usize::try_from(token)
+                    .ok()
+                    .and_then(|token| logits.get_mut(token))
+                {
+                    *logit = if *logit < 0.0 {
+                        *logit * self.config.repetition_penalty
+                    } else {
+                        *logit / self.config.repetition_penalty
+                    };
+                }
+            }
+        }
+
+        // ban the repeated n-grams
+        for token in find_banned_tokens(&hypothesis.tokens, self.config.no_repeat_ngram_size) {
+            if let Some(logit) = usize::try_from(token)
+                .ok()
+                .and_then(|token| logits.get_mut(token))
+            {
+                *logit = f32::NEG_INFINITY;
+            }
+        }
+
+        // suppress the EOS token
+        if hypothesis.logprobs.len() < self.config.min_new_tokens {
+            if let Some(logit) = usize::try_from(self.token_eos)
+                .ok()
+                .and_then(|token| logits.get_mut(token))
+            {
+                *logit = f32::NEG_INFINITY;
+            }
+        }
+
+        if let Some(sampling) = &self.config.sampling {
+            if sampling.temperature > 0.0 && sampling.temperature != 1.0 {
+                logits
+                    .iter_mut()
+                    .for_each(|logit| *logit /= sampling.temperature);
+            }
+        }
+        log_softmax(&logits)
+    }
+
+    /// Keeps the best `num_beams` candidates of each input.
+    fn search_beams(&self, logprobs: &[Vec<f32>]) -> Vec<(usize, usize, f32)> {
+        let num_beams = self.config.num_beams;
+
+        let mut candidates: Vec<Vec<(usize, usize, f32)>> = vec![vec![]; self.finished.len()];
+        for (parent, (hypothesis, logprobs)) in self.active.iter().zip(logprobs).enumerate() {
+            let mut tokens: Vec<_> = (0..logprobs.len()).collect();
+            tokens.sort_unstable_by(|&a, &b| logprobs[b].total_cmp(&logprobs[a]));
+            tokens.truncate(2 * num_beams);
+
+            candidates[hypothesis.input].extend(
+                tokens
+                    .into_iter()
+                    .filter(|&token| logprobs[token].is_finite())
+                    .map(|token| (parent, token, logprobs[token])),
+            );
+        }
+
+        candidates
+            .into_iter()
+            .flat_map(|mut candidates| {
+                let score = |&(parent, _, logprob): &(usize, usize, f32)| {
+                    self.active[parent].score() + logprob
+                };
+                candidates.sort_unstable_by(|a, b| score(b).total_cmp(&score(a)));
+
+                // the finished candidates are kept only if they are within the beams
+                let mut selected = vec![];
+                let mut num_active = 0;
+                for (rank, candidate) in candidates.into_iter().enumerate() {
+                    if num_active == num_beams {
+                        break;
+                    }
+                    if candidate.1 as i64 == self.token_eos {
+                        if rank < num_beams {
+                            selected.push(candidate);
+                        }
+                    } else {
+                        num_active += 1;
+                        selected.push(candidate);
+                    }
+                }
+                selected
+            })
+            .collect()
+    }
+
+    fn is_finished(&self, input: usize) -> bool {
+        let num_beams = self.config.num_beams;
+        let length_penalty = self.config.length_penalty;
+        let finished = &self.finished[input];
+        if num_beams == 1 || finished.len() < num_beams {
+            return !finished.is_empty();
+        }
+
+        // the beams are done when the best one cannot exceed the worst finished one
+        let mut scores: Vec<_> = finished
+            .iter()
+            .map(|hypothesis| hypothesis.normalized_score(length_penalty))
+            .collect();
+        scores.sort_unstable_by(|a, b| b.total_cmp(a));
+        let worst = scores[num_beams - 1];
+
+        self.active
+            .iter()
+            .filter(|hypothesis| hypothesis.input == input)
+            .map(|hypothesis| hypothesis.normalized_score(length_penalty))
+            .fold(f32::NEG_INFINITY, f32::max)
+            <= worst
+    }
+}
+
+/// Finds the tokens completing the n-grams already in the sequence.
+#[cfg(feature = "generation")]
+fn find_banned_tokens(tokens: &[i64], ngram_size: usize) -> Vec<i64> {
+    if ngram_size == 0 || tokens.len() + 1 < ngram_size {
+        return vec![];
+    }
+
+    let prefix = &tokens[tokens.len() + 1 - ngram_size..];
+    tokens
+        .windows(ngram_size)
+        .filter(|ngram| &ngram[..ngram_size - 1] == prefix)
+        .map(|ngram| ngram[ngram_size - 1])
+        .collect()
+}
+
+#[cfg(feature = "generation")]
+fn log_softmax(logits: &[f32]) -> Vec<f32> {
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let sum: f32 = logits.iter().map(|logit| (logit - max).exp()).sum();
+    let log_sum = max + sum.ln();
+    logits.iter().map(|logit| logit - log_sum).collect()
+}
+
+#[cfg(feature = "generation")]
+fn argmax(logprobs: &[f32]) -> usize {
+    logprobs
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(token, _)| token)
+        .unwrap_or_default()
+}
+
+#[cfg(feature = "generation")]
+fn sample(rng: &mut StdRng, sampling: &Sampling, logprobs: &[f32]) -> usize {
+    let mut tokens: Vec<_> = (0..logprobs.len())
+        .filter(|&token| logprobs[token].is_finite())
+        .collect();
+    tokens.sort_unstable_by(|&a, &b| logprobs[b].total_cmp(&logprobs[a]));
+
+    // filter the candidates
+    if sampling.top_k > 0 {
+        tokens.truncate(sampling.top_k);
+    }
+    if sampling.top_p < 1.0 {
+        let mut total = 0.0;
+        let len = tokens
+            .iter()
+            .take_while(|&&token| {
+                let reached = total >= sampling.top_p;
+                total += logprobs[token].exp();
+                !reached
+            })
+            .count();
+        tokens.truncate(len.max(1));
+    }
+
+    let total: f32 = tokens.iter().map(|&token| logprobs[token].exp()).sum();
+    let mut threshold = rng.gen::<f32>() * total;
+    for &token in &tokens {
+        threshold -= logprobs[token].exp();
+        if threshold <= 0.0 {
+            return token;
+        }
+    }
+    tokens.first().copied().unwrap_or_else(|| argmax(logprobs))
+}
+
+#[cfg(all(test, feature = "generation"))]
+mod tests {
+    use super::*;
+
+    const TOKEN_EOS: i64 = 1;
+
+    /// Generates from the logits given by the last token.
+    fn generate(config: &GenerationConfig, logits: impl Fn(i64) -> [f32; 4]) -> Vec<i64> {
+        let mut generator = Generator::new(config, vec![vec![0]], TOKEN_EOS).unwrap();
+        while !generator.is_done() {
+            let rows: Vec<_> = generator
+                .active()
+                .iter()
+                .flat_map(|hypothesis| logits(*hypothesis.tokens.last().unwrap()))
+                .collect();
+            let rows = ndarray::Array2::from_shape_vec((rows.len() / 4, 4), rows).unwrap();
+            generator.step(rows.view()).unwrap();
+        }
+        generator.finish()[0].generated().to_vec()
+    }
+
+    /// Prefers `2` at first, but `3` leads to the more probable sequence.
+    fn logits(last: i64) -> [f32; 4] {
+        let inf = f32::NEG_INFINITY;
+        match last {
+            0 => [inf, inf, 1.0, 0.9],
+            2 => [inf, 0.0, -0.1, -0.1],
+            _ => [inf, 10.0, inf, inf],
+        }
+    }
+
+    #[test]
+    fn beam_search_finds_the_more_probable_sequence() {
+        let greedy = generate(&Default::default(), logits);
+        assert_eq!(greedy, [2, 1]);
+
+        let config = GenerationConfig {
+            num_beams: 2,
+            ..Default::default()
+        };
+        assert_eq!(generate(&config, logits), [3, 1]);
+    }
+
+    #[test]
+    fn generation_stops_at_max_new_tokens() {
+        let config = GenerationConfig {
+            max_new_tokens: 4,
+            ..Default::default()
+        };
+        let generated = generate(&config, |_| [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(generated, [3, 3, 3, 3]);
+    }
+
+    #[test]
+    fn repeated_ngrams_are_banned() {
+        let config = GenerationConfig {
+            no_repeat_ngram_size: 1,
+            ..Default::default()
+        };
+        let generated = generate(&config, |_| [1.0, 0.0, 2.0, 3.0]);
+        assert_eq!(generated, [3, 2, 1]);
+    }
+
+    #[test]
+    fn every_input_has_a_hypothesis() {
+        let prefixes = vec![vec![0], vec![0, 2]];
+        let generator = Generator::new(&Default::default(), prefixes, TOKEN_EOS).unwrap();
+        let hypotheses = generator.finish();
+        assert_eq!(hypotheses.len(), 2);
+        for (input, hypothesis) in hypotheses.iter().enumerate() {
+            assert_eq!(hypothesis.input, input);
+            assert!(hypothesis.generated().is_empty());
+        }
+    }
+}
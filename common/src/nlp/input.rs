@@ -1,12 +1,23 @@
 use std::collections::HashMap;
 
 use ipis::core::{
+    anyhow::{bail, Result},
     ndarray,
     signed::IsSigned,
-    value::text::{LanguageTag, Text},
+    value::{
+        array::Array,
+        text::{LanguageTag, Text},
+    },
 };
 
-use crate::tensor::ToTensor;
+use crate::{
+    model::Model,
+    nlp::{
+        tensor::StringTensorData,
+        tokenizer::{Encoding, Tokenizer},
+    },
+    tensor::{shape::Shape, TensorData, ToTensor},
+};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct QAInputs {
@@ -16,33 +27,37 @@ pub struct QAInputs {
 
 impl IsSigned for QAInputs {}
 
-#[cfg(feature = "rust_tokenizers")]
 impl QAInputs {
-    pub fn tokenize<T, V>(self, tokenizer: &T) -> ::ipis::core::anyhow::Result<Tokenized>
+    pub fn tokenize<T>(self, tokenizer: &T) -> Result<Tokenized>
     where
-        T: ::rust_tokenizers::tokenizer::Tokenizer<V>,
-        V: ::rust_tokenizers::vocab::Vocab,
+        T: Tokenizer + ?Sized,
     {
-        tokenize(tokenizer, self.into_vec(), true)
+        tokenize(tokenizer, self.into(), &Default::default(), true)
     }
 
-    pub fn tokenize_without_tensors<T, V>(
-        self,
-        tokenizer: &T,
-    ) -> ::ipis::core::anyhow::Result<Tokenized>
+    pub fn tokenize_without_tensors<T>(self, tokenizer: &T) -> Result<Tokenized>
     where
-        T: ::rust_tokenizers::tokenizer::Tokenizer<V>,
-        V: ::rust_tokenizers::vocab::Vocab,
+        T: Tokenizer + ?Sized,
     {
-        tokenize(tokenizer, self.into_vec(), false)
+        tokenize(tokenizer, self.into(), &Default::default(), false)
     }
 
-    fn into_vec(self) -> Vec<GenericInput> {
+    pub fn tokenize_with<T>(self, tokenizer: &T, options: &TokenizeOptions) -> Result<Tokenized>
+    where
+        T: Tokenizer + ?Sized,
+    {
+        tokenize(tokenizer, self.into(), options, true)
+    }
+}
+
+impl From<QAInputs> for Vec<GenericInput> {
+    fn from(value: QAInputs) -> Self {
         use ipis::itertools::Itertools;
 
-        self.query
+        value
+            .query
             .into_iter()
-            .cartesian_product(self.context.into_iter())
+            .cartesian_product(value.context)
             .map(|(text_2, text_1)| GenericInput {
                 text_1,
                 text_2: Some(text_2),
@@ -59,33 +74,37 @@ pub struct SCInputs {
 
 impl IsSigned for SCInputs {}
 
-#[cfg(feature = "rust_tokenizers")]
 impl SCInputs {
-    pub fn tokenize<T, V>(self, tokenizer: &T) -> ::ipis::core::anyhow::Result<Tokenized>
+    pub fn tokenize<T>(self, tokenizer: &T) -> Result<Tokenized>
     where
-        T: ::rust_tokenizers::tokenizer::Tokenizer<V>,
-        V: ::rust_tokenizers::vocab::Vocab,
+        T: Tokenizer + ?Sized,
     {
-        tokenize(tokenizer, self.into_vec(), true)
+        tokenize(tokenizer, self.into(), &Default::default(), true)
     }
 
-    pub fn tokenize_without_tensors<T, V>(
-        self,
-        tokenizer: &T,
-    ) -> ::ipis::core::anyhow::Result<Tokenized>
+    pub fn tokenize_without_tensors<T>(self, tokenizer: &T) -> Result<Tokenized>
+    where
+        T: Tokenizer + ?Sized,
+    {
+        tokenize(tokenizer, self.into(), &Default::default(), false)
+    }
+
+    pub fn tokenize_with<T>(self, tokenizer: &T, options: &TokenizeOptions) -> Result<Tokenized>
     where
-        T: ::rust_tokenizers::tokenizer::Tokenizer<V>,
-        V: ::rust_tokenizers::vocab::Vocab,
+        T: Tokenizer + ?Sized,
     {
-        tokenize(tokenizer, self.into_vec(), false)
+        tokenize(tokenizer, self.into(), options, true)
     }
+}
 
-    fn into_vec(self) -> Vec<GenericInput> {
+impl From<SCInputs> for Vec<GenericInput> {
+    fn from(value: SCInputs) -> Self {
         use ipis::itertools::Itertools;
 
-        self.query
+        value
+            .query
             .into_iter()
-            .cartesian_product(self.context.into_iter())
+            .cartesian_product(value.context)
             .map(|(text_2, text_1)| GenericInput {
                 text_1,
                 text_2: Some(text_2),
@@ -94,6 +113,139 @@ impl SCInputs {
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextInputs {
+    pub text: Vec<String>,
+}
+
+impl IsSigned for TextInputs {}
+
+impl TextInputs {
+    pub fn tokenize<T>(self, tokenizer: &T) -> Result<Tokenized>
+    where
+        T: Tokenizer + ?Sized,
+    {
+        tokenize(tokenizer, self.into(), &Default::default(), true)
+    }
+
+    pub fn tokenize_without_tensors<T>(self, tokenizer: &T) -> Result<Tokenized>
+    where
+        T: Tokenizer + ?Sized,
+    {
+        tokenize(tokenizer, self.into(), &Default::default(), false)
+    }
+
+    pub fn tokenize_with<T>(self, tokenizer: &T, options: &TokenizeOptions) -> Result<Tokenized>
+    where
+        T: Tokenizer + ?Sized,
+    {
+        tokenize(tokenizer, self.into(), options, true)
+    }
+}
+
+impl From<TextInputs> for Vec<GenericInput> {
+    fn from(value: TextInputs) -> Self {
+        value
+            .text
+            .into_iter()
+            .map(|text_1| GenericInput {
+                text_1,
+                text_2: None,
+            })
+            .collect()
+    }
+}
+
+/// The independent text pairs, each of which places the first text before the second one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PairInputs {
+    pub pairs: Vec<(String, String)>,
+}
+
+impl IsSigned for PairInputs {}
+
+impl PairInputs {
+    pub fn tokenize<T>(self, tokenizer: &T) -> Result<Tokenized>
+    where
+        T: Tokenizer + ?Sized,
+    {
+        tokenize(tokenizer, self.into(), &Default::default(), true)
+    }
+
+    pub fn tokenize_without_tensors<T>(self, tokenizer: &T) -> Result<Tokenized>
+    where
+        T: Tokenizer + ?Sized,
+    {
+        tokenize(tokenizer, self.into(), &Default::default(), false)
+    }
+
+    pub fn tokenize_with<T>(self, tokenizer: &T, options: &TokenizeOptions) -> Result<Tokenized>
+    where
+        T: Tokenizer + ?Sized,
+    {
+        tokenize(tokenizer, self.into(), options, true)
+    }
+}
+
+impl From<PairInputs> for Vec<GenericInput> {
+    fn from(value: PairInputs) -> Self {
+        value
+            .pairs
+            .into_iter()
+            .map(|(text_1, text_2)| GenericInput {
+                text_1,
+                text_2: Some(text_2),
+            })
+            .collect()
+    }
+}
+
+/// The candidate passages of a query, each of which is paired with the query.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RerankInputs {
+    pub query: String,
+    pub passages: Vec<String>,
+}
+
+impl IsSigned for RerankInputs {}
+
+impl RerankInputs {
+    pub fn tokenize<T>(self, tokenizer: &T) -> Result<Tokenized>
+    where
+        T: Tokenizer + ?Sized,
+    {
+        tokenize(tokenizer, self.into(), &Default::default(), true)
+    }
+
+    pub fn tokenize_without_tensors<T>(self, tokenizer: &T) -> Result<Tokenized>
+    where
+        T: Tokenizer + ?Sized,
+    {
+        tokenize(tokenizer, self.into(), &Default::default(), false)
+    }
+
+    pub fn tokenize_with<T>(self, tokenizer: &T, options: &TokenizeOptions) -> Result<Tokenized>
+    where
+        T: Tokenizer + ?Sized,
+    {
+        tokenize(tokenizer, self.into(), options, true)
+    }
+}
+
+impl From<RerankInputs> for Vec<GenericInput> {
+    fn from(value: RerankInputs) -> Self {
+        let query = value.query;
+        value
+            .passages
+            .into_iter()
+            .map(|text_2| GenericInput {
+                text_1: query.clone(),
+                text_2: Some(text_2),
+            })
+            .collect()
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct TranslationInputs {
     pub context: Vec<Text>,
@@ -102,29 +254,33 @@ pub struct TranslationInputs {
 
 impl IsSigned for TranslationInputs {}
 
-#[cfg(feature = "rust_tokenizers")]
 impl TranslationInputs {
-    pub fn tokenize<T, V>(self, tokenizer: &T) -> ::ipis::core::anyhow::Result<Tokenized>
+    pub fn tokenize<T>(self, tokenizer: &T) -> Result<Tokenized>
     where
-        T: ::rust_tokenizers::tokenizer::Tokenizer<V>,
-        V: ::rust_tokenizers::vocab::Vocab,
+        T: Tokenizer + ?Sized,
     {
-        tokenize(tokenizer, self.into_vec(), true)
+        tokenize(tokenizer, self.into(), &Default::default(), true)
     }
 
-    pub fn tokenize_without_tensors<T, V>(
-        self,
-        tokenizer: &T,
-    ) -> ::ipis::core::anyhow::Result<Tokenized>
+    pub fn tokenize_without_tensors<T>(self, tokenizer: &T) -> Result<Tokenized>
     where
-        T: ::rust_tokenizers::tokenizer::Tokenizer<V>,
-        V: ::rust_tokenizers::vocab::Vocab,
+        T: Tokenizer + ?Sized,
     {
-        tokenize(tokenizer, self.into_vec(), false)
+        tokenize(tokenizer, self.into(), &Default::default(), false)
     }
 
-    fn into_vec(self) -> Vec<GenericInput> {
-        self.context
+    pub fn tokenize_with<T>(self, tokenizer: &T, options: &TokenizeOptions) -> Result<Tokenized>
+    where
+        T: Tokenizer + ?Sized,
+    {
+        tokenize(tokenizer, self.into(), options, true)
+    }
+}
+
+impl From<TranslationInputs> for Vec<GenericInput> {
+    fn from(value: TranslationInputs) -> Self {
+        value
+            .context
             .into_iter()
             .map(|text_1| GenericInput {
                 text_1: text_1.to_string(),
@@ -142,126 +298,207 @@ pub struct GenericInput {
 
 impl IsSigned for GenericInput {}
 
-#[cfg(feature = "rust_tokenizers")]
 impl GenericInput {
-    pub fn tokenize<T, V>(self, tokenizer: &T) -> ::ipis::core::anyhow::Result<Tokenized>
+    pub fn tokenize<T>(self, tokenizer: &T) -> Result<Tokenized>
+    where
+        T: Tokenizer + ?Sized,
+    {
+        tokenize(tokenizer, vec![self], &Default::default(), true)
+    }
+
+    pub fn tokenize_without_tensors<T>(self, tokenizer: &T) -> Result<Tokenized>
+    where
+        T: Tokenizer + ?Sized,
+    {
+        tokenize(tokenizer, vec![self], &Default::default(), false)
+    }
+
+    pub fn tokenize_with<T>(self, tokenizer: &T, options: &TokenizeOptions) -> Result<Tokenized>
     where
-        T: ::rust_tokenizers::tokenizer::Tokenizer<V>,
-        V: ::rust_tokenizers::vocab::Vocab,
+        T: Tokenizer + ?Sized,
     {
-        tokenize(tokenizer, vec![self], true)
+        tokenize(tokenizer, vec![self], options, true)
     }
 
-    pub fn tokenize_without_tensors<T, V>(
-        self,
+    pub fn tokenize_batch<T>(inputs: Vec<Self>, tokenizer: &T) -> Result<Tokenized>
+    where
+        T: Tokenizer + ?Sized,
+    {
+        tokenize(tokenizer, inputs, &Default::default(), true)
+    }
+
+    pub fn tokenize_batch_without_tensors<T>(inputs: Vec<Self>, tokenizer: &T) -> Result<Tokenized>
+    where
+        T: Tokenizer + ?Sized,
+    {
+        tokenize(tokenizer, inputs, &Default::default(), false)
+    }
+
+    pub fn tokenize_batch_with<T>(
+        inputs: Vec<Self>,
         tokenizer: &T,
-    ) -> ::ipis::core::anyhow::Result<Tokenized>
+        options: &TokenizeOptions,
+    ) -> Result<Tokenized>
     where
-        T: ::rust_tokenizers::tokenizer::Tokenizer<V>,
-        V: ::rust_tokenizers::vocab::Vocab,
+        T: Tokenizer + ?Sized,
     {
-        tokenize(tokenizer, vec![self], false)
+        tokenize(tokenizer, inputs, options, true)
+    }
+
+    pub fn tokenize_batch_without_tensors_with<T>(
+        inputs: Vec<Self>,
+        tokenizer: &T,
+        options: &TokenizeOptions,
+    ) -> Result<Tokenized>
+    where
+        T: Tokenizer + ?Sized,
+    {
+        tokenize(tokenizer, inputs, options, false)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Padding {
+    #[default]
+    Right,
+    Left,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TruncationStrategy {
+    /// Removes the tokens from the longer text of the pair first.
+    #[default]
+    LongestFirst,
+    OnlyFirst,
+    OnlySecond,
+    /// Fails if the inputs exceed the maximum length.
+    DoNotTruncate,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TokenizeOptions {
+    pub padding: Padding,
+    /// The maximum number of tokens, including the special tokens.
+    pub max_length: Option<usize>,
+    pub truncation: TruncationStrategy,
+    /// Splits the overflowing text into the overlapping windows sharing `stride` tokens,
+    /// instead of dropping the truncated tokens.
+    pub stride: Option<usize>,
+}
+
+impl TokenizeOptions {
+    /// Limits the length to the fixed sequence length of the model's `input_ids`, if any.
+    pub fn with_model(self, model: &Model) -> Self {
+        match model.inputs.iter().find(|shape| shape.name == "input_ids") {
+            Some(shape) => self.with_shape(shape),
+            None => self,
+        }
+    }
+
+    pub fn with_shape(mut self, shape: &Shape) -> Self {
+        if let Some(Some(max_length)) = shape.to_vec().get(1) {
+            self.max_length = Some(match self.max_length {
+                Some(limit) => limit.min(*max_length),
+                None => *max_length,
+            });
+        }
+        self
     }
 }
 
 pub struct Tokenized {
     pub input_ids: ndarray::Array<i64, ndarray::Ix2>,
+    /// Marks the real tokens with `1` and the padding with `0`.
+    pub attention_mask: ndarray::Array<i64, ndarray::Ix2>,
     pub inputs: HashMap<String, Box<dyn ToTensor + Send + Sync>>,
     pub inputs_str: Vec<GenericInput>,
+    /// The unpadded encodings of each row, including the token offsets.
+    pub encodings: Vec<Encoding>,
+    /// The index of the input of each row, as an input may be split into multiple windows.
+    pub sample_indices: Vec<usize>,
+    /// The number of removed tokens of each input, which is `0` unless truncated.
+    pub num_truncated_tokens: Vec<usize>,
 }
 
 impl IsSigned for Tokenized {}
 
-#[cfg(feature = "rust_tokenizers")]
-fn tokenize<T, V>(
+impl Tokenized {
+    /// Returns the indices of the truncated inputs.
+    pub fn truncated(&self) -> impl Iterator<Item = usize> + '_ {
+        self.num_truncated_tokens
+            .iter()
+            .enumerate()
+            .filter(|(_, &num_truncated_tokens)| num_truncated_tokens > 0)
+            .map(|(index, _)| index)
+    }
+}
+
+fn tokenize<T>(
     tokenizer: &T,
     inputs_str: Vec<GenericInput>,
+    options: &TokenizeOptions,
     to_tensor: bool,
-) -> ::ipis::core::anyhow::Result<Tokenized>
+) -> Result<Tokenized>
 where
-    T: ::rust_tokenizers::tokenizer::Tokenizer<V>,
-    V: ::rust_tokenizers::vocab::Vocab,
+    T: Tokenizer + ?Sized,
 {
-    use ipis::core::{anyhow::bail, value::array::Array};
-    use rust_tokenizers::{tokenizer::TruncationStrategy, TokenizedInput};
-
-    use crate::{nlp::tensor::StringTensorData, tensor::TensorData};
-
-    fn collect_encode_batch<T>(
-        encodings: &[TokenizedInput],
+    fn collect_encode_batch(
+        encodings: &[Encoding],
         max_len: usize,
-        f: impl Fn(&TokenizedInput) -> &[T],
-    ) -> ::ipis::core::anyhow::Result<ndarray::Array<i64, ndarray::Ix2>>
-    where
-        T: Copy + Into<i64>,
-    {
-        let arrays: Vec<_> = encodings
+        padding: Padding,
+        pad_id: i64,
+        f: impl Fn(&Encoding) -> Vec<i64>,
+    ) -> Result<ndarray::Array<i64, ndarray::Ix2>> {
+        let values = encodings
             .iter()
-            .map(|encoding| {
-                f(encoding)
-                    .iter()
-                    .copied()
-                    .map(Into::into)
-                    .collect::<Vec<_>>()
-            })
-            .map(|mut input| {
-                input.extend([0].repeat(max_len - input.len()));
-                input
+            .flat_map(|encoding| {
+                let mut values = f(encoding);
+                let pads = vec![pad_id; max_len - values.len()];
+                match padding {
+                    Padding::Right => {
+                        values.extend(pads);
+                        values
+                    }
+                    Padding::Left => [pads, values].concat(),
+                }
             })
-            .map(ndarray::Array::from)
-            .map(|input| {
-                let length = input.len();
-                input.into_shape((1, length))
-            })
-            .collect::<Result<_, _>>()?;
+            .collect();
 
-        let arrays: Vec<_> = arrays.iter().map(|array| array.view()).collect();
-        ndarray::concatenate(ndarray::Axis(0), &arrays).map_err(Into::into)
+        ndarray::Array::from_shape_vec((encodings.len(), max_len), values).map_err(Into::into)
     }
 
-    let max_len = inputs_str
-        .iter()
-        .map(|input| {
-            input
-                .text_1
-                .len()
-                .max(input.text_2.as_ref().map(|e| e.len()).unwrap_or(0))
-        })
-        .max()
-        .unwrap_or(0);
-
-    let inputs_1: Vec<_> = inputs_str
-        .iter()
-        .map(|input| input.text_1.as_str())
-        .collect();
-    let inputs_2: Vec<_> = inputs_str
-        .iter()
-        .filter_map(|input| input.text_2.as_deref())
-        .collect();
-
-    if !inputs_2.is_empty() && inputs_1.len() != inputs_2.len() {
+    // the text pairs should not be mixed with the single texts
+    if inputs_str
+        .windows(2)
+        .any(|pair| pair[0].text_2.is_some() != pair[1].text_2.is_some())
+    {
         bail!("failed to parse the text pairs");
     }
 
-    let encodings = if inputs_2.is_empty() {
-        tokenizer.encode_list(&inputs_1, max_len, &TruncationStrategy::LongestFirst, 0)
-    } else {
-        let inputs_pair: Vec<_> = inputs_1.into_iter().zip(inputs_2.into_iter()).collect();
-
-        tokenizer.encode_pair_list(&inputs_pair, max_len, &TruncationStrategy::LongestFirst, 0)
-    };
-    let input_lens: Vec<_> = encodings
+    let mut encodings = vec![];
+    let mut sample_indices = vec![];
+    let mut num_truncated_tokens = vec![];
+    for (index, input) in inputs_str.iter().enumerate() {
+        let (windows, num_truncated) = encode(tokenizer, input, options)?;
+        sample_indices.extend(vec![index; windows.len()]);
+        encodings.extend(windows);
+        num_truncated_tokens.push(num_truncated);
+    }
+    let max_len = encodings
         .iter()
-        .map(|encoding| encoding.token_ids.len())
-        .collect();
-    let max_len = input_lens.iter().max().copied().unwrap_or(0);
+        .map(|encoding| encoding.len())
+        .max()
+        .unwrap_or(0);
 
-    let input_ids = collect_encode_batch(&encodings, max_len, |encoding| &encoding.token_ids)?;
+    let collect = |pad_id, f: &dyn Fn(&Encoding) -> Vec<i64>| {
+        collect_encode_batch(&encodings, max_len, options.padding, pad_id, f)
+    };
+    let pad_id = tokenizer.special_tokens().pad.unwrap_or(0);
+    let input_ids = collect(pad_id, &|encoding| encoding.ids.clone())?;
+    let attention_mask = collect(0, &|encoding| vec![1; encoding.len()])?;
 
     let inputs = if to_tensor {
-        let attention_mask = ndarray::Array::ones(input_ids.dim());
-        let token_type_ids =
-            collect_encode_batch(&encodings, max_len, |encoding| &encoding.segment_ids)?;
+        let token_type_ids = collect(0, &|encoding| encoding.type_ids.clone())?;
 
         vec![
             (
@@ -273,7 +510,7 @@ where
             (
                 "attention_mask".to_string(),
                 Box::new(TensorData::from(StringTensorData::I64(Array(
-                    attention_mask.into(),
+                    attention_mask.clone().into(),
                 )))) as Box<dyn ToTensor + Send + Sync>,
             ),
             (
@@ -291,7 +528,306 @@ where
 
     Ok(Tokenized {
         input_ids,
+        attention_mask,
         inputs,
         inputs_str,
+        encodings,
+        sample_indices,
+        num_truncated_tokens,
     })
 }
+
+/// Encodes the input into the window(s) with the special tokens,
+/// returning the number of the truncated tokens.
+fn encode<T>(
+    tokenizer: &T,
+    input: &GenericInput,
+    options: &TokenizeOptions,
+) -> Result<(Vec<Encoding>, usize)>
+where
+    T: Tokenizer + ?Sized,
+{
+    let mut first = tokenizer.encode(&input.text_1)?;
+    let mut second = input
+        .text_2
+        .as_deref()
+        .map(|text| tokenizer.encode(text))
+        .transpose()?;
+
+    // count the tokens to be removed
+    let len_first = first.len();
+    let len_second = second.as_ref().map(Encoding::len).unwrap_or(0);
+    let num_tokens = len_first + len_second + tokenizer.num_special_tokens(second.is_some())?;
+    let num_truncated_tokens = match options.max_length {
+        Some(max_length) => num_tokens.saturating_sub(max_length),
+        None => 0,
+    };
+
+    if num_truncated_tokens > 0 {
+        if let Some(stride) = options.stride {
+            let windows = split_windows(first, second, num_truncated_tokens, stride, options)?;
+            return windows
+                .into_iter()
+                .map(|(first, second)| tokenizer.build_inputs(first, second))
+                .collect::<Result<_>>()
+                .map(|windows| (windows, 0));
+        }
+
+        let (len_first, len_second) = match (options.truncation, &second) {
+            (TruncationStrategy::LongestFirst, _) => {
+                let (mut len_first, mut len_second) = (len_first, len_second);
+                for _ in 0..num_truncated_tokens {
+                    if len_first > len_second {
+                        len_first -= 1;
+                    } else if len_second > 0 {
+                        len_second -= 1;
+                    } else {
+                        bail!("the special tokens exceed the maximum length");
+                    }
+                }
+                (len_first, len_second)
+            }
+            (TruncationStrategy::OnlyFirst, _) if len_first >= num_truncated_tokens => {
+                (len_first - num_truncated_tokens, len_second)
+            }
+            (TruncationStrategy::OnlySecond, Some(_)) if len_second >= num_truncated_tokens => {
+                (len_first, len_second - num_truncated_tokens)
+            }
+            (strategy, _) => {
+                bail!("failed to truncate {num_tokens} tokens into the maximum length with {strategy:?}")
+            }
+        };
+
+        first.truncate(len_first);
+        if let Some(second) = &mut second {
+            second.truncate(len_second);
+        }
+    }
+
+    let encoding = tokenizer.build_inputs(first, second)?;
+    Ok((vec![encoding], num_truncated_tokens))
+}
+
+/// Splits the overflowing text into the overlapping windows, keeping the other text as it is.
+fn split_windows(
+    first: Encoding,
+    second: Option<Encoding>,
+    num_truncated_tokens: usize,
+    stride: usize,
+    options: &TokenizeOptions,
+) -> Result<Vec<(Encoding, Option<Encoding>)>> {
+    let split_first = match (options.truncation, &second) {
+        (TruncationStrategy::LongestFirst, None) | (TruncationStrategy::OnlyFirst, _) => true,
+        (TruncationStrategy::OnlySecond, Some(_)) => false,
+        (strategy, _) => bail!("failed to split the inputs into the windows with {strategy:?}"),
+    };
+    let (text, other) = match (split_first, second) {
+        (true, second) => (first, second),
+        (false, second) => (second.unwrap(), Some(first)),
+    };
+
+    let len = text.len();
+    let window = match len.checked_sub(num_truncated_tokens) {
+        Some(window) if window > stride => window,
+        _ => bail!("the stride should be less than the window size"),
+    };
+
+    let mut windows = vec![];
+    let mut begin = 0;
+    loop {
+        let end = (begin + window).min(len);
+        let text = text.slice(begin..end);
+        windows.push(match (split_first, other.clone()) {
+            (true, other) => (text, other),
+            (false, other) => (other.unwrap(), Some(text)),
+        });
+
+        if end == len {
+            break Ok(windows);
+        }
+        begin = end - stride;
+    }
+}
+
+#[cfg(all(test, feature = "rust_tokenizers"))]
+mod tests {
+    use std::collections::HashMap;
+
+    use rust_tokenizers::{
+        tokenizer::BertTokenizer,
+        vocab::{BertVocab, Vocab},
+    };
+
+    use super::*;
+
+    fn tokenizer() -> BertTokenizer {
+        let tokens = [
+            "[UNK]", "[CLS]", "[SEP]", "[MASK]", "[PAD]", "the", "quick", "brown", "fox", "jumps",
+            "hello", "world",
+        ];
+
+        let values: HashMap<_, _> = tokens
+            .iter()
+            .enumerate()
+            .map(|(id, token)| (token.to_string(), id as i64))
+            .collect();
+        let special_values: HashMap<_, _> = tokens[..5]
+            .iter()
+            .map(|token| (token.to_string(), values[*token]))
+            .collect();
+
+        let swap = |values: &HashMap<String, i64>| {
+            values
+                .iter()
+                .map(|(token, id)| (*id, token.clone()))
+                .collect()
+        };
+        let vocab = BertVocab {
+            indices: swap(&values),
+            special_indices: swap(&special_values),
+            values,
+            special_values,
+            unknown_value: BertVocab::unknown_value(),
+        };
+        BertTokenizer::from_existing_vocab(vocab, true, true)
+    }
+
+    fn inputs() -> TextInputs {
+        TextInputs {
+            text: vec!["The quick brown fox jumps".into(), "Hello world".into()],
+        }
+    }
+
+    /// Returns the token ids of each row, skipping the padding.
+    fn unpad(tokenized: &Tokenized) -> Vec<Vec<i64>> {
+        tokenized
+            .input_ids
+            .rows()
+            .into_iter()
+            .zip(tokenized.attention_mask.rows())
+            .map(|(input_ids, attention_mask)| {
+                input_ids
+                    .iter()
+                    .zip(attention_mask)
+                    .filter(|(_, &mask)| mask == 1)
+                    .map(|(&id, _)| id)
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn assert_batched_matches_unbatched(padding: Padding) {
+        let tokenizer = tokenizer();
+        let options = TokenizeOptions {
+            padding,
+            ..Default::default()
+        };
+
+        let batched = inputs().tokenize_with(&tokenizer, &options).unwrap();
+        let unbatched: Vec<_> = inputs()
+            .text
+            .into_iter()
+            .map(|text| {
+                TextInputs { text: vec![text] }
+                    .tokenize_with(&tokenizer, &options)
+                    .unwrap()
+            })
+            .collect();
+
+        for (row, unbatched) in unpad(&batched).into_iter().zip(&unbatched) {
+            assert_eq!(unbatched.attention_mask.iter().min(), Some(&1));
+            assert_eq!(row, unbatched.input_ids.row(0).to_vec());
+        }
+    }
+
+    #[test]
+    fn batched_matches_unbatched_with_right_padding() {
+        assert_batched_matches_unbatched(Padding::Right);
+    }
+
+    #[test]
+    fn batched_matches_unbatched_with_left_padding() {
+        assert_batched_matches_unbatched(Padding::Left);
+    }
+
+    #[test]
+    fn padding_uses_pad_token() {
+        let tokenizer = tokenizer();
+        let pad_id = 4;
+
+        let right = inputs().tokenize(&tokenizer).unwrap();
+        assert_eq!(
+            right.input_ids.row(1).to_vec(),
+            [1, 10, 11, 2, pad_id, pad_id, pad_id]
+        );
+        assert_eq!(right.attention_mask.row(1).to_vec(), [1, 1, 1, 1, 0, 0, 0]);
+
+        let options = TokenizeOptions {
+            padding: Padding::Left,
+            ..Default::default()
+        };
+        let left = inputs().tokenize_with(&tokenizer, &options).unwrap();
+        assert_eq!(
+            left.input_ids.row(1).to_vec(),
+            [pad_id, pad_id, pad_id, 1, 10, 11, 2]
+        );
+        assert_eq!(left.attention_mask.row(1).to_vec(), [0, 0, 0, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn truncation_reports_truncated_inputs() {
+        let tokenizer = tokenizer();
+        let options = TokenizeOptions {
+            max_length: Some(5),
+            ..Default::default()
+        };
+
+        let tokenized = inputs().tokenize_with(&tokenizer, &options).unwrap();
+        assert_eq!(tokenized.input_ids.row(0).to_vec(), [1, 5, 6, 7, 2]);
+        assert_eq!(tokenized.num_truncated_tokens, [2, 0]);
+        assert_eq!(tokenized.truncated().collect::<Vec<_>>(), [0]);
+
+        let options = TokenizeOptions {
+            truncation: TruncationStrategy::DoNotTruncate,
+            ..options
+        };
+        assert!(inputs().tokenize_with(&tokenizer, &options).is_err());
+    }
+
+    #[test]
+    fn truncation_removes_the_longest_first() {
+        let tokenizer = tokenizer();
+        let inputs = PairInputs {
+            pairs: vec![("The quick brown fox".into(), "Hello world".into())],
+        };
+        let options = TokenizeOptions {
+            max_length: Some(6),
+            ..Default::default()
+        };
+
+        let tokenized = inputs.tokenize_with(&tokenizer, &options).unwrap();
+        assert_eq!(tokenized.input_ids.row(0).to_vec(), [1, 5, 6, 2, 10, 2]);
+        assert_eq!(tokenized.num_truncated_tokens, [3]);
+    }
+
+    #[test]
+    fn stride_splits_into_overlapping_windows() {
+        let tokenizer = tokenizer();
+        let options = TokenizeOptions {
+            max_length: Some(5),
+            stride: Some(1),
+            ..Default::default()
+        };
+
+        let tokenized = inputs().tokenize_with(&tokenizer, &options).unwrap();
+        assert_eq!(
+            unpad(&tokenized),
+            [vec![1, 5, 6, 7, 2], vec![1, 7, 8, 9, 2], vec![1, 10, 11, 2]],
+        );
+        assert_eq!(tokenized.sample_indices, [0, 0, 1]);
+        assert_eq!(tokenized.num_truncated_tokens, [0, 0]);
+
+        // the offsets are kept in the source text
+        assert_eq!(tokenized.encodings[1].offsets[1], Some((10, 15)));
+    }
+}
@@ -0,0 +1,74 @@
+/// Finds the ISO 639-1 code, the ISO 639-3 code and the default script of the language,
+/// given either of the codes.
+pub fn find_language(language: &str) -> Option<(&'static str, &'static str, &'static str)> {
+    LANGUAGES
+        .iter()
+        .find(|(iso_639_1, iso_639_3, _)| *iso_639_1 == language || *iso_639_3 == language)
+        .copied()
+}
+
+/// The ISO 639-1 code, the ISO 639-3 code and the default script of each language,
+/// following the language codes of NLLB, e.g. `pes` for `fa`.
+pub const LANGUAGES: &[(&str, &str, &str)] = &[
+    ("ar", "arb", "Arab"),
+    ("bn", "ben", "Beng"),
+    ("cs", "ces", "Latn"),
+    ("da", "dan", "Latn"),
+    ("de", "deu", "Latn"),
+    ("el", "ell", "Grek"),
+    ("en", "eng", "Latn"),
+    ("es", "spa", "Latn"),
+    ("fa", "pes", "Arab"),
+    ("fi", "fin", "Latn"),
+    ("fr", "fra", "Latn"),
+    ("he", "heb", "Hebr"),
+    ("hi", "hin", "Deva"),
+    ("hu", "hun", "Latn"),
+    ("id", "ind", "Latn"),
+    ("it", "ita", "Latn"),
+    ("ja", "jpn", "Jpan"),
+    ("ko", "kor", "Hang"),
+    ("ms", "zsm", "Latn"),
+    ("nl", "nld", "Latn"),
+    ("no", "nob", "Latn"),
+    ("pl", "pol", "Latn"),
+    ("pt", "por", "Latn"),
+    ("ro", "ron", "Latn"),
+    ("ru", "rus", "Cyrl"),
+    ("sv", "swe", "Latn"),
+    ("sw", "swh", "Latn"),
+    ("ta", "tam", "Taml"),
+    ("th", "tha", "Thai"),
+    ("tr", "tur", "Latn"),
+    ("uk", "ukr", "Cyrl"),
+    ("ur", "urd", "Arab"),
+    ("vi", "vie", "Latn"),
+    ("zh", "zho", "Hans"),
+];
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    #[test]
+    fn languages_are_found_by_both_codes() {
+        assert_eq!(find_language("en"), Some(("en", "eng", "Latn")));
+        assert_eq!(find_language("eng"), Some(("en", "eng", "Latn")));
+        assert_eq!(find_language("pes"), Some(("fa", "pes", "Arab")));
+        assert_eq!(find_language("EN"), None);
+        assert_eq!(find_language("xx"), None);
+    }
+
+    #[test]
+    fn languages_are_unique() {
+        let mut iso_639_1 = BTreeSet::default();
+        let mut iso_639_3 = BTreeSet::default();
+        for &(code_1, code_3, script) in LANGUAGES {
+            assert!(code_1.len() == 2 && iso_639_1.insert(code_1), "{code_1}");
+            assert!(code_3.len() == 3 && iso_639_3.insert(code_3), "{code_3}");
+            assert_eq!(script.len(), 4);
+        }
+    }
+}
@@ -1,3 +1,13 @@
+pub mod cache;
+#[cfg(feature = "generation")]
+pub mod causal_lm;
+pub mod generation;
 pub mod input;
+pub mod lang;
 pub mod output;
+#[cfg(feature = "generation")]
+pub mod seq2seq;
+pub mod session;
+pub mod stream;
 pub mod tensor;
+pub mod tokenizer;
@@ -1,7 +1,12 @@
+#![allow(deprecated)]
+
 use bytecheck::CheckBytes;
 use ipis::core::signed::IsSigned;
 use rkyv::{Archive, Deserialize, Serialize};
 
+#[deprecated(
+    note = "use the named labels of `ipnis_modules_text_classification::labels::LabelSet`"
+)]
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Archive, Serialize, Deserialize)]
 #[archive(compare(PartialEq))]
 #[archive_attr(derive(CheckBytes, Copy, Clone, Debug, PartialEq, Eq, Hash))]
@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+
+use ipis::core::{
+    anyhow::{anyhow, bail, Result},
+    ndarray,
+    value::array::Array,
+};
+
+use crate::{
+    model::Model,
+    tensor::{dynamic::DynamicTensorData, Tensor, TensorData, ToTensor},
+    Ipnis,
+};
+
+use super::{
+    cache::KvCache,
+    generation::{GenerationConfig, Generator, Hypothesis},
+    tensor::StringTensorData,
+    tokenizer::SpecialTokens,
+};
+
+/// The split exports of a seq2seq model, which reuse the encoder outputs and the past key-values.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SplitModels<'a> {
+    /// Produces the `last_hidden_state` from the `input_ids` and the `attention_mask`.
+    pub encoder: &'a Model,
+    /// Starts decoding from the whole prefix, producing the `present.*` key-values.
+    pub decoder: &'a Model,
+    /// Decodes the new tokens only, consuming the `past_key_values.*`.
+    pub decoder_with_past: &'a Model,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Seq2SeqModels<'a> {
+    /// Runs the whole model at each step.
+    Single(&'a Model),
+    Split(SplitModels<'a>),
+}
+
+impl<'a> Seq2SeqModels<'a> {
+    /// Returns the model consuming the `input_ids` of the encoder.
+    pub fn encoder(&self) -> &'a Model {
+        match self {
+            Self::Single(model) => model,
+            Self::Split(models) => models.encoder,
+        }
+    }
+}
+
+/// Decodes the outputs of an encoder-decoder model step by step.
+pub struct Seq2Seq<'a, C: ?Sized> {
+    client: &'a C,
+    models: Seq2SeqModels<'a>,
+    state: Seq2SeqState,
+}
+
+/// The state of the decoding between the steps, which does not borrow the client and the models.
+pub struct Seq2SeqState {
+    input_ids: ndarray::Array2<i64>,
+    attention_mask: ndarray::Array2<i64>,
+    generator: Generator,
+    token_pad: i64,
+    /// The outputs of the encoder, which are computed at the first step.
+    encoder_hidden_states: Option<ndarray::ArcArray<f32, ndarray::IxDyn>>,
+    cache: KvCache,
+}
+
+impl Seq2SeqState {
+    /// Starts decoding each row of the encoder inputs from its decoder prefix.
+    pub fn new(
+        config: &GenerationConfig,
+        input_ids: ndarray::Array2<i64>,
+        attention_mask: ndarray::Array2<i64>,
+        prefixes: Vec<Vec<i64>>,
+        special_tokens: &SpecialTokens,
+    ) -> Result<Self> {
+        if prefixes.len() != input_ids.nrows() {
+            let expected = input_ids.nrows();
+            let given = prefixes.len();
+            bail!("unexpected decoder prefixes: Expected {expected}, Given {given}");
+        }
+
+        let token_eos = special_tokens
+            .eos
+            .ok_or_else(|| anyhow!("the tokenizer has no EOS token"))?;
+        let token_pad = special_tokens
+            .pad
+            .ok_or_else(|| anyhow!("the tokenizer has no padding token"))?;
+
+        Ok(Self {
+            input_ids,
+            attention_mask,
+            generator: Generator::new(config, prefixes, token_eos)?,
+            token_pad,
+            encoder_hidden_states: None,
+            cache: Default::default(),
+        })
+    }
+
+    /// Continues decoding with the client and the models.
+    pub fn resume<'a, C: ?Sized>(self, client: &'a C, models: Seq2SeqModels<'a>) -> Seq2Seq<'a, C> {
+        Seq2Seq {
+            client,
+            models,
+            state: self,
+        }
+    }
+
+    pub fn generator(&self) -> &Generator {
+        &self.generator
+    }
+
+    /// Finishes the active hypotheses matching the condition.
+    pub fn stop_if(&mut self, f: impl FnMut(&Hypothesis) -> bool) -> Result<()> {
+        let parents = self.generator.stop_if(f);
+        self.cache.reorder(&parents)
+    }
+}
+
+impl<'a, C> Seq2Seq<'a, C>
+where
+    C: Ipnis + ?Sized + Sync,
+{
+    /// Starts decoding each row of the encoder inputs from its decoder prefix.
+    pub fn new(
+        client: &'a C,
+        models: Seq2SeqModels<'a>,
+        config: &GenerationConfig,
+        input_ids: ndarray::Array2<i64>,
+        attention_mask: ndarray::Array2<i64>,
+        prefixes: Vec<Vec<i64>>,
+        special_tokens: &SpecialTokens,
+    ) -> Result<Self> {
+        Seq2SeqState::new(config, input_ids, attention_mask, prefixes, special_tokens)
+            .map(|state| state.resume(client, models))
+    }
+
+    pub fn generator(&self) -> &Generator {
+        &self.state.generator
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.state.generator.is_done()
+    }
+
+    /// Generates the next tokens of the active hypotheses.
+    pub async fn step(&mut self) -> Result<()> {
+        match self.models {
+            Seq2SeqModels::Single(model) => self.step_single(model).await,
+            Seq2SeqModels::Split(models) => self.step_split(models).await,
+        }
+    }
+
+    /// Decodes until all the hypotheses are finished, returning the best one of each row.
+    pub async fn run(mut self) -> Result<Vec<Hypothesis>> {
+        while !self.is_done() {
+            self.step().await?;
+        }
+        Ok(self.finish())
+    }
+
+    pub fn finish(self) -> Vec<Hypothesis> {
+        self.state.generator.finish()
+    }
+
+    /// Releases the client and the models, keeping the state to be resumed later.
+    pub fn into_state(self) -> Seq2SeqState {
+        self.state
+    }
+
+    async fn step_single(&mut self, model: &Model) -> Result<()> {
+        let active = self.state.generator.active();
+        let mut inputs = HashMap::default();
+        {
+            // the encoder inputs of the active hypotheses
+            let indices: Vec<_> = active.iter().map(|hypothesis| hypothesis.input).collect();
+            inputs.insert(
+                "input_ids".into(),
+                Box::new(TensorData::from(StringTensorData::I64(Array(
+                    self.state
+                        .input_ids
+                        .select(ndarray::Axis(0), &indices)
+                        .into(),
+                )))) as Box<dyn ToTensor + Send + Sync>,
+            );
+            inputs.insert(
+                "attention_mask".into(),
+                Box::new(TensorData::from(StringTensorData::I64(Array(
+                    self.state
+                        .attention_mask
+                        .select(ndarray::Axis(0), &indices)
+                        .into(),
+                )))) as Box<dyn ToTensor + Send + Sync>,
+            );
+
+            // append a placeholder token to each sequence
+            let decoder_input_ids: Vec<_> = active
+                .iter()
+                .map(|hypothesis| [hypothesis.tokens.as_slice(), &[self.state.token_pad]].concat())
+                .collect();
+            let decoder_input_ids = stack(&decoder_input_ids)?;
+            let decoder_attention_mask = ndarray::Array::ones(decoder_input_ids.dim());
+
+            inputs.insert(
+                "decoder_input_ids".into(),
+                Box::new(TensorData::from(StringTensorData::I64(Array(
+                    decoder_input_ids.into(),
+                )))) as Box<dyn ToTensor + Send + Sync>,
+            );
+            inputs.insert(
+                "decoder_attention_mask".into(),
+                Box::new(TensorData::from(StringTensorData::I64(Array(
+                    decoder_attention_mask.into(),
+                )))) as Box<dyn ToTensor + Send + Sync>,
+            );
+        }
+
+        let mut outputs = self.client.call(model, &inputs).await?;
+        if outputs.is_empty() {
+            let outputs = outputs.len();
+            bail!("unexpected outputs: Expected 1, Given {outputs}");
+        }
+
+        let logits: Tensor<StringTensorData> = Tensor::find(&mut outputs, "logits")?.try_into()?;
+
+        match &logits.data {
+            StringTensorData::F32Embedding(logits) => {
+                // skip the placeholder token
+                let position = logits.shape()[1] - 2;
+                self.state
+                    .generator
+                    .step(logits.index_axis(ndarray::Axis(1), position))?;
+                Ok(())
+            }
+            _ => {
+                let logits = logits.shape();
+                bail!("unexpected StringTensorData: {logits:?}")
+            }
+        }
+    }
+
+    async fn step_split(&mut self, models: SplitModels<'a>) -> Result<()> {
+        // run the encoder
+        let encoder_hidden_states = match &self.state.encoder_hidden_states {
+            Some(encoder_hidden_states) => encoder_hidden_states.clone(),
+            None => {
+                let inputs: HashMap<_, _> = [
+                    (
+                        "input_ids".to_string(),
+                        TensorData::from(StringTensorData::I64(Array(
+                            self.state.input_ids.clone().into(),
+                        ))),
+                    ),
+                    (
+                        "attention_mask".to_string(),
+                        TensorData::from(StringTensorData::I64(Array(
+                            self.state.attention_mask.clone().into(),
+                        ))),
+                    ),
+                ]
+                .into_iter()
+                .collect();
+
+                let mut outputs = self.client.call(models.encoder, &inputs).await?;
+                let last_hidden_state = Tensor::find(&mut outputs, "last_hidden_state")?;
+                let encoder_hidden_states = match last_hidden_state.data {
+                    TensorData::Dynamic(DynamicTensorData::F32(data)) => data.0,
+                    _ => {
+                        let last_hidden_state = last_hidden_state.shape();
+                        bail!("unexpected TensorData: {last_hidden_state:?}")
+                    }
+                };
+                self.state
+                    .encoder_hidden_states
+                    .insert(encoder_hidden_states)
+                    .clone()
+            }
+        };
+
+        let active = self.state.generator.active();
+        let mut inputs = HashMap::default();
+        {
+            // the encoder outputs of the active hypotheses
+            let indices: Vec<_> = active.iter().map(|hypothesis| hypothesis.input).collect();
+            inputs.insert(
+                "encoder_hidden_states".into(),
+                Box::new(TensorData::from(DynamicTensorData::F32(Array(
+                    encoder_hidden_states
+                        .select(ndarray::Axis(0), &indices)
+                        .into(),
+                )))) as Box<dyn ToTensor + Send + Sync>,
+            );
+            inputs.insert(
+                "encoder_attention_mask".into(),
+                Box::new(TensorData::from(StringTensorData::I64(Array(
+                    self.state
+                        .attention_mask
+                        .select(ndarray::Axis(0), &indices)
+                        .into(),
+                )))) as Box<dyn ToTensor + Send + Sync>,
+            );
+
+            // feed the new tokens only if the past key-values are cached
+            let decoder_input_ids: Vec<_> = active
+                .iter()
+                .map(|hypothesis| match self.state.cache.is_empty() {
+                    true => hypothesis.tokens.clone(),
+                    false => hypothesis.tokens[hypothesis.tokens.len() - 1..].to_vec(),
+                })
+                .collect();
+            inputs.insert(
+                "input_ids".into(),
+                Box::new(TensorData::from(StringTensorData::I64(Array(
+                    stack(&decoder_input_ids)?.into(),
+                )))) as Box<dyn ToTensor + Send + Sync>,
+            );
+            self.state.cache.extend_inputs(&mut inputs);
+        }
+
+        let model = match self.state.cache.is_empty() {
+            true => models.decoder,
+            false => models.decoder_with_past,
+        };
+        let mut outputs = self
+            .state
+            .cache
+            .update(self.client.call(model, &inputs).await?);
+
+        let logits: Tensor<StringTensorData> = Tensor::find(&mut outputs, "logits")?.try_into()?;
+
+        match &logits.data {
+            StringTensorData::F32Embedding(logits) => {
+                let position = logits.shape()[1] - 1;
+                let parents = self
+                    .state
+                    .generator
+                    .step(logits.index_axis(ndarray::Axis(1), position))?;
+                self.state.cache.reorder(&parents)
+            }
+            _ => {
+                let logits = logits.shape();
+                bail!("unexpected StringTensorData: {logits:?}")
+            }
+        }
+    }
+}
+
+pub(super) fn stack(sequences: &[Vec<i64>]) -> Result<ndarray::Array2<i64>> {
+    ndarray::stack(
+        ndarray::Axis(0),
+        sequences
+            .iter()
+            .map(ndarray::ArrayView::from)
+            .collect::<Vec<_>>()
+            .as_slice(),
+    )
+    .map_err(Into::into)
+}
@@ -0,0 +1,746 @@
+use bytecheck::CheckBytes;
+use ipis::{
+    core::{
+        anyhow::{bail, Error, Result},
+        ndarray,
+        signed::IsSigned,
+    },
+    futures::{
+        future,
+        stream::{self, BoxStream},
+        StreamExt, TryStreamExt,
+    },
+};
+use rkyv::{Archive, Deserialize, Serialize};
+#[cfg(feature = "generation")]
+use {
+    super::{
+        causal_lm::CausalLmState,
+        generation::{Generator, Hypothesis},
+        seq2seq::{stack, Seq2SeqModels, Seq2SeqState, SplitModels},
+    },
+    ipis::core::{account::AccountRef, anyhow::anyhow},
+    rand::Rng,
+    std::{
+        collections::HashMap,
+        sync::{Mutex, MutexGuard},
+        time::{Duration, Instant},
+    },
+};
+
+use crate::{model::Model, Ipnis};
+
+use super::{
+    generation::GenerationConfig,
+    stream::{TextDelta, TextStreamer},
+    tokenizer::SpecialTokens,
+};
+
+/// The models of a generation, which are kept by the service between the steps.
+#[derive(Clone, Debug, PartialEq, Eq, Archive, Serialize, Deserialize)]
+#[archive_attr(derive(CheckBytes, Debug, PartialEq))]
+pub enum GenerationModels {
+    /// A decoder-only model, continuing the inputs.
+    CausalLm(Model),
+    /// An encoder-decoder model, which runs as a whole at each step.
+    Seq2Seq(Model),
+    /// The split exports of an encoder-decoder model.
+    Seq2SeqSplit {
+        encoder: Model,
+        decoder: Model,
+        decoder_with_past: Model,
+    },
+}
+
+impl IsSigned for GenerationModels {}
+
+#[cfg(feature = "generation")]
+impl GenerationModels {
+    /// Returns the models of an encoder-decoder model.
+    pub fn as_seq2seq(&self) -> Result<Seq2SeqModels<'_>> {
+        match self {
+            Self::CausalLm(_) => bail!("the decoder-only model is not an encoder-decoder model"),
+            Self::Seq2Seq(model) => Ok(Seq2SeqModels::Single(model)),
+            Self::Seq2SeqSplit {
+                encoder,
+                decoder,
+                decoder_with_past,
+            } => Ok(Seq2SeqModels::Split(SplitModels {
+                encoder,
+                decoder,
+                decoder_with_past,
+            })),
+        }
+    }
+}
+
+#[cfg(feature = "generation")]
+impl From<Seq2SeqModels<'_>> for GenerationModels {
+    fn from(models: Seq2SeqModels<'_>) -> Self {
+        match models {
+            Seq2SeqModels::Single(model) => Self::Seq2Seq(model.clone()),
+            Seq2SeqModels::Split(models) => Self::Seq2SeqSplit {
+                encoder: models.encoder.clone(),
+                decoder: models.decoder.clone(),
+                decoder_with_past: models.decoder_with_past.clone(),
+            },
+        }
+    }
+}
+
+/// Requests the service to generate step by step, keeping the state between the steps.
+#[derive(Clone, Debug, PartialEq, Archive, Serialize, Deserialize)]
+#[archive_attr(derive(CheckBytes, Debug, PartialEq))]
+pub struct GenerationRequest {
+    pub models: GenerationModels,
+    pub config: GenerationConfig,
+    /// The inputs of the model, or of the encoder of the encoder-decoder models.
+    pub input_ids: Vec<Vec<i64>>,
+    pub attention_mask: Vec<Vec<i64>>,
+    /// The tokens which each input is continued from,
+    /// i.e. the inputs without the padding, or the decoder prefixes.
+    pub prefixes: Vec<Vec<i64>>,
+    pub special_tokens: SpecialTokens,
+}
+
+impl IsSigned for GenerationRequest {}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Archive, Serialize, Deserialize)]
+#[archive_attr(derive(CheckBytes, Debug, PartialEq, Eq, Hash))]
+pub struct GenerationSession(pub u64);
+
+impl IsSigned for GenerationSession {}
+
+/// Requests the next step of a generation.
+#[derive(Clone, Debug, PartialEq, Eq, Archive, Serialize, Deserialize)]
+#[archive_attr(derive(CheckBytes, Debug, PartialEq))]
+pub struct GenerationStep {
+    pub session: GenerationSession,
+    /// The inputs to be finished before the step, e.g. by the stop sequences.
+    pub stop: Vec<usize>,
+}
+
+impl IsSigned for GenerationStep {}
+
+/// The tokens generated by a step of an input.
+#[derive(Clone, Debug, PartialEq, Eq, Archive, Serialize, Deserialize)]
+#[archive_attr(derive(CheckBytes, Debug, PartialEq))]
+pub struct TokenDelta {
+    /// The index of the input.
+    pub index: usize,
+    pub tokens: Vec<i64>,
+    /// Whether the input is finished, so that no more deltas follow.
+    pub is_finished: bool,
+}
+
+impl IsSigned for TokenDelta {}
+
+/// Keeps the generations between the steps, on behalf of the clients.
+///
+/// The generations only proceed when the clients request the next steps.
+/// Each generation belongs to the account which started it,
+/// and the idle ones are dropped after the timeout.
+///
+/// The account is `None` for the in-process clients.
+#[cfg(feature = "generation")]
+#[derive(Default)]
+pub struct GenerationSessions {
+    sessions: Mutex<HashMap<GenerationSession, Session>>,
+}
+
+#[cfg(feature = "generation")]
+impl GenerationSessions {
+    pub const TIMEOUT: Duration = Duration::from_secs(60);
+
+    /// The maximum number of the open generations of each account.
+    pub const MAX_SESSIONS_PER_ACCOUNT: usize = 4;
+
+    pub fn start(
+        &self,
+        account: Option<AccountRef>,
+        request: GenerationRequest,
+    ) -> Result<GenerationSession> {
+        // the best beam may be replaced, rewriting the sent tokens
+        if request.config.num_beams > 1 {
+            bail!("streaming with the beam search is not supported");
+        }
+        if request.input_ids.is_empty() {
+            bail!("empty inputs are given");
+        }
+
+        let input_ids = stack(&request.input_ids)?;
+        let attention_mask = stack(&request.attention_mask)?;
+        let num_sent = vec![Some(0); request.prefixes.len()];
+        let state = match request.models {
+            GenerationModels::CausalLm(model) => {
+                let token_eos = request
+                    .special_tokens
+                    .eos
+                    .ok_or_else(|| anyhow!("the tokenizer has no EOS token"))?;
+                State::CausalLm {
+                    state: Box::new(CausalLmState::new(
+                        &model,
+                        &request.config,
+                        input_ids,
+                        attention_mask,
+                        request.prefixes,
+                        token_eos,
+                    )?),
+                    model,
+                }
+            }
+            models => State::Seq2Seq {
+                models,
+                state: Box::new(Seq2SeqState::new(
+                    &request.config,
+                    input_ids,
+                    attention_mask,
+                    request.prefixes,
+                    &request.special_tokens,
+                )?),
+            },
+        };
+
+        let mut sessions = self.lock();
+        let num_sessions = sessions
+            .values()
+            .filter(|session| session.account == account)
+            .count();
+        if num_sessions >= Self::MAX_SESSIONS_PER_ACCOUNT {
+            bail!("too many generation sessions: {num_sessions}");
+        }
+
+        let id = loop {
+            let id = GenerationSession(::rand::thread_rng().gen());
+            if !sessions.contains_key(&id) {
+                break id;
+            }
+        };
+        sessions.insert(
+            id,
+            Session {
+                account,
+                state,
+                num_sent,
+                updated_at: Instant::now(),
+            },
+        );
+        Ok(id)
+    }
+
+    /// Runs a step of the generation, returning the new tokens of each input.
+    ///
+    /// The session is closed when every input is finished.
+    pub async fn next<C>(
+        &self,
+        client: &C,
+        account: Option<AccountRef>,
+        step: GenerationStep,
+    ) -> Result<Vec<TokenDelta>>
+    where
+        C: Ipnis + ?Sized + Sync,
+    {
+        let GenerationStep { session: id, stop } = step;
+        let session = self.take(account, id)?;
+
+        let mut session = session.step(client, &stop).await?;
+        let deltas = session.deltas();
+
+        if !session.state.generator().is_done() {
+            session.updated_at = Instant::now();
+            self.lock().insert(id, session);
+        }
+        Ok(deltas)
+    }
+
+    /// Closes the generation before it is finished.
+    pub fn stop(&self, account: Option<AccountRef>, id: GenerationSession) -> Result<()> {
+        self.take(account, id).map(drop)
+    }
+
+    /// Removes the session of the account.
+    fn take(&self, account: Option<AccountRef>, id: GenerationSession) -> Result<Session> {
+        let mut sessions = self.lock();
+        match sessions.remove(&id) {
+            Some(session) if session.account == account => Ok(session),
+            Some(session) => {
+                sessions.insert(id, session);
+                let GenerationSession(id) = id;
+                bail!("the generation session belongs to another account: {id}")
+            }
+            None => {
+                let GenerationSession(id) = id;
+                bail!("no such generation session: {id}")
+            }
+        }
+    }
+
+    /// Locks the sessions, dropping the expired ones.
+    fn lock(&self) -> MutexGuard<'_, HashMap<GenerationSession, Session>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let now = Instant::now();
+        sessions.retain(|_, session| now.duration_since(session.updated_at) < Self::TIMEOUT);
+        sessions
+    }
+}
+
+#[cfg(feature = "generation")]
+struct Session {
+    account: Option<AccountRef>,
+    state: State,
+    /// The number of the tokens sent of each input, or `None` if it is finished.
+    num_sent: Vec<Option<usize>>,
+    updated_at: Instant,
+}
+
+#[cfg(feature = "generation")]
+impl Session {
+    async fn step<C>(mut self, client: &C, stop: &[usize]) -> Result<Self>
+    where
+        C: Ipnis + ?Sized + Sync,
+    {
+        let is_stopped = |hypothesis: &Hypothesis| stop.contains(&hypothesis.input);
+
+        self.state = match self.state {
+            State::CausalLm { model, mut state } => {
+                state.stop_if(is_stopped)?;
+
+                let mut session = state.resume(client, &model);
+                if !session.is_done() {
+                    session.step().await?;
+                }
+                let state = Box::new(session.into_state());
+                State::CausalLm { model, state }
+            }
+            State::Seq2Seq { models, mut state } => {
+                state.stop_if(is_stopped)?;
+
+                let mut session = state.resume(client, models.as_seq2seq()?);
+                if !session.is_done() {
+                    session.step().await?;
+                }
+                let state = Box::new(session.into_state());
+                State::Seq2Seq { models, state }
+            }
+        };
+        Ok(self)
+    }
+
+    /// Collects the tokens generated since the last step.
+    fn deltas(&mut self) -> Vec<TokenDelta> {
+        let generator = self.state.generator();
+        self.num_sent
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, num_sent)| {
+                let (hypothesis, is_finished) = generator.current(index)?;
+                let tokens = hypothesis.generated().get((*num_sent)?..)?.to_vec();
+                if tokens.is_empty() && !is_finished {
+                    return None;
+                }
+
+                *num_sent = match is_finished {
+                    true => None,
+                    false => Some(hypothesis.generated().len()),
+                };
+                Some(TokenDelta {
+                    index,
+                    tokens,
+                    is_finished,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "generation")]
+enum State {
+    CausalLm {
+        model: Model,
+        state: Box<CausalLmState>,
+    },
+    Seq2Seq {
+        models: GenerationModels,
+        state: Box<Seq2SeqState>,
+    },
+}
+
+#[cfg(feature = "generation")]
+impl State {
+    fn generator(&self) -> &Generator {
+        match self {
+            Self::CausalLm { state, .. } => state.generator(),
+            Self::Seq2Seq { state, .. } => state.generator(),
+        }
+    }
+}
+
+/// Follows a generation of the service, collecting the generated tokens of each input.
+///
+/// The service only generates when the next step is requested,
+/// so the generation halts when this is dropped and expires after the timeout.
+/// [`close`](Self::close) releases it at once.
+pub struct Generation<'a, C: ?Sized> {
+    client: &'a C,
+    session: GenerationSession,
+    tokens: Vec<Vec<i64>>,
+    finished: Vec<bool>,
+    stop: Vec<usize>,
+    /// Whether the service still keeps the generation.
+    is_open: bool,
+}
+
+impl<'a, C> Generation<'a, C>
+where
+    C: Ipnis + ?Sized + Sync,
+{
+    pub async fn start(client: &'a C, request: GenerationRequest) -> Result<Generation<'a, C>> {
+        let num_inputs = request.prefixes.len();
+        Ok(Self {
+            client,
+            session: client.start_generation(request).await?,
+            tokens: vec![vec![]; num_inputs],
+            finished: vec![false; num_inputs],
+            stop: vec![],
+            is_open: true,
+        })
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.finished.iter().all(|&is_finished| is_finished)
+    }
+
+    /// Returns the generated tokens of the input so far, and whether it is finished.
+    pub fn generated(&self, index: usize) -> (&[i64], bool) {
+        (&self.tokens[index], self.finished[index])
+    }
+
+    /// Finishes the input, e.g. by a stop sequence, which is sent with the next step.
+    pub fn stop(&mut self, index: usize) {
+        if !self.finished[index] {
+            self.finished[index] = true;
+            self.stop.push(index);
+        }
+    }
+
+    /// Generates the next tokens, returning the indices of the updated inputs.
+    pub async fn step(&mut self) -> Result<Vec<usize>> {
+        let step = GenerationStep {
+            session: self.session,
+            stop: ::core::mem::take(&mut self.stop),
+        };
+
+        let mut indices = vec![];
+        for delta in self.client.next_generation(step).await? {
+            let TokenDelta {
+                index,
+                tokens,
+                is_finished,
+            } = delta;
+            if index >= self.tokens.len() {
+                let expected = self.tokens.len();
+                bail!("unexpected input index: Expected less than {expected}, Given {index}");
+            }
+
+            self.tokens[index].extend(tokens);
+            self.finished[index] |= is_finished;
+            indices.push(index);
+        }
+
+        // the service closes the generation when every input is finished
+        self.is_open = !self.is_done();
+        Ok(indices)
+    }
+
+    /// Releases the generation of the service, even if it is not finished.
+    pub async fn close(self) -> Result<()> {
+        if self.is_open {
+            self.client.stop_generation(self.session).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Generates on the service, yielding the newly decoded text of each input step by step.
+///
+/// `decode` converts the generated tokens of an input into the whole text so far,
+/// given whether it is finished. It may finish the input earlier, e.g. by a stop sequence.
+///
+/// The generation halts when the stream is dropped, and expires after the timeout.
+pub fn stream_generation<'a, C, F>(
+    client: &'a C,
+    request: GenerationRequest,
+    decode: F,
+) -> BoxStream<'a, Result<Vec<TextDelta>>>
+where
+    C: Ipnis + ?Sized + Sync,
+    F: FnMut(&[i64], bool) -> Option<(String, bool)> + Send + 'a,
+{
+    if request.prefixes.is_empty() {
+        return stream::empty().boxed();
+    }
+
+    let streamer = TextStreamer::new(&request.config, request.prefixes.len());
+    stream::once(async move {
+        let streamer = streamer?;
+        let generation = Generation::start(client, request).await?;
+        Ok::<_, Error>((generation, streamer, decode))
+    })
+    .map_ok(|session| {
+        stream::try_unfold(
+            session,
+            |(mut generation, mut streamer, mut decode)| async move {
+                if generation.is_done() {
+                    generation.close().await?;
+                    return Ok(None);
+                }
+
+                let mut deltas = vec![];
+                for index in generation.step().await? {
+                    let (tokens, is_finished) = generation.generated(index);
+                    let (text, is_finished) = match decode(tokens, is_finished) {
+                        Some((text, is_stopped)) => (text, is_finished || is_stopped),
+                        None => continue,
+                    };
+                    if is_finished {
+                        generation.stop(index);
+                    }
+                    deltas.extend(streamer.push(index, &text, is_finished));
+                }
+                Ok(Some((deltas, (generation, streamer, decode))))
+            },
+        )
+    })
+    .try_flatten()
+    .try_filter(|deltas| future::ready(!deltas.is_empty()))
+    .boxed()
+}
+
+/// Converts the rows into the nested vectors, e.g. the inputs of the [`GenerationRequest`].
+pub fn to_rows<T: Clone>(array: &ndarray::Array2<T>) -> Vec<Vec<T>> {
+    array.rows().into_iter().map(|row| row.to_vec()).collect()
+}
+
+#[cfg(all(test, feature = "generation"))]
+mod tests {
+    use ipis::{
+        async_trait::async_trait,
+        core::{
+            account::Account,
+            value::{array::Array, hash::Hash},
+        },
+        futures::executor::block_on,
+        path::Path,
+    };
+
+    use crate::{
+        nlp::tensor::StringTensorData,
+        tensor::{shape::Shape, ty::TensorType, Tensor, TensorData},
+    };
+
+    use super::*;
+
+    const TOKEN_EOS: i64 = 1;
+
+    /// Generates `2`, `3` and then the EOS token after the last token.
+    #[derive(Default)]
+    struct Client {
+        generations: GenerationSessions,
+    }
+
+    #[async_trait]
+    impl Ipnis for Client {
+        async fn protocol(&self) -> Result<String> {
+            Ok("mock".into())
+        }
+
+        async fn call_raw(&self, _model: &Model, inputs: Vec<Tensor>) -> Result<Vec<Tensor>> {
+            let input_ids = inputs
+                .into_iter()
+                .find(|tensor| tensor.name == "input_ids")
+                .unwrap();
+            let input_ids = match input_ids.data {
+                TensorData::String(StringTensorData::I64(input_ids)) => input_ids,
+                _ => unreachable!(),
+            };
+
+            let inf = f32::NEG_INFINITY;
+            let logits: Vec<_> = input_ids
+                .rows()
+                .into_iter()
+                .flat_map(|row| match row[row.len() - 1] {
+                    0 => [inf, inf, 1.0, inf],
+                    2 => [inf, inf, inf, 1.0],
+                    _ => [inf, 1.0, inf, inf],
+                })
+                .collect();
+            let logits = ndarray::Array::from_shape_vec((input_ids.nrows(), 1, 4), logits)?;
+            Ok(vec![Tensor {
+                name: "logits".into(),
+                data: StringTensorData::F32Embedding(Array(logits.into())).into(),
+            }])
+        }
+
+        async fn load_model(&self, _path: &Path) -> Result<Model> {
+            bail!("no models are stored")
+        }
+
+        async fn start_generation(&self, request: GenerationRequest) -> Result<GenerationSession> {
+            self.generations.start(None, request)
+        }
+
+        async fn next_generation(&self, step: GenerationStep) -> Result<Vec<TokenDelta>> {
+            self.generations.next(self, None, step).await
+        }
+
+        async fn stop_generation(&self, session: GenerationSession) -> Result<()> {
+            self.generations.stop(None, session)
+        }
+    }
+
+    fn request(prefixes: Vec<Vec<i64>>) -> GenerationRequest {
+        let shape = |name| Shape::new(name, TensorType::I64, vec![None, None]).unwrap();
+        let model = Model {
+            path: Path {
+                value: Hash::with_str(""),
+                len: 0,
+            },
+            inputs: vec![
+                shape("input_ids"),
+                shape("position_ids"),
+                shape("attention_mask"),
+            ],
+            outputs: vec![],
+        };
+
+        GenerationRequest {
+            models: GenerationModels::CausalLm(model),
+            config: Default::default(),
+            attention_mask: prefixes.iter().map(|ids| vec![1; ids.len()]).collect(),
+            input_ids: prefixes.clone(),
+            prefixes,
+            special_tokens: SpecialTokens {
+                eos: Some(TOKEN_EOS),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn stream_yields_the_new_text_of_each_input() {
+        let client = Client::default();
+        let request = request(vec![vec![0], vec![2]]);
+        let stream = stream_generation(&client, request, |tokens, _| {
+            let text = tokens
+                .iter()
+                .filter(|&&token| token != TOKEN_EOS)
+                .map(ToString::to_string)
+                .collect();
+            Some((text, false))
+        });
+
+        let mut texts = vec![String::new(); 2];
+        let mut finished = vec![false; 2];
+        for delta in block_on(stream.try_collect::<Vec<_>>()).unwrap().concat() {
+            assert!(!finished[delta.index]);
+            texts[delta.index].push_str(&delta.text);
+            finished[delta.index] = delta.is_finished;
+        }
+        assert_eq!(texts, ["23", "3"]);
+        assert_eq!(finished, [true, true]);
+
+        // the finished sessions are closed
+        assert!(client.generations.sessions.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn stream_closes_the_stopped_session() {
+        let client = Client::default();
+        let request = request(vec![vec![0]]);
+        let stream = stream_generation(&client, request, |_, _| Some((String::new(), true)));
+
+        let deltas = block_on(stream.try_collect::<Vec<_>>()).unwrap().concat();
+        assert_eq!(
+            deltas,
+            [TextDelta {
+                index: 0,
+                text: String::new(),
+                is_finished: true,
+            }],
+        );
+        assert!(client.generations.sessions.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn sessions_belong_to_their_accounts() {
+        let client = Client::default();
+        let account = Some(Account::generate().account_ref());
+        let session = client
+            .generations
+            .start(account, request(vec![vec![0]]))
+            .unwrap();
+
+        let step = GenerationStep {
+            session,
+            stop: vec![],
+        };
+        assert!(block_on(client.generations.next(&client, None, step.clone())).is_err());
+        assert!(client.generations.stop(None, session).is_err());
+        assert!(block_on(client.generations.next(&client, account, step)).is_ok());
+
+        client.generations.stop(account, session).unwrap();
+        assert!(client.generations.sessions.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn sessions_are_limited_per_account() {
+        let client = Client::default();
+        for _ in 0..GenerationSessions::MAX_SESSIONS_PER_ACCOUNT {
+            client
+                .generations
+                .start(None, request(vec![vec![0]]))
+                .unwrap();
+        }
+        assert!(client
+            .generations
+            .start(None, request(vec![vec![0]]))
+            .is_err());
+
+        let account = Some(Account::generate().account_ref());
+        assert!(client
+            .generations
+            .start(account, request(vec![vec![0]]))
+            .is_ok());
+    }
+
+    #[test]
+    fn stopped_inputs_are_finished_without_new_tokens() {
+        let client = Client::default();
+        let session = client
+            .generations
+            .start(None, request(vec![vec![0]]))
+            .unwrap();
+
+        let step = |stop| GenerationStep { session, stop };
+        let deltas = block_on(client.generations.next(&client, None, step(vec![]))).unwrap();
+        assert_eq!(
+            deltas,
+            [TokenDelta {
+                index: 0,
+                tokens: vec![2],
+                is_finished: false,
+            }],
+        );
+
+        let deltas = block_on(client.generations.next(&client, None, step(vec![0]))).unwrap();
+        assert_eq!(
+            deltas,
+            [TokenDelta {
+                index: 0,
+                tokens: vec![],
+                is_finished: true,
+            }],
+        );
+        assert!(block_on(client.generations.next(&client, None, step(vec![]))).is_err());
+    }
+}
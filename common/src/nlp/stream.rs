@@ -0,0 +1,111 @@
+use ipis::core::anyhow::{bail, Result};
+
+use super::generation::GenerationConfig;
+
+/// The text decoded since the last step of an input.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextDelta {
+    /// The index of the input.
+    pub index: usize,
+    pub text: String,
+    /// Whether the input is finished, so that no more deltas follow.
+    pub is_finished: bool,
+}
+
+/// Splits the decoded texts into the deltas, step by step.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TextStreamer {
+    sent: Vec<Option<String>>,
+}
+
+impl TextStreamer {
+    pub fn new(config: &GenerationConfig, num_inputs: usize) -> Result<Self> {
+        // the best beam may be replaced, rewriting the sent text
+        if config.num_beams > 1 {
+            bail!("streaming with the beam search is not supported");
+        }
+
+        Ok(Self {
+            sent: vec![Some(Default::default()); num_inputs],
+        })
+    }
+
+    /// Returns the new text of the input, given the whole text decoded so far.
+    ///
+    /// The incomplete characters and the spaces at the end are kept until the next step,
+    /// as decoding the next tokens may still change them.
+    pub fn push(&mut self, index: usize, text: &str, is_finished: bool) -> Option<TextDelta> {
+        let sent = self.sent.get_mut(index)?.as_mut()?;
+
+        let text = match is_finished {
+            true => text,
+            false => text
+                .trim_end_matches(|c: char| c == char::REPLACEMENT_CHARACTER || c.is_whitespace()),
+        };
+
+        // the sent text cannot be rewritten, so only the new suffix is sent
+        let delta = match text.strip_prefix(sent.as_str()) {
+            Some(delta) => delta.to_string(),
+            // wait until the sent text is decoded the same again
+            None if !is_finished => return None,
+            // otherwise, the changed characters are assumed to be replaced in place
+            None => text.chars().skip(sent.chars().count()).collect(),
+        };
+        if delta.is_empty() && !is_finished {
+            return None;
+        }
+
+        match is_finished {
+            true => self.sent[index] = None,
+            false => sent.push_str(&delta),
+        }
+        Some(TextDelta {
+            index,
+            text: delta,
+            is_finished,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn streamer_sends_the_new_text_only() {
+        let mut streamer = TextStreamer::new(&Default::default(), 1).unwrap();
+        let mut push = |text, is_finished| {
+            streamer
+                .push(0, text, is_finished)
+                .map(|delta| (delta.text, delta.is_finished))
+        };
+
+        assert_eq!(push("Hello", false), Some(("Hello".into(), false)));
+        assert_eq!(push("Hello", false), None);
+        assert_eq!(push("Hello, w\u{FFFD}", false), Some((", w".into(), false)));
+        assert_eq!(push("Hello, wörld", true), Some(("örld".into(), true)));
+        assert_eq!(push("Hello, wörld!", true), None);
+    }
+
+    #[test]
+    fn streamer_holds_back_the_unstable_text() {
+        let mut streamer = TextStreamer::new(&Default::default(), 1).unwrap();
+        let mut push = |text, is_finished| {
+            streamer
+                .push(0, text, is_finished)
+                .map(|delta| (delta.text, delta.is_finished))
+        };
+
+        // the multi-byte characters are sent as a whole
+        assert_eq!(push("日\u{FFFD}", false), Some(("日".into(), false)));
+        assert_eq!(push("日本語 ", false), Some(("本語".into(), false)));
+
+        // the text is held back while the sent text is decoded differently
+        assert_eq!(push("日本語 ,", false), Some((" ,".into(), false)));
+        assert_eq!(push("日本語, é", false), None);
+        assert_eq!(push("日本語 , é", false), Some((" é".into(), false)));
+
+        // the changed text is not sent again
+        assert_eq!(push("日本吾 , éà", true), Some(("à".into(), true)));
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+use ipis::core::anyhow::{anyhow, bail, Result};
+use tokenizers::PostProcessor;
+
+use super::{Encoding, SpecialTokens, Tokenizer};
+
+impl Tokenizer for ::tokenizers::Tokenizer {
+    fn encode(&self, text: &str) -> Result<Encoding> {
+        // the truncation is performed by the callers
+        if self.get_truncation().is_some() {
+            bail!("the truncation of the tokenizer should be disabled");
+        }
+
+        let encoding = self
+            .encode_char_offsets(text, false)
+            .map_err(|e| anyhow!(e))?;
+        Ok(from_encoding(&encoding))
+    }
+
+    fn build_inputs(&self, first: Encoding, second: Option<Encoding>) -> Result<Encoding> {
+        let first = into_encoding(self, first);
+        let second = second.map(|second| into_encoding(self, second));
+
+        let encoding = match self.get_post_processor() {
+            Some(post_processor) => post_processor
+                .process(first, second, true)
+                .map_err(|e| anyhow!(e))?,
+            None => {
+                let mut first = first;
+                if let Some(second) = second {
+                    first.merge_with(second, false);
+                }
+                first
+            }
+        };
+        Ok(from_encoding(&encoding))
+    }
+
+    fn decode(&self, ids: &[i64], skip_special_tokens: bool) -> Result<String> {
+        let ids = ids
+            .iter()
+            .map(|&id| id.try_into())
+            .collect::<Result<Vec<u32>, _>>()?;
+
+        // call the inner implementation, not this trait
+        (**self)
+            .decode(&ids, skip_special_tokens)
+            .map_err(|e| anyhow!(e))
+    }
+
+    fn token_to_id(&self, token: &str) -> Option<i64> {
+        (**self).token_to_id(token).map(Into::into)
+    }
+
+    fn id_to_token(&self, id: i64) -> Option<String> {
+        (**self).id_to_token(id.try_into().ok()?)
+    }
+
+    fn special_tokens(&self) -> SpecialTokens {
+        let token_to_id = |token: &str| Tokenizer::token_to_id(self, token);
+
+        SpecialTokens {
+            pad: self
+                .get_padding()
+                .map(|padding| padding.pad_id.into())
+                .or_else(|| SpecialTokens::find(token_to_id).pad),
+            ..SpecialTokens::find(token_to_id)
+        }
+    }
+}
+
+fn into_encoding(
+    tokenizer: &::tokenizers::Tokenizer,
+    encoding: Encoding,
+) -> ::tokenizers::Encoding {
+    let len = encoding.len();
+
+    ::tokenizers::Encoding::new(
+        encoding.ids.iter().map(|&id| id as u32).collect(),
+        encoding.type_ids.iter().map(|&id| id as u32).collect(),
+        encoding
+            .ids
+            .iter()
+            .map(|&id| Tokenizer::id_to_token(tokenizer, id).unwrap_or_default())
+            .collect(),
+        vec![None; len],
+        encoding
+            .offsets
+            .iter()
+            .map(|offset| offset.unwrap_or_default())
+            .collect(),
+        encoding
+            .special_tokens_mask
+            .iter()
+            .map(|&special| special as u32)
+            .collect(),
+        vec![1; len],
+        vec![],
+        HashMap::from([(0, 0..len)]),
+    )
+}
+
+fn from_encoding(encoding: &::tokenizers::Encoding) -> Encoding {
+    let sequence_ids = encoding.get_sequence_ids();
+
+    let mut output = Encoding::default();
+    for (index, &mask) in encoding.get_attention_mask().iter().enumerate() {
+        // skip the padding
+        if mask == 0 {
+            continue;
+        }
+
+        let special = encoding.get_special_tokens_mask()[index] != 0;
+        output.ids.push(encoding.get_ids()[index].into());
+        output.type_ids.push(encoding.get_type_ids()[index].into());
+        output.special_tokens_mask.push(special);
+        output
+            .offsets
+            .push(Some(encoding.get_offsets()[index]).filter(|_| !special));
+        output
+            .sequence_ids
+            .push(sequence_ids[index].or(Some(0)).filter(|_| !special));
+    }
+    output
+}
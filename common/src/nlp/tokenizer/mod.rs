@@ -0,0 +1,125 @@
+#[cfg(feature = "tokenizers")]
+mod huggingface;
+#[cfg(feature = "rust_tokenizers")]
+mod rust_tokenizers;
+
+use std::ops::Range;
+
+use bytecheck::CheckBytes;
+use ipis::core::anyhow::Result;
+use rkyv::{Archive, Deserialize, Serialize};
+
+/// A tokenizer, which converts the texts into the token ids and vice versa.
+pub trait Tokenizer {
+    /// Splits the text into the tokens, without any special tokens.
+    fn encode(&self, text: &str) -> Result<Encoding>;
+
+    /// Joins the text(s) with the special tokens, e.g. `[CLS] first [SEP] second [SEP]`.
+    fn build_inputs(&self, first: Encoding, second: Option<Encoding>) -> Result<Encoding>;
+
+    fn decode(&self, ids: &[i64], skip_special_tokens: bool) -> Result<String>;
+
+    /// Returns the id of the token only if it is in the vocabulary.
+    fn token_to_id(&self, token: &str) -> Option<i64>;
+
+    fn id_to_token(&self, id: i64) -> Option<String>;
+
+    fn special_tokens(&self) -> SpecialTokens {
+        SpecialTokens::find(|token| self.token_to_id(token))
+    }
+
+    /// Returns the number of the special tokens added by `build_inputs`.
+    fn num_special_tokens(&self, pair: bool) -> Result<usize> {
+        self.build_inputs(Encoding::default(), pair.then(Encoding::default))
+            .map(|encoding| encoding.len())
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Encoding {
+    pub ids: Vec<i64>,
+    pub type_ids: Vec<i64>,
+    pub special_tokens_mask: Vec<bool>,
+    /// The `(begin, end)` character offsets in the source text, which are missing on the special tokens.
+    pub offsets: Vec<Option<(usize, usize)>>,
+    /// The index of the source text of each token, which is missing on the special tokens.
+    pub sequence_ids: Vec<Option<usize>>,
+}
+
+impl Encoding {
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Returns the tokens in the range, keeping their offsets in the source text.
+    pub fn slice(&self, range: Range<usize>) -> Self {
+        Self {
+            ids: self.ids[range.clone()].to_vec(),
+            type_ids: self.type_ids[range.clone()].to_vec(),
+            special_tokens_mask: self.special_tokens_mask[range.clone()].to_vec(),
+            offsets: self.offsets[range.clone()].to_vec(),
+            sequence_ids: self.sequence_ids[range].to_vec(),
+        }
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.ids.truncate(len);
+        self.type_ids.truncate(len);
+        self.special_tokens_mask.truncate(len);
+        self.offsets.truncate(len);
+        self.sequence_ids.truncate(len);
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Archive, Serialize, Deserialize)]
+#[archive_attr(derive(CheckBytes, Debug, PartialEq))]
+pub struct SpecialTokens {
+    pub pad: Option<i64>,
+    pub unk: Option<i64>,
+    pub bos: Option<i64>,
+    pub eos: Option<i64>,
+    pub cls: Option<i64>,
+    pub sep: Option<i64>,
+    pub mask: Option<i64>,
+}
+
+impl SpecialTokens {
+    /// Finds the special tokens with their well-known names.
+    pub fn find(token_to_id: impl Fn(&str) -> Option<i64>) -> Self {
+        let find = |tokens: &[&str]| tokens.iter().find_map(|token| token_to_id(token));
+
+        Self {
+            pad: find(&["[PAD]", "<pad>", "<PAD>"]),
+            unk: find(&["[UNK]", "<unk>", "<UNK>"]),
+            bos: find(&["<s>", "<|endoftext|>"]),
+            eos: find(&["</s>", "<|endoftext|>"]),
+            cls: find(&["[CLS]", "<s>"]),
+            sep: find(&["[SEP]", "</s>"]),
+            mask: find(&["[MASK]", "<mask>"]),
+        }
+    }
+}
+
+/// Assigns the source texts to the tokens, skipping the special tokens.
+#[cfg(feature = "rust_tokenizers")]
+pub(crate) fn find_sequence_ids(
+    special_tokens_mask: &[bool],
+    len_first: usize,
+) -> Vec<Option<usize>> {
+    let mut index = 0;
+    special_tokens_mask
+        .iter()
+        .map(|&special| {
+            if special {
+                None
+            } else {
+                index += 1;
+                Some(if index <= len_first { 0 } else { 1 })
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,145 @@
+use ipis::core::anyhow::Result;
+use rust_tokenizers::{
+    tokenizer::{
+        AlbertTokenizer, BertTokenizer, CtrlTokenizer, DeBERTaTokenizer, DeBERTaV2Tokenizer,
+        FNetTokenizer, Gpt2Tokenizer, M2M100Tokenizer, MBart50Tokenizer, MarianTokenizer,
+        OpenAiGptTokenizer, PegasusTokenizer, ProphetNetTokenizer, ReformerTokenizer,
+        RobertaTokenizer, SentencePieceBpeTokenizer, SentencePieceTokenizer, T5Tokenizer,
+        Tokenizer as _, XLMRobertaTokenizer, XLNetTokenizer,
+    },
+    vocab::Vocab,
+    Mask, Offset, TokenIdsWithOffsets,
+};
+
+use super::{find_sequence_ids, Encoding, Tokenizer};
+
+macro_rules! impl_tokenizer {
+    ( $( $ty:ty , )* ) => {
+        $(
+            impl Tokenizer for $ty {
+                fn encode(&self, text: &str) -> Result<Encoding> {
+                    let tokens = self.tokenize_with_offsets(text);
+                    let ids = self.convert_tokens_to_ids(&tokens.tokens);
+                    let len = ids.len();
+
+                    Ok(Encoding {
+                        ids,
+                        type_ids: vec![0; len],
+                        special_tokens_mask: vec![false; len],
+                        offsets: tokens
+                            .offsets
+                            .into_iter()
+                            .map(|offset| offset.map(|e| (e.begin as usize, e.end as usize)))
+                            .collect(),
+                        sequence_ids: vec![Some(0); len],
+                    })
+                }
+
+                fn build_inputs(
+                    &self,
+                    first: Encoding,
+                    second: Option<Encoding>,
+                ) -> Result<Encoding> {
+                    let len_first = first.len();
+                    let merged = self.build_input_with_special_tokens(
+                        into_token_ids(first),
+                        second.map(into_token_ids),
+                    );
+                    let special_tokens_mask: Vec<_> = merged
+                        .special_tokens_mask
+                        .iter()
+                        .map(|&special| special != 0)
+                        .collect();
+
+                    Ok(Encoding {
+                        ids: merged.token_ids,
+                        type_ids: merged.segment_ids.into_iter().map(Into::into).collect(),
+                        sequence_ids: find_sequence_ids(&special_tokens_mask, len_first),
+                        offsets: merged
+                            .token_offsets
+                            .into_iter()
+                            .zip(&special_tokens_mask)
+                            .map(|(offset, &special)| match offset {
+                                Some(offset) if !special => {
+                                    Some((offset.begin as usize, offset.end as usize))
+                                }
+                                _ => None,
+                            })
+                            .collect(),
+                        special_tokens_mask,
+                    })
+                }
+
+                fn decode(&self, ids: &[i64], skip_special_tokens: bool) -> Result<String> {
+                    Ok(::rust_tokenizers::tokenizer::Tokenizer::decode(
+                        self,
+                        ids,
+                        skip_special_tokens,
+                        true,
+                    ))
+                }
+
+                fn token_to_id(&self, token: &str) -> Option<i64> {
+                    let vocab = self.vocab();
+                    vocab
+                        .special_values()
+                        .get(token)
+                        .or_else(|| vocab.values().get(token))
+                        .copied()
+                }
+
+                fn id_to_token(&self, id: i64) -> Option<String> {
+                    let vocab = self.vocab();
+                    vocab
+                        .special_indices()
+                        .get(&id)
+                        .or_else(|| vocab.indices().get(&id))
+                        .cloned()
+                }
+            }
+        )*
+    };
+}
+
+impl_tokenizer!(
+    AlbertTokenizer,
+    BertTokenizer,
+    CtrlTokenizer,
+    DeBERTaTokenizer,
+    DeBERTaV2Tokenizer,
+    FNetTokenizer,
+    Gpt2Tokenizer,
+    M2M100Tokenizer,
+    MBart50Tokenizer,
+    MarianTokenizer,
+    OpenAiGptTokenizer,
+    PegasusTokenizer,
+    ProphetNetTokenizer,
+    ReformerTokenizer,
+    RobertaTokenizer,
+    SentencePieceBpeTokenizer,
+    SentencePieceTokenizer,
+    T5Tokenizer,
+    XLMRobertaTokenizer,
+    XLNetTokenizer,
+);
+
+fn into_token_ids(encoding: Encoding) -> TokenIdsWithOffsets {
+    let len = encoding.len();
+
+    TokenIdsWithOffsets {
+        ids: encoding.ids,
+        offsets: encoding
+            .offsets
+            .into_iter()
+            .map(|offset| {
+                offset.map(|(begin, end)| Offset {
+                    begin: begin as u32,
+                    end: end as u32,
+                })
+            })
+            .collect(),
+        reference_offsets: vec![vec![]; len],
+        masks: vec![Mask::None; len],
+    }
+}
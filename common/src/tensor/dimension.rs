@@ -19,6 +19,11 @@ pub enum Dimensions {
     String {
         max_length: Option<usize>,
     },
+    Audio {
+        /// The number of the features of each frame, or `None` for the raw waveforms.
+        num_features: Option<usize>,
+        num_frames: Option<usize>,
+    },
 }
 
 impl IsSigned for Dimensions {}
@@ -38,10 +43,19 @@ impl Dimensions {
 
         match (self, child) {
             // Unknown
-            (Self::Unknown(parent), Self::Unknown(child)) => parent == child,
+            (Self::Unknown(parent), Self::Unknown(child)) => {
+                parent.len() == child.len()
+                    && parent
+                        .iter()
+                        .zip(child.iter())
+                        .all(|(parent, child)| try_contains(parent, child))
+            }
             (Self::Unknown(parent), Self::Class { .. }) => parent.len() == 2,
             (Self::Unknown(parent), Self::Image { .. }) => parent.len() == 4,
             (Self::Unknown(parent), Self::String { .. }) => parent.len() == 2,
+            (Self::Unknown(parent), Self::Audio { num_features, .. }) => {
+                parent.len() == if num_features.is_some() { 3 } else { 2 }
+            }
             // Class
             (
                 Self::Class {
@@ -77,6 +91,20 @@ impl Dimensions {
                     max_length: child_max_length,
                 },
             ) => try_contains(parent_max_length, child_max_length),
+            // Audio
+            (
+                Self::Audio {
+                    num_features: parent_num_features,
+                    num_frames: parent_num_frames,
+                },
+                Self::Audio {
+                    num_features: child_num_features,
+                    num_frames: child_num_frames,
+                },
+            ) => {
+                try_contains(parent_num_features, child_num_features)
+                    && try_contains(parent_num_frames, child_num_frames)
+            }
             // Otherwise
             _ => false,
         }
@@ -90,8 +118,68 @@ impl Dimensions {
                 channels,
                 width,
                 height,
-            } => vec![Some(1), Some((*channels).into()), *width, *height],
+            } => vec![Some(1), Some((*channels).into()), *height, *width],
             Dimensions::String { max_length } => vec![Some(1), *max_length],
+            Dimensions::Audio {
+                num_features: Some(num_features),
+                num_frames,
+            } => vec![Some(1), Some(*num_features), *num_frames],
+            Dimensions::Audio {
+                num_features: None,
+                num_frames,
+            } => vec![Some(1), *num_frames],
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ipis::core::{ndarray, value::array::Array};
+
+    use crate::tensor::{
+        dynamic::DynamicTensorData, shape::Shape, ty::TensorType, TensorData, ToTensor,
+    };
+
+    use super::*;
+
+    #[test]
+    fn unknown_dimensions_contain_the_known_ones() {
+        let parent = Dimensions::Unknown(vec![None, Some(8), Some(3)]);
+
+        assert!(parent.contains(&Dimensions::Unknown(vec![Some(1), Some(8), Some(3)])));
+        assert!(parent.contains(&Dimensions::Unknown(vec![Some(4), Some(8), Some(3)])));
+        assert!(parent.contains(&Dimensions::Unknown(vec![None, Some(8), Some(3)])));
+        assert!(!parent.contains(&Dimensions::Unknown(vec![Some(1), Some(4), Some(3)])));
+        assert!(!parent.contains(&Dimensions::Unknown(vec![Some(1), Some(8)])));
+        assert!(!parent.contains(&Dimensions::Unknown(vec![
+            Some(1),
+            Some(8),
+            Some(3),
+            Some(1)
+        ])));
+
+        // the known dimensions do not contain the unknown ones
+        let parent = Dimensions::Unknown(vec![Some(1), Some(8), Some(3)]);
+        assert!(parent.contains(&Dimensions::Unknown(vec![Some(1), Some(8), Some(3)])));
+        assert!(!parent.contains(&Dimensions::Unknown(vec![None, Some(8), Some(3)])));
+    }
+
+    #[test]
+    fn dynamic_data_fill_the_dynamic_axes() {
+        // e.g. a video model, taking `[batch, time, channels, height, width]`
+        let shape = Shape::new(
+            "pixel_values",
+            TensorType::F32,
+            vec![None, Some(8), Some(3), None, None],
+        )
+        .unwrap();
+        let data = |shape: &[usize]| -> TensorData {
+            DynamicTensorData::F32(Array(ndarray::ArcArray::zeros(ndarray::IxDyn(shape)))).into()
+        };
+
+        assert!(data(&[1, 8, 3, 224, 224]).to_tensor(&shape).is_ok());
+        assert!(data(&[2, 8, 3, 32, 24]).to_tensor(&shape).is_ok());
+        assert!(data(&[1, 4, 3, 224, 224]).to_tensor(&shape).is_err());
+        assert!(data(&[1, 8, 3, 224]).to_tensor(&shape).is_err());
+    }
+}
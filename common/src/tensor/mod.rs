@@ -97,6 +97,7 @@ pub enum TensorData {
     Class(self::class::ClassTensorData),
     Image(super::vision::tensor::ImageTensorData),
     String(super::nlp::tensor::StringTensorData),
+    Audio(super::audio::tensor::AudioTensorData),
 }
 
 impl IsSigned for TensorData {}
@@ -112,6 +113,7 @@ impl<'t> AsOrtTensorDyn<'t> for TensorData {
             Self::Class(v) => v.as_ort_tensor_dyn(session),
             Self::Image(v) => v.as_ort_tensor_dyn(session),
             Self::String(v) => v.as_ort_tensor_dyn(session),
+            Self::Audio(v) => v.as_ort_tensor_dyn(session),
         }
     }
 }
@@ -123,6 +125,7 @@ impl AsTensorData for TensorData {
             Self::Class(v) => v.ty(),
             Self::Image(v) => v.ty(),
             Self::String(v) => v.ty(),
+            Self::Audio(v) => v.ty(),
         }
     }
 
@@ -132,6 +135,7 @@ impl AsTensorData for TensorData {
             Self::Class(v) => v.dimensions(),
             Self::Image(v) => v.dimensions(),
             Self::String(v) => v.dimensions(),
+            Self::Audio(v) => v.dimensions(),
         }
     }
 }
@@ -165,3 +169,74 @@ pub trait AsTensorData {
 
     fn dimensions(&self) -> Dimensions;
 }
+
+#[cfg(test)]
+mod tests {
+    use ipis::core::{ndarray, value::array::Array};
+    use rkyv::ser::serializers::AllocSerializer;
+
+    use super::*;
+
+    /// Returns the archived discriminant, which the peers rely on.
+    fn archive<T>(value: &T) -> (rkyv::AlignedVec, u8)
+    where
+        T: Archive + Serialize<AllocSerializer<256>>,
+    {
+        let bytes = rkyv::to_bytes::<_, 256>(value).unwrap();
+        let archived = unsafe { rkyv::archived_root::<T>(&bytes) };
+        let tag = unsafe { *(archived as *const T::Archived as *const u8) };
+        (bytes, tag)
+    }
+
+    #[test]
+    fn dimensions_keep_their_discriminants() {
+        let dimensions = [
+            Dimensions::Unknown(vec![None, Some(3)]),
+            Dimensions::Class { num_classes: 10 },
+            Dimensions::Image {
+                channels: crate::vision::channel::ImageChannel::Rgb8,
+                width: Some(32),
+                height: Some(24),
+            },
+            Dimensions::String {
+                max_length: Some(128),
+            },
+            Dimensions::Audio {
+                num_features: Some(80),
+                num_frames: None,
+            },
+        ];
+
+        for (expected, dimensions) in dimensions.into_iter().enumerate() {
+            let (bytes, tag) = archive(&dimensions);
+            assert_eq!(tag as usize, expected);
+
+            let archived = unsafe { rkyv::archived_root::<Dimensions>(&bytes) };
+            let given: Dimensions = archived.deserialize(&mut rkyv::Infallible).unwrap();
+            assert_eq!(given, dimensions);
+        }
+    }
+
+    #[test]
+    fn tensor_data_keep_their_discriminants() {
+        let data: [TensorData; 5] = [
+            dynamic::DynamicTensorData::U8(Array(ndarray::ArcArray::zeros(ndarray::IxDyn(&[1]))))
+                .into(),
+            class::ClassTensorData::F32(Array(ndarray::ArcArray::zeros((1, 1)))).into(),
+            crate::vision::tensor::ImageTensorData::U8(Array(ndarray::ArcArray::zeros((
+                1, 1, 1, 1,
+            ))))
+            .into(),
+            crate::nlp::tensor::StringTensorData::I64(Array(ndarray::ArcArray::zeros((1, 1))))
+                .into(),
+            crate::audio::tensor::AudioTensorData::Waveform(Array(ndarray::ArcArray::zeros((
+                1, 1,
+            ))))
+            .into(),
+        ];
+
+        for (expected, data) in data.iter().enumerate() {
+            assert_eq!(archive(data).1 as usize, expected);
+        }
+    }
+}
@@ -34,7 +34,8 @@ impl Shape {
                     | [Some(1), Some(num_classes), Some(1), Some(1)] => {
                         Dimensions::Class { num_classes }
                     }
-                    [Some(1), Some(channels), width, height] => Dimensions::Image {
+                    // the images are in NCHW, so the height comes first
+                    [Some(1), Some(channels), height, width] => Dimensions::Image {
                         channels: channels.try_into()?,
                         width,
                         height,
@@ -77,3 +78,33 @@ impl TryFrom<&'_ Output> for Shape {
         Self::new(&value.name, ty, dimensions)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ipis::core::{ndarray, value::array::Array};
+
+    use crate::{
+        tensor::AsTensorData,
+        vision::{channel::ImageChannel, tensor::ImageTensorData},
+    };
+
+    use super::*;
+
+    #[test]
+    fn image_dimensions_follow_nchw() {
+        let dimensions = vec![Some(1), Some(3), Some(24), Some(32)];
+        let shape = Shape::new("pixel_values", TensorType::F32, dimensions.clone()).unwrap();
+        assert_eq!(
+            shape.dimensions,
+            Dimensions::Image {
+                channels: ImageChannel::Rgb8,
+                width: Some(32),
+                height: Some(24),
+            },
+        );
+        assert_eq!(shape.to_vec(), dimensions);
+
+        let data = ImageTensorData::F32(Array(ndarray::ArcArray::zeros((1, 3, 24, 32))));
+        assert_eq!(data.dimensions(), shape.dimensions);
+    }
+}
@@ -1,2 +1,6 @@
 pub mod channel;
 pub mod tensor;
+#[cfg(feature = "image")]
+pub mod tiling;
+#[cfg(feature = "image")]
+pub mod video;
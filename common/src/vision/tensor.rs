@@ -59,8 +59,8 @@ impl AsTensorData for ImageTensorData {
         fn dimensions_with_shape(shape: &[usize]) -> Dimensions {
             Dimensions::Image {
                 channels: shape[1].try_into().unwrap(),
-                width: Some(shape[2]),
-                height: Some(shape[3]),
+                width: Some(shape[3]),
+                height: Some(shape[2]),
             }
         }
 
@@ -74,46 +74,106 @@ impl AsTensorData for ImageTensorData {
 #[cfg(feature = "image")]
 impl ToTensor for DynamicImage {
     fn to_tensor(&self, shape: &Shape) -> anyhow::Result<Tensor> {
-        const RESIZE_FILTER: FilterType = FilterType::Nearest;
+        images_to_tensor(&[self], shape)
+    }
+}
 
-        let (channels, width, height) = match &shape.dimensions {
-            Dimensions::Image {
-                channels,
-                width,
-                height,
-            } => (*channels, *width, *height),
-            _ => bail!("only images are supported in this shape."),
-        };
+#[cfg(feature = "image")]
+impl ToTensor for Vec<DynamicImage> {
+    fn to_tensor(&self, shape: &Shape) -> anyhow::Result<Tensor> {
+        images_to_tensor(&self.iter().collect::<Vec<_>>(), shape)
+    }
+}
 
-        let image = match (width, height) {
-            (Some(width), Some(height)) => {
-                Cow::Owned(self.resize_exact(width as u32, height as u32, RESIZE_FILTER))
-            }
+/// Stacks the images into a batch, resizing them to fit the shape.
+#[cfg(feature = "image")]
+pub(crate) fn images_to_tensor(images: &[&DynamicImage], shape: &Shape) -> anyhow::Result<Tensor> {
+    let (channels, width, height) = get_image_dimensions(shape)?;
+    let data = images_to_data(images, shape.ty, channels, width, height)?;
+
+    Ok(Tensor {
+        name: shape.name.to_string(),
+        data: data.into(),
+    })
+}
+
+#[cfg(feature = "image")]
+pub(crate) fn images_to_data(
+    images: &[&DynamicImage],
+    ty: TensorType,
+    channels: ImageChannel,
+    width: Option<usize>,
+    height: Option<usize>,
+) -> anyhow::Result<ImageTensorData> {
+    const RESIZE_FILTER: FilterType = FilterType::Nearest;
+
+    let images = images
+        .iter()
+        .map(|image| match (width, height) {
+            (Some(width), Some(height)) => Ok(Cow::Owned(image.resize_exact(
+                width as u32,
+                height as u32,
+                RESIZE_FILTER,
+            ))),
             (Some(_), None) | (None, Some(_)) => bail!("scaling an image is not supported yet."),
-            (None, None) => Cow::Borrowed(self),
-        };
-        let width = width.unwrap_or(image.width() as usize);
-        let height = height.unwrap_or(image.height() as usize);
-
-        let ty = shape.ty;
-        let get_image_shape = |c| (1, c, width, height);
-        let data = match channels {
-            ImageChannel::L8 => convert_image(image.to_luma8(), ty, get_image_shape(1)),
-            ImageChannel::La8 => convert_image(image.to_luma_alpha8(), ty, get_image_shape(2)),
-            ImageChannel::Rgb8 => convert_image(image.to_rgb8(), ty, get_image_shape(3)),
-            ImageChannel::Rgba8 => convert_image(image.to_rgba8(), ty, get_image_shape(4)),
-        };
-
-        Ok(Tensor {
-            name: shape.name.to_string(),
-            data: data.into(),
+            (None, None) => Ok(Cow::Borrowed(*image)),
         })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let (width, height) = match images.first() {
+        Some(image) => (image.width(), image.height()),
+        None => bail!("empty images are given."),
+    };
+    if images
+        .iter()
+        .any(|image| image.width() != width || image.height() != height)
+    {
+        bail!("batching images with different sizes is not supported.");
+    }
+
+    let get_image_shape = |c| (images.len(), c, height as usize, width as usize);
+    Ok(match channels {
+        ImageChannel::L8 => {
+            convert_images(&images, |image| image.to_luma8(), ty, get_image_shape(1))
+        }
+        ImageChannel::La8 => convert_images(
+            &images,
+            |image| image.to_luma_alpha8(),
+            ty,
+            get_image_shape(2),
+        ),
+        ImageChannel::Rgb8 => {
+            convert_images(&images, |image| image.to_rgb8(), ty, get_image_shape(3))
+        }
+        ImageChannel::Rgba8 => {
+            convert_images(&images, |image| image.to_rgba8(), ty, get_image_shape(4))
+        }
+    })
+}
+
+/// Returns the `(channels, width, height)` of the image shape, including the dynamic batches.
+#[cfg(feature = "image")]
+pub(crate) fn get_image_dimensions(
+    shape: &Shape,
+) -> anyhow::Result<(ImageChannel, Option<usize>, Option<usize>)> {
+    match &shape.dimensions {
+        Dimensions::Image {
+            channels,
+            width,
+            height,
+        } => Ok((*channels, *width, *height)),
+        Dimensions::Unknown(dimensions) => match dimensions[..] {
+            [None, Some(channels), height, width] => Ok((channels.try_into()?, width, height)),
+            _ => bail!("only images are supported in this shape."),
+        },
+        _ => bail!("only images are supported in this shape."),
     }
 }
 
 #[cfg(feature = "image")]
-fn convert_image<I>(
-    image: I,
+fn convert_images<I>(
+    images: &[Cow<DynamicImage>],
+    convert: impl Fn(&DynamicImage) -> I,
     ty: TensorType,
     shape: (usize, usize, usize, usize),
 ) -> ImageTensorData
@@ -121,8 +181,9 @@ where
     I: GenericImageView,
     <I as GenericImageView>::Pixel: Pixel<Subpixel = u8>,
 {
-    let get_pixel = |(_, c, y, x)| {
-        let pixel = image.get_pixel(x as u32, y as u32);
+    let images: Vec<I> = images.iter().map(|image| convert(image)).collect();
+    let get_pixel = |(n, c, y, x): (usize, usize, usize, usize)| {
+        let pixel = images[n].get_pixel(x as u32, y as u32);
         let channels = pixel.channels();
         channels[c]
     };
@@ -0,0 +1,455 @@
+use image::{imageops, DynamicImage, GenericImageView};
+use ipis::{
+    async_trait::async_trait,
+    core::{
+        anyhow::{bail, Result},
+        ndarray,
+    },
+};
+
+use crate::{
+    model::Model,
+    tensor::{dynamic::DynamicTensorData, shape::Shape, Tensor, TensorData, ToTensor},
+    Ipnis,
+};
+
+use super::tensor::{get_image_dimensions, images_to_tensor};
+
+/// Splits the large images into the overlapping tiles, which fit the model.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Tiling {
+    pub width: u32,
+    pub height: u32,
+    /// The number of pixels shared by the neighboring tiles.
+    pub overlap: u32,
+}
+
+impl Tiling {
+    pub fn with_shape(shape: &Shape, overlap: u32) -> Result<Self> {
+        match get_image_dimensions(shape)? {
+            (_, Some(width), Some(height)) => Ok(Self {
+                width: width as u32,
+                height: height as u32,
+                overlap,
+            }),
+            _ => bail!("tiling requires the fixed image size."),
+        }
+    }
+
+    pub fn split(&self, image: &DynamicImage) -> Tiles {
+        let (width, height) = image.dimensions();
+
+        let tiles = positions(height, self.height, self.overlap)
+            .into_iter()
+            .flat_map(|y| {
+                positions(width, self.width, self.overlap)
+                    .into_iter()
+                    .map(move |x| (x, y))
+            })
+            .map(|(x, y)| {
+                let tile_width = self.width.min(width - x);
+                let tile_height = self.height.min(height - y);
+
+                // pad the tiles on the right and bottom edges
+                let mut tile = image.crop_imm(x, y, tile_width, tile_height);
+                if tile_width < self.width || tile_height < self.height {
+                    let mut padded = DynamicImage::new(self.width, self.height, image.color());
+                    imageops::replace(&mut padded, &tile, 0, 0);
+                    tile = padded;
+                }
+
+                Tile {
+                    x,
+                    y,
+                    width: tile_width,
+                    height: tile_height,
+                    image: tile,
+                }
+            })
+            .collect();
+
+        Tiles {
+            width,
+            height,
+            tiling: *self,
+            tiles,
+        }
+    }
+}
+
+/// Returns the tile offsets, aligning the last tile to the edge.
+fn positions(length: u32, tile: u32, overlap: u32) -> Vec<u32> {
+    if length <= tile {
+        return vec![0];
+    }
+
+    let stride = tile.saturating_sub(overlap).max(1);
+    let mut positions: Vec<_> = (0..length - tile).step_by(stride as usize).collect();
+    positions.push(length - tile);
+    positions
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    /// The width of the tile, excluding the padding.
+    pub width: u32,
+    /// The height of the tile, excluding the padding.
+    pub height: u32,
+    pub image: DynamicImage,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tiles {
+    pub width: u32,
+    pub height: u32,
+    pub tiling: Tiling,
+    pub tiles: Vec<Tile>,
+}
+
+impl Tiles {
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    pub fn batches(&self, batch_size: usize) -> impl Iterator<Item = TileBatch<'_>> {
+        self.tiles.chunks(batch_size.max(1)).map(TileBatch)
+    }
+
+    /// Merges the per-tile maps `[tiles, channels, height, width]` into `[channels, height, width]`,
+    /// averaging the overlapped pixels.
+    ///
+    /// The maps may have a different resolution from the tiles, e.g. the downsampled masks.
+    pub fn stitch_maps(&self, maps: ndarray::ArrayView4<f32>) -> Result<ndarray::Array3<f32>> {
+        let (num_tiles, channels, map_height, map_width) = maps.dim();
+        if num_tiles != self.len() {
+            let tiles = self.len();
+            bail!("unexpected maps: Expected {tiles}, Given {num_tiles}");
+        }
+
+        let scale_x = map_width as f32 / self.tiling.width as f32;
+        let scale_y = map_height as f32 / self.tiling.height as f32;
+        let scale = |value: u32, scale: f32| (value as f32 * scale).round() as usize;
+
+        let height = scale(self.height, scale_y);
+        let width = scale(self.width, scale_x);
+        let mut sum = ndarray::Array3::<f32>::zeros((channels, height, width));
+        let mut count = ndarray::Array2::<f32>::zeros((height, width));
+
+        for (tile, map) in self.tiles.iter().zip(maps.outer_iter()) {
+            let (y, x) = (scale(tile.y, scale_y), scale(tile.x, scale_x));
+            let tile_height = scale(tile.height, scale_y).min(map_height).min(height - y);
+            let tile_width = scale(tile.width, scale_x).min(map_width).min(width - x);
+
+            let region = ndarray::s![.., y..y + tile_height, x..x + tile_width];
+            let mut slot = sum.slice_mut(region);
+            slot += &map.slice(ndarray::s![.., ..tile_height, ..tile_width]);
+            count
+                .slice_mut(ndarray::s![y..y + tile_height, x..x + tile_width])
+                .mapv_inplace(|count| count + 1.0);
+        }
+
+        for ((_, y, x), value) in sum.indexed_iter_mut() {
+            *value /= count[(y, x)].max(1.0);
+        }
+        Ok(sum)
+    }
+
+    /// Merges the per-tile class scores and picks the best class of each pixel.
+    pub fn stitch_class_maps(
+        &self,
+        maps: ndarray::ArrayView4<f32>,
+    ) -> Result<ndarray::Array2<usize>> {
+        let maps = self.stitch_maps(maps)?;
+        let (_, height, width) = maps.dim();
+
+        Ok(ndarray::Array2::from_shape_fn((height, width), |(y, x)| {
+            maps.slice(ndarray::s![.., y, x])
+                .iter()
+                .enumerate()
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(class, _)| class)
+                .unwrap_or_default()
+        }))
+    }
+
+    /// Moves the per-tile detections into the image and removes the duplicates across the tiles.
+    pub fn stitch_detections(
+        &self,
+        detections: Vec<Vec<Detection>>,
+        iou_threshold: f32,
+    ) -> Result<Vec<Detection>> {
+        if detections.len() != self.len() {
+            let tiles = self.len();
+            let num_tiles = detections.len();
+            bail!("unexpected detections: Expected {tiles}, Given {num_tiles}");
+        }
+
+        let (width, height) = (self.width as f32, self.height as f32);
+        let detections = self
+            .tiles
+            .iter()
+            .zip(detections)
+            .flat_map(|(tile, detections)| {
+                detections.into_iter().filter_map(move |detection| {
+                    // clip the boxes into the image
+                    let x_min = (detection.x + tile.x as f32).clamp(0.0, width);
+                    let y_min = (detection.y + tile.y as f32).clamp(0.0, height);
+                    let x_max = (detection.x + detection.width + tile.x as f32).clamp(0.0, width);
+                    let y_max = (detection.y + detection.height + tile.y as f32).clamp(0.0, height);
+                    if x_max <= x_min || y_max <= y_min {
+                        return None;
+                    }
+
+                    Some(Detection {
+                        x: x_min,
+                        y: y_min,
+                        width: x_max - x_min,
+                        height: y_max - y_min,
+                        ..detection
+                    })
+                })
+            })
+            .collect();
+        Ok(non_maximum_suppression(detections, iou_threshold))
+    }
+}
+
+#[async_trait]
+pub trait IpnisTiling: Ipnis {
+    /// Calls the model with the batches of tiles, concatenating each output along the batch axis.
+    async fn call_tiles(
+        &self,
+        model: &Model,
+        tiles: &Tiles,
+        batch_size: usize,
+    ) -> Result<Vec<ndarray::ArrayD<f32>>> {
+        let name = match &model.inputs[..] {
+            [shape] => &shape.name,
+            inputs => {
+                let inputs = inputs.len();
+                bail!("unexpected inputs: Expected 1, Given {inputs}")
+            }
+        };
+
+        let mut outputs: Vec<Vec<ndarray::ArrayD<f32>>> = vec![];
+        for batch in tiles.batches(batch_size) {
+            let inputs = vec![(name.clone(), batch)].into_iter().collect();
+            let tensors = self.call(model, &inputs).await?;
+
+            outputs.resize_with(tensors.len(), Default::default);
+            for (output, tensor) in outputs.iter_mut().zip(tensors) {
+                match tensor.data {
+                    TensorData::Dynamic(DynamicTensorData::F32(data)) => {
+                        output.push(data.0.into_owned())
+                    }
+                    _ => {
+                        let shape = tensor.shape();
+                        bail!("unexpected TensorData: {shape:?}")
+                    }
+                }
+            }
+        }
+
+        outputs
+            .into_iter()
+            .map(|output| {
+                let views: Vec<_> = output.iter().map(|output| output.view()).collect();
+                ndarray::concatenate(ndarray::Axis(0), &views).map_err(Into::into)
+            })
+            .collect()
+    }
+}
+
+impl<T: Ipnis + ?Sized> IpnisTiling for T {}
+
+/// A batch of tiles, which can be passed to the model with the dynamic batch size.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TileBatch<'a>(pub &'a [Tile]);
+
+impl<'a> ToTensor for TileBatch<'a> {
+    fn to_tensor(&self, shape: &Shape) -> Result<Tensor> {
+        let images: Vec<_> = self.0.iter().map(|tile| &tile.image).collect();
+        images_to_tensor(&images, shape)
+    }
+}
+
+/// An axis-aligned box in pixels.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Detection {
+    pub class: usize,
+    pub score: f32,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Detection {
+    pub fn area(&self) -> f32 {
+        self.width * self.height
+    }
+
+    pub fn iou(&self, other: &Self) -> f32 {
+        let width = (self.x + self.width).min(other.x + other.width) - self.x.max(other.x);
+        let height = (self.y + self.height).min(other.y + other.height) - self.y.max(other.y);
+        if width <= 0.0 || height <= 0.0 {
+            return 0.0;
+        }
+
+        let intersection = width * height;
+        intersection / (self.area() + other.area() - intersection)
+    }
+}
+
+/// Keeps the most certain boxes, dropping the others overlapped in the same class.
+pub fn non_maximum_suppression(
+    mut detections: Vec<Detection>,
+    iou_threshold: f32,
+) -> Vec<Detection> {
+    detections.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    let mut kept: Vec<Detection> = Vec::with_capacity(detections.len());
+    for detection in detections {
+        if kept
+            .iter()
+            .all(|kept| kept.class != detection.class || kept.iou(&detection) <= iou_threshold)
+        {
+            kept.push(detection);
+        }
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detection(class: usize, score: f32, x: f32, y: f32) -> Detection {
+        Detection {
+            class,
+            score,
+            x,
+            y,
+            width: 2.0,
+            height: 2.0,
+        }
+    }
+
+    #[test]
+    fn tiles_are_aligned_to_the_edges() {
+        let tiling = Tiling {
+            width: 4,
+            height: 4,
+            overlap: 1,
+        };
+        let tiles = tiling.split(&DynamicImage::new_rgb8(10, 7));
+
+        let positions: Vec<_> = tiles.tiles.iter().map(|tile| (tile.x, tile.y)).collect();
+        assert_eq!(positions, [(0, 0), (3, 0), (6, 0), (0, 3), (3, 3), (6, 3)],);
+        for tile in &tiles.tiles {
+            assert_eq!((tile.width, tile.height), (4, 4));
+            assert_eq!(tile.image.dimensions(), (4, 4));
+        }
+    }
+
+    #[test]
+    fn small_images_are_padded() {
+        let tiling = Tiling {
+            width: 4,
+            height: 4,
+            overlap: 1,
+        };
+        let tiles = tiling.split(&DynamicImage::new_rgb8(3, 2));
+
+        assert_eq!(tiles.len(), 1);
+        let tile = &tiles.tiles[0];
+        assert_eq!((tile.x, tile.y, tile.width, tile.height), (0, 0, 3, 2));
+        assert_eq!(tile.image.dimensions(), (4, 4));
+    }
+
+    #[test]
+    fn overlapped_maps_are_averaged() {
+        let tiling = Tiling {
+            width: 4,
+            height: 4,
+            overlap: 2,
+        };
+        let tiles = tiling.split(&DynamicImage::new_rgb8(6, 4));
+        assert_eq!(tiles.len(), 2);
+
+        let maps = |size| {
+            ndarray::Array4::from_shape_fn((2, 1, size, size), |(tile, ..)| [1.0, 3.0][tile])
+        };
+
+        let stitched = tiles.stitch_maps(maps(4).view()).unwrap();
+        assert_eq!(stitched.dim(), (1, 4, 6));
+        for row in stitched.rows() {
+            assert_eq!(row.to_vec(), [1.0, 1.0, 2.0, 2.0, 3.0, 3.0]);
+        }
+
+        // the downsampled maps are stitched in their own resolution
+        let stitched = tiles.stitch_maps(maps(2).view()).unwrap();
+        assert_eq!(stitched.dim(), (1, 2, 3));
+        for row in stitched.rows() {
+            assert_eq!(row.to_vec(), [1.0, 2.0, 3.0]);
+        }
+
+        assert!(tiles
+            .stitch_maps(maps(4).slice(ndarray::s![..1, .., .., ..]))
+            .is_err());
+    }
+
+    #[test]
+    fn duplicated_detections_are_suppressed() {
+        let detections = vec![
+            detection(0, 0.8, 0.5, 0.0),
+            detection(0, 0.9, 0.0, 0.0),
+            detection(1, 0.7, 0.5, 0.0),
+            detection(0, 0.6, 5.0, 5.0),
+        ];
+
+        let kept = non_maximum_suppression(detections, 0.5);
+        assert_eq!(
+            kept,
+            [
+                detection(0, 0.9, 0.0, 0.0),
+                detection(1, 0.7, 0.5, 0.0),
+                detection(0, 0.6, 5.0, 5.0),
+            ],
+        );
+    }
+
+    #[test]
+    fn detections_are_moved_into_the_image() {
+        let tiling = Tiling {
+            width: 4,
+            height: 4,
+            overlap: 2,
+        };
+        let tiles = tiling.split(&DynamicImage::new_rgb8(6, 4));
+
+        // the same box is found by both tiles, and the clipped one is dropped
+        let detections = vec![
+            vec![detection(0, 0.9, 2.0, 1.0)],
+            vec![detection(0, 0.8, 0.0, 1.0), detection(0, 0.7, 3.0, 3.0)],
+        ];
+        let stitched = tiles.stitch_detections(detections, 0.5).unwrap();
+        assert_eq!(
+            stitched,
+            [
+                detection(0, 0.9, 2.0, 1.0),
+                Detection {
+                    width: 1.0,
+                    height: 1.0,
+                    ..detection(0, 0.7, 5.0, 3.0)
+                },
+            ],
+        );
+    }
+}
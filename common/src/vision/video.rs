@@ -0,0 +1,248 @@
+use std::{fs::File, io::BufReader};
+
+use image::{codecs::gif::GifDecoder, AnimationDecoder, DynamicImage, ImageFormat};
+use ipis::core::{
+    anyhow::{bail, Result},
+    ndarray,
+    value::array::Array,
+};
+
+use crate::tensor::{dynamic::DynamicTensorData, shape::Shape, Tensor, ToTensor};
+
+use super::tensor::{images_to_data, images_to_tensor, ImageTensorData};
+
+/// An ordered set of frames, such as a video clip.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FrameSequence {
+    pub frames: Vec<DynamicImage>,
+}
+
+impl From<Vec<DynamicImage>> for FrameSequence {
+    fn from(frames: Vec<DynamicImage>) -> Self {
+        Self { frames }
+    }
+}
+
+impl FrameSequence {
+    /// Loads the frames from a directory, an animated GIF or a single image.
+    pub fn open(path: impl AsRef<::std::path::Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if path.is_dir() {
+            return Self::from_dir(path);
+        }
+
+        match ImageFormat::from_path(path)? {
+            ImageFormat::Gif => Self::from_gif(BufReader::new(File::open(path)?)),
+            _ => Ok(Self {
+                frames: vec![::image::open(path)?],
+            }),
+        }
+    }
+
+    /// Loads the images in the directory, ordered by their file names.
+    pub fn from_dir(path: impl AsRef<::std::path::Path>) -> Result<Self> {
+        let mut paths = vec![];
+        for entry in ::std::fs::read_dir(path)? {
+            let path = entry?.path();
+            // skip the non-image files
+            if path.is_file() && ImageFormat::from_path(&path).is_ok() {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        Ok(Self {
+            frames: paths
+                .into_iter()
+                .map(::image::open)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Decodes the frames of an animated GIF.
+    pub fn from_gif(reader: impl ::std::io::Read) -> Result<Self> {
+        let frames = GifDecoder::new(reader)?.into_frames().collect_frames()?;
+
+        Ok(Self {
+            frames: frames
+                .into_iter()
+                .map(|frame| DynamicImage::ImageRgba8(frame.into_buffer()))
+                .collect(),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Keeps every `stride`-th frame.
+    pub fn sample(self, stride: usize) -> Self {
+        Self {
+            frames: self.frames.into_iter().step_by(stride.max(1)).collect(),
+        }
+    }
+
+    /// Splits the frames into the batches, e.g. for the per-frame image models.
+    pub fn batches(&self, batch_size: usize) -> impl Iterator<Item = FrameBatch<'_>> {
+        self.frames.chunks(batch_size.max(1)).map(FrameBatch)
+    }
+
+    /// Returns the clips of `length` frames, starting every `step` frames.
+    pub fn clips(&self, length: usize, step: usize) -> impl Iterator<Item = FrameBatch<'_>> {
+        self.frames
+            .windows(length.max(1))
+            .step_by(step.max(1))
+            .map(FrameBatch)
+    }
+}
+
+impl ToTensor for FrameSequence {
+    fn to_tensor(&self, shape: &Shape) -> Result<Tensor> {
+        FrameBatch(&self.frames).to_tensor(shape)
+    }
+}
+
+/// A borrowed set of frames.
+///
+/// The frames are stacked into `[batch, channels, height, width]` for the image models,
+/// or `[1, time, channels, height, width]` for the video models.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FrameBatch<'a>(pub &'a [DynamicImage]);
+
+impl<'a> ToTensor for FrameBatch<'a> {
+    fn to_tensor(&self, shape: &Shape) -> Result<Tensor> {
+        let frames: Vec<_> = self.0.iter().collect();
+
+        match shape.to_vec()[..] {
+            [batch, time, Some(channels), height, width] => {
+                if !matches!(batch, None | Some(1)) {
+                    bail!("batching multiple clips is not supported yet.");
+                }
+                match time {
+                    Some(time) if time != frames.len() => {
+                        let frames = frames.len();
+                        bail!("unexpected frames: Expected {time}, Given {frames}")
+                    }
+                    _ => (),
+                }
+
+                let data = images_to_data(&frames, shape.ty, channels.try_into()?, width, height)?;
+                let data = match data {
+                    ImageTensorData::U8(data) => DynamicTensorData::U8(Array(
+                        data.0.into_dyn().insert_axis(ndarray::Axis(0)),
+                    )),
+                    ImageTensorData::F32(data) => DynamicTensorData::F32(Array(
+                        data.0.into_dyn().insert_axis(ndarray::Axis(0)),
+                    )),
+                };
+
+                Ok(Tensor {
+                    name: shape.name.to_string(),
+                    data: data.into(),
+                })
+            }
+            _ => images_to_tensor(&frames, shape),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{codecs::gif::GifEncoder, Frame, Rgb, RgbImage, RgbaImage};
+
+    use crate::tensor::{ty::TensorType, TensorData};
+
+    use super::*;
+
+    /// Creates the frames, whose red values are their indices.
+    fn frames(len: usize) -> FrameSequence {
+        (0..len)
+            .map(|index| RgbImage::from_pixel(4, 4, Rgb([index as u8, 0, 0])).into())
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    fn indices(frames: &[DynamicImage]) -> Vec<u8> {
+        frames
+            .iter()
+            .map(|frame| frame.to_rgb8()[(0, 0)][0])
+            .collect()
+    }
+
+    /// Returns the shape of the stacked frames.
+    fn stack(frames: FrameBatch, dimensions: Vec<Option<usize>>) -> Result<Vec<usize>> {
+        let shape = Shape::new("pixel_values", TensorType::F32, dimensions)?;
+        match frames.to_tensor(&shape)?.data {
+            TensorData::Dynamic(DynamicTensorData::F32(data)) => Ok(data.shape().to_vec()),
+            TensorData::Image(ImageTensorData::F32(data)) => Ok(data.shape().to_vec()),
+            _ => bail!("unexpected tensor data"),
+        }
+    }
+
+    #[test]
+    fn frames_are_sampled_and_batched() {
+        let frames = frames(10);
+
+        assert_eq!(indices(&frames.clone().sample(3).frames), [0, 3, 6, 9]);
+        assert_eq!(indices(&frames.clone().sample(0).frames).len(), 10);
+
+        let batches: Vec<_> = frames.batches(4).map(|batch| indices(batch.0)).collect();
+        assert_eq!(batches, [vec![0, 1, 2, 3], vec![4, 5, 6, 7], vec![8, 9]]);
+
+        let clips: Vec<_> = frames.clips(4, 3).map(|clip| indices(clip.0)).collect();
+        assert_eq!(
+            clips,
+            [vec![0, 1, 2, 3], vec![3, 4, 5, 6], vec![6, 7, 8, 9]]
+        );
+    }
+
+    #[test]
+    fn clips_are_stacked_along_the_time() {
+        let frames = frames(4);
+        let clip = FrameBatch(&frames.frames);
+
+        assert_eq!(
+            stack(clip, vec![Some(1), Some(4), Some(3), Some(2), Some(2)]).unwrap(),
+            [1, 4, 3, 2, 2],
+        );
+        assert_eq!(
+            stack(clip, vec![None, None, Some(3), Some(4), Some(4)]).unwrap(),
+            [1, 4, 3, 4, 4],
+        );
+        assert!(stack(clip, vec![Some(1), Some(8), Some(3), Some(2), Some(2)]).is_err());
+        assert!(stack(clip, vec![Some(2), Some(4), Some(3), Some(2), Some(2)]).is_err());
+    }
+
+    #[test]
+    fn frames_are_stacked_along_the_batch() {
+        let frames = frames(4);
+
+        assert_eq!(
+            stack(
+                FrameBatch(&frames.frames),
+                vec![None, Some(3), Some(2), Some(2)]
+            )
+            .unwrap(),
+            [4, 3, 2, 2],
+        );
+    }
+
+    #[test]
+    fn gif_frames_are_decoded() {
+        let mut gif = vec![];
+        GifEncoder::new(&mut gif)
+            .encode_frames((0..3).map(|_| Frame::new(RgbaImage::new(4, 4))))
+            .unwrap();
+
+        let frames = FrameSequence::from_gif(&gif[..]).unwrap();
+        assert_eq!(frames.len(), 3);
+        assert!(frames
+            .frames
+            .iter()
+            .all(|frame| (frame.width(), frame.height()) == (4, 4)));
+    }
+}
@@ -0,0 +1,75 @@
+use std::env;
+
+use ipis::{
+    core::anyhow::{anyhow, bail, Result},
+    env::Infer,
+    path::Path,
+    tokio,
+};
+use ipnis_api::{client::IpnisClientInner, common::Ipnis};
+use ipnis_modules_speech_recognition::{
+    vocabulary::Vocabulary, Audio, CtcDecoder, IpnisSpeechRecognition, SpeechRecognitionConfig,
+};
+use ipsis_api::client::IpsisClient;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // create a client
+    let client = IpnisClientInner::<IpsisClient>::try_infer().await?;
+
+    // load a model (e.g. facebook/wav2vec2-base-960h)
+    // NOTE: you can export it manually with: "optimum-cli export onnx --model facebook/wav2vec2-base-960h --task automatic-speech-recognition"
+    let model = client
+        .load_model(&get_path_from_env("IPNIS_SPEECH_RECOGNITION_MODEL")?)
+        .await?;
+
+    // load the vocabulary from the model's vocab.json
+    let vocabulary = Vocabulary::from_file(get_env("IPNIS_SPEECH_RECOGNITION_VOCAB")?)?;
+
+    // restrict the words to the lexicon, which has a word per line, if given
+    let mut config = SpeechRecognitionConfig::default();
+    if let Ok(path) = env::var("IPNIS_SPEECH_RECOGNITION_LEXICON") {
+        let words = ::std::fs::read_to_string(path)?;
+        config.decoder = CtcDecoder::BeamSearch { beam_width: 16 };
+        config.lexicon = Some(vocabulary.lexicon(words.split_whitespace())?);
+    }
+
+    // load a recording
+    let audio = Audio::open_wav(get_env("IPNIS_SPEECH_RECOGNITION_AUDIO")?)?;
+
+    // perform the inference
+    let outputs = client
+        .call_speech_recognition(&model, &vocabulary, &config, &[audio])
+        .await?;
+
+    // show the result
+    for (batch, output) in outputs.answers.into_iter().enumerate() {
+        let batch = batch + 1;
+        let text = &output.text;
+        println!("Transcription for data {batch}th = {text}");
+
+        for word in output.words {
+            let start = word.start;
+            let end = word.end;
+            let word = &word.word;
+            println!("  [{start:.2}s - {end:.2}s] {word}");
+        }
+    }
+    Ok(())
+}
+
+fn get_env(key: &str) -> Result<String> {
+    env::var(key).map_err(|_| anyhow!("environment variable is not set: {key}"))
+}
+
+/// Parses a stored path, formatted as `{cid}:{length}`.
+fn get_path_from_env(key: &str) -> Result<Path> {
+    let path = get_env(key)?;
+    match path.split_once(':') {
+        Some((value, len)) => Ok(Path {
+            value: value.parse()?,
+            len: len.parse()?,
+        }),
+        None => bail!("malformed path: {path}"),
+    }
+}
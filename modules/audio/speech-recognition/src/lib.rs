@@ -0,0 +1,479 @@
+pub mod vocabulary;
+
+pub use ipnis_common::{
+    audio::{
+        feature::{AudioFeature, MelConfig, MfccConfig, WaveformConfig},
+        waveform::Audio,
+    },
+    ctc::{CtcDecoder, Lexicon},
+};
+
+use std::{collections::HashMap, ops::Range};
+
+use ipis::{
+    async_trait::async_trait,
+    core::{
+        anyhow::{bail, Result},
+        ndarray,
+        ordered_float::OrderedFloat,
+        value::array::Array,
+    },
+};
+use ipnis_common::{
+    audio::feature::AudioInputs,
+    ctc::CtcToken,
+    model::Model,
+    nlp::tensor::StringTensorData,
+    onnxruntime::tensor::ndarray_tensor::NdArrayTensor,
+    tensor::{shape::Shape, Tensor, TensorData, ToTensor},
+    Ipnis,
+};
+
+use crate::vocabulary::Vocabulary;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpeechRecognitionConfig {
+    pub feature: AudioFeature,
+    pub decoder: CtcDecoder,
+    /// Keeps only these words with the beam search, if given.
+    pub lexicon: Option<Lexicon>,
+    /// Splits the longer recordings into the chunks of this duration, in seconds.
+    pub chunk_length: f32,
+    /// The duration shared with the neighboring chunks on each side, in seconds.
+    /// The outputs of the shared parts are dropped, as they lack the context.
+    pub stride_length: f32,
+    pub batch_size: usize,
+}
+
+impl Default for SpeechRecognitionConfig {
+    fn default() -> Self {
+        Self {
+            feature: Default::default(),
+            decoder: Default::default(),
+            lexicon: None,
+            chunk_length: 20.0,
+            stride_length: 3.0,
+            batch_size: 4,
+        }
+    }
+}
+
+impl SpeechRecognitionConfig {
+    /// Validates the config, returning the chunk and stride lengths in samples.
+    fn validate(&self) -> Result<(usize, usize)> {
+        if self.lexicon.is_some() && self.decoder == CtcDecoder::Greedy {
+            bail!("the lexicon is supported with the beam search only");
+        }
+
+        let sample_rate = self.feature.sample_rate() as f32;
+        let chunk_length = (self.chunk_length * sample_rate) as usize;
+        let stride_length = (self.stride_length * sample_rate) as usize;
+        if chunk_length <= 2 * stride_length {
+            let chunk_length = self.chunk_length;
+            let stride_length = self.stride_length;
+            bail!("the chunks are too short: {chunk_length} <= 2 * {stride_length}");
+        }
+        Ok((chunk_length, stride_length))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Outputs {
+    pub answers: Vec<Output>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Output {
+    pub text: String,
+    pub score: OrderedFloat<f32>,
+    pub words: Vec<Word>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Word {
+    pub word: String,
+    /// The `start` and `end` timestamps in seconds.
+    pub start: f32,
+    pub end: f32,
+}
+
+#[async_trait]
+pub trait IpnisSpeechRecognition: Ipnis {
+    /// Transcribes the recordings with a CTC model, e.g. wav2vec2.
+    async fn call_speech_recognition(
+        &self,
+        model: &Model,
+        vocabulary: &Vocabulary,
+        config: &SpeechRecognitionConfig,
+        audios: &[Audio],
+    ) -> Result<Outputs> {
+        let shape = find_input(model)?;
+        let sample_rate = config.feature.sample_rate();
+        let (chunk_length, stride_length) = config.validate()?;
+
+        let mut answers = Vec::with_capacity(audios.len());
+        for audio in audios {
+            let samples = audio.to_mono_samples(sample_rate);
+            if samples.is_empty() {
+                answers.push(Output {
+                    text: Default::default(),
+                    score: Default::default(),
+                    words: Default::default(),
+                });
+                continue;
+            }
+
+            let chunks = split_chunks(samples.len(), chunk_length, stride_length);
+
+            // stitch the logits of the chunks, dropping the strides
+            let mut logits = vec![];
+            let mut samples_per_frame = None;
+            for chunks in chunks.chunks(config.batch_size.max(1)) {
+                let inputs = into_inputs(model, shape, config, &samples, chunks)?;
+                let mut outputs = self.call(model, &inputs).await?;
+                if outputs.is_empty() {
+                    let outputs = outputs.len();
+                    bail!("unexpected outputs: Expected 1, Given {outputs}");
+                }
+
+                let outputs: Tensor<StringTensorData> =
+                    Tensor::find(&mut outputs, "logits")?.try_into()?;
+                let outputs = match outputs.data {
+                    StringTensorData::F32Embedding(outputs)
+                        if outputs.shape()[0] == chunks.len() =>
+                    {
+                        outputs.0
+                    }
+                    _ => {
+                        let shape = outputs.shape();
+                        bail!("unexpected logits shape: {shape:?}")
+                    }
+                };
+
+                // the frames are proportional to the samples
+                let num_samples = chunks.iter().map(Chunk::len).max().unwrap_or_default();
+                let ratio = outputs.shape()[1] as f32 / num_samples.max(1) as f32;
+                samples_per_frame.get_or_insert(1.0 / ratio);
+
+                for (chunk, outputs) in chunks.iter().zip(outputs.outer_iter()) {
+                    let frames = chunk.frames(ratio, outputs.nrows());
+                    logits.push(outputs.slice(ndarray::s![frames, ..]).to_owned());
+                }
+            }
+
+            let logits = ndarray::concatenate(
+                ndarray::Axis(0),
+                &logits
+                    .iter()
+                    .map(|logits| logits.view())
+                    .collect::<Vec<_>>(),
+            )?;
+            if logits.ncols() != vocabulary.len() {
+                let expected = vocabulary.len();
+                let given = logits.ncols();
+                bail!("unexpected vocabulary: Expected {expected}, Given {given}");
+            }
+
+            // decode the words
+            let probs = logits.softmax(ndarray::Axis(1));
+            let output =
+                config
+                    .decoder
+                    .decode(probs.view(), vocabulary.blank, config.lexicon.as_ref());
+
+            let seconds_per_frame = samples_per_frame.unwrap_or_default() / sample_rate as f32;
+            let words = to_words(vocabulary, &output.tokens, seconds_per_frame);
+
+            answers.push(Output {
+                text: words
+                    .iter()
+                    .map(|word| word.word.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                score: OrderedFloat(output.score),
+                words,
+            });
+        }
+        Ok(Outputs { answers })
+    }
+}
+
+impl<T: Ipnis + ?Sized> IpnisSpeechRecognition for T {}
+
+/// A range of the samples, sharing the strides with the neighboring chunks.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Chunk {
+    start: usize,
+    end: usize,
+    stride_left: usize,
+    stride_right: usize,
+}
+
+impl Chunk {
+    fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Returns the frames of the outputs without the strides, given the frames per sample.
+    fn frames(&self, ratio: f32, num_frames: usize) -> Range<usize> {
+        let to_frames = |samples: usize| (samples as f32 * ratio).round() as usize;
+        let end = to_frames(self.len()).min(num_frames);
+        let begin = to_frames(self.stride_left).min(end);
+        let end = end.saturating_sub(to_frames(self.stride_right)).max(begin);
+        begin..end
+    }
+}
+
+fn split_chunks(num_samples: usize, chunk_length: usize, stride_length: usize) -> Vec<Chunk> {
+    let step = chunk_length - 2 * stride_length;
+
+    let mut chunks = vec![];
+    let mut start = 0;
+    loop {
+        let end = (start + chunk_length).min(num_samples);
+        chunks.push(Chunk {
+            start,
+            end,
+            stride_left: if start == 0 { 0 } else { stride_length },
+            stride_right: if end == num_samples { 0 } else { stride_length },
+        });
+
+        if end == num_samples {
+            break chunks;
+        }
+        start += step;
+    }
+}
+
+/// Converts the frames of the words into the timestamps.
+fn to_words(vocabulary: &Vocabulary, tokens: &[CtcToken], seconds_per_frame: f32) -> Vec<Word> {
+    vocabulary
+        .words(tokens)
+        .into_iter()
+        .map(|(word, start, end)| Word {
+            word,
+            start: start as f32 * seconds_per_frame,
+            end: end as f32 * seconds_per_frame,
+        })
+        .collect()
+}
+
+fn find_input(model: &Model) -> Result<&Shape> {
+    match model
+        .inputs
+        .iter()
+        .find(|shape| shape.name != "attention_mask")
+    {
+        Some(shape) => Ok(shape),
+        None => bail!("the model has no inputs"),
+    }
+}
+
+/// Pads the chunks of the batch, masking the padding if the model accepts the `attention_mask`.
+fn into_inputs(
+    model: &Model,
+    shape: &Shape,
+    config: &SpeechRecognitionConfig,
+    samples: &[f32],
+    chunks: &[Chunk],
+) -> Result<HashMap<String, Box<dyn ToTensor + Send + Sync>>> {
+    let sample_rate = config.feature.sample_rate();
+
+    let mut inputs = HashMap::default();
+    inputs.insert(
+        shape.name.clone(),
+        Box::new(AudioInputs {
+            audios: chunks
+                .iter()
+                .map(|chunk| Audio::new_mono(sample_rate, samples[chunk.start..chunk.end].to_vec()))
+                .collect(),
+            feature: config.feature.clone(),
+        }) as Box<dyn ToTensor + Send + Sync>,
+    );
+
+    if model
+        .inputs
+        .iter()
+        .any(|shape| shape.name == "attention_mask")
+    {
+        if !matches!(config.feature, AudioFeature::Waveform(_)) {
+            bail!("the attention mask is supported for the waveforms only");
+        }
+
+        let num_samples = chunks.iter().map(Chunk::len).max().unwrap_or_default();
+        let attention_mask =
+            ndarray::Array::from_shape_fn((chunks.len(), num_samples), |(batch, index)| {
+                (index < chunks[batch].len()) as i64
+            });
+        inputs.insert(
+            "attention_mask".into(),
+            Box::new(TensorData::from(StringTensorData::I64(Array(
+                attention_mask.into(),
+            )))) as Box<dyn ToTensor + Send + Sync>,
+        );
+    }
+    Ok(inputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that the chunks cover the samples, and the kept parts do not overlap.
+    fn assert_chunks(num_samples: usize, chunk_length: usize, stride_length: usize) {
+        let chunks = split_chunks(num_samples, chunk_length, stride_length);
+
+        let mut kept = 0;
+        for (index, chunk) in chunks.iter().enumerate() {
+            assert!(chunk.len() <= chunk_length);
+            assert_eq!(
+                chunk.stride_left,
+                if index == 0 { 0 } else { stride_length }
+            );
+            assert_eq!(
+                chunk.stride_right,
+                if index + 1 == chunks.len() {
+                    0
+                } else {
+                    stride_length
+                },
+            );
+
+            // the kept parts are stitched next to each other
+            let frames = chunk.frames(1.0, chunk.len());
+            assert_eq!(chunk.start + frames.start, kept);
+            kept = chunk.start + frames.end;
+        }
+        assert_eq!(chunks.last().unwrap().end, num_samples);
+        assert_eq!(kept, num_samples);
+    }
+
+    #[test]
+    fn chunks_share_the_strides() {
+        assert_eq!(
+            split_chunks(25, 10, 2),
+            [
+                Chunk {
+                    start: 0,
+                    end: 10,
+                    stride_left: 0,
+                    stride_right: 2,
+                },
+                Chunk {
+                    start: 6,
+                    end: 16,
+                    stride_left: 2,
+                    stride_right: 2,
+                },
+                Chunk {
+                    start: 12,
+                    end: 22,
+                    stride_left: 2,
+                    stride_right: 2,
+                },
+                Chunk {
+                    start: 18,
+                    end: 25,
+                    stride_left: 2,
+                    stride_right: 0,
+                },
+            ],
+        );
+
+        for num_samples in [1, 9, 10, 11, 16, 100, 1001] {
+            assert_chunks(num_samples, 10, 2);
+            assert_chunks(num_samples, 10, 0);
+        }
+    }
+
+    #[test]
+    fn strides_are_dropped_in_frames() {
+        let chunk = Chunk {
+            start: 32_000,
+            end: 352_000,
+            stride_left: 48_000,
+            stride_right: 48_000,
+        };
+
+        // 320 samples per frame
+        assert_eq!(chunk.frames(1.0 / 320.0, 1000), 150..850);
+        // the outputs may be shorter than expected
+        assert_eq!(chunk.frames(1.0 / 320.0, 999), 150..849);
+        assert_eq!(chunk.frames(1.0 / 320.0, 100), 100..100);
+    }
+
+    #[test]
+    fn frames_are_converted_into_seconds() {
+        let vocabulary =
+            Vocabulary::from_json_str(r#"{ "<pad>": 0, "|": 1, "A": 2, "B": 3 }"#).unwrap();
+        let tokens = [
+            CtcToken {
+                index: 2,
+                start: 50,
+                end: 51,
+            },
+            CtcToken {
+                index: 1,
+                start: 60,
+                end: 61,
+            },
+            CtcToken {
+                index: 3,
+                start: 100,
+                end: 102,
+            },
+        ];
+
+        // 320 samples per frame, at 16 kHz
+        let words = to_words(&vocabulary, &tokens, 320.0 / 16_000.0);
+        assert_eq!(
+            words,
+            [
+                Word {
+                    word: "A".into(),
+                    start: 1.0,
+                    end: 1.02,
+                },
+                Word {
+                    word: "B".into(),
+                    start: 2.0,
+                    end: 2.04,
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn lexicon_requires_the_beam_search() {
+        let lexicon = Lexicon::new([vec![2]], 1);
+        let config = SpeechRecognitionConfig {
+            lexicon: Some(lexicon.clone()),
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+
+        let config = SpeechRecognitionConfig {
+            decoder: CtcDecoder::BeamSearch { beam_width: 8 },
+            lexicon: Some(lexicon),
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn chunks_should_be_longer_than_the_strides() {
+        let config = SpeechRecognitionConfig {
+            chunk_length: 6.0,
+            stride_length: 3.0,
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+
+        let config = SpeechRecognitionConfig::default();
+        let sample_rate = config.feature.sample_rate() as usize;
+        assert_eq!(
+            config.validate().unwrap(),
+            (20 * sample_rate, 3 * sample_rate),
+        );
+    }
+}
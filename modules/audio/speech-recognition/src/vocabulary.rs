@@ -0,0 +1,186 @@
+use std::collections::BTreeMap;
+
+use ipis::core::anyhow::{bail, Result};
+use ipnis_common::ctc::{CtcToken, Lexicon};
+
+/// The CTC classes of the model, ordered by their indices.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Vocabulary {
+    tokens: Vec<String>,
+    /// The CTC blank, which is the padding token of wav2vec2.
+    pub blank: usize,
+    /// Separates the words, e.g. `|`.
+    pub delimiter: Option<usize>,
+}
+
+impl Vocabulary {
+    pub const DEFAULT_BLANK: &'static str = "<pad>";
+    pub const DEFAULT_DELIMITER: &'static str = "|";
+
+    /// Loads the `vocab.json`, which maps the tokens into their indices.
+    pub fn from_file(path: impl AsRef<::std::path::Path>) -> Result<Self> {
+        Self::from_json_str(&::std::fs::read_to_string(path)?)
+    }
+
+    pub fn from_json_str(vocab: &str) -> Result<Self> {
+        let vocab: BTreeMap<String, usize> = ::serde_json::from_str(vocab)?;
+
+        let mut tokens = vec![None; vocab.len()];
+        for (token, index) in vocab {
+            match tokens.get_mut(index) {
+                Some(slot @ None) => *slot = Some(token),
+                Some(Some(_)) => bail!("duplicated token index: {index}"),
+                None => bail!("token index out of range: {token} => {index}"),
+            }
+        }
+
+        let tokens: Vec<_> = tokens.into_iter().flatten().collect();
+        let find = |name| tokens.iter().position(|token| token == name);
+        Ok(Self {
+            blank: match find(Self::DEFAULT_BLANK) {
+                Some(blank) => blank,
+                None => bail!("the vocabulary has no blank token: {}", Self::DEFAULT_BLANK),
+            },
+            delimiter: find(Self::DEFAULT_DELIMITER),
+            tokens,
+        })
+    }
+
+    /// Returns the number of classes, including the blank.
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Spells each word with the tokens, trying the uppercase letters if not found.
+    pub fn lexicon<I>(&self, words: I) -> Result<Lexicon>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let delimiter = match self.delimiter {
+            Some(delimiter) => delimiter,
+            None => bail!("the vocabulary has no word delimiter"),
+        };
+
+        let find = |token: &str| self.tokens.iter().position(|given| given == token);
+        let words = words
+            .into_iter()
+            .map(|word| {
+                let word = word.as_ref();
+                word.chars()
+                    .map(|c| {
+                        match find(&c.to_string()).or_else(|| find(&c.to_uppercase().to_string())) {
+                            Some(index) => Ok(index),
+                            None => bail!("unknown character in the lexicon: {c:?} ({word})"),
+                        }
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Lexicon::new(words, delimiter))
+    }
+
+    /// Groups the tokens into the words, skipping the special tokens like `<unk>`.
+    pub(crate) fn words(&self, tokens: &[CtcToken]) -> Vec<(String, usize, usize)> {
+        let mut words: Vec<(String, usize, usize)> = vec![];
+        let mut is_finished = true;
+        for token in tokens {
+            if Some(token.index) == self.delimiter {
+                is_finished = true;
+                continue;
+            }
+
+            let text = match self.tokens.get(token.index) {
+                Some(text) if !(text.starts_with('<') && text.ends_with('>')) => text,
+                _ => continue,
+            };
+            match words.last_mut() {
+                Some((word, _, end)) if !is_finished => {
+                    word.push_str(text);
+                    *end = token.end;
+                }
+                _ => words.push((text.clone(), token.start, token.end)),
+            }
+            is_finished = false;
+        }
+        words
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vocabulary() -> Vocabulary {
+        Vocabulary::from_json_str(
+            r#"{ "<pad>": 0, "<s>": 1, "</s>": 2, "<unk>": 3, "|": 4, "E": 5, "H": 6, "L": 7, "O": 8 }"#,
+        )
+        .unwrap()
+    }
+
+    fn tokens(indices: &[usize]) -> Vec<CtcToken> {
+        indices
+            .iter()
+            .enumerate()
+            .map(|(frame, &index)| CtcToken {
+                index,
+                start: 2 * frame,
+                end: 2 * frame + 1,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tokens_are_ordered_by_indices() {
+        let vocabulary = Vocabulary::from_json_str(r#"{ "|": 2, "a": 1, "<pad>": 0 }"#).unwrap();
+
+        assert_eq!(vocabulary.tokens, ["<pad>", "a", "|"]);
+        assert_eq!(vocabulary.blank, 0);
+        assert_eq!(vocabulary.delimiter, Some(2));
+        assert_eq!(vocabulary.len(), 3);
+    }
+
+    #[test]
+    fn malformed_vocabularies_are_rejected() {
+        for vocab in [
+            // no blank
+            r#"{ "a": 0, "|": 1 }"#,
+            r#"{ "<pad>": 0, "a": 0 }"#,
+            r#"{ "<pad>": 0, "a": 2 }"#,
+            r#"["<pad>", "a"]"#,
+        ] {
+            assert!(Vocabulary::from_json_str(vocab).is_err(), "{vocab}");
+        }
+
+        let vocabulary = Vocabulary::from_json_str(r#"{ "a": 0, "<pad>": 1 }"#).unwrap();
+        assert_eq!(vocabulary.blank, 1);
+        assert_eq!(vocabulary.delimiter, None);
+    }
+
+    #[test]
+    fn tokens_are_grouped_into_words() {
+        let vocabulary = vocabulary();
+        // "HE|<unk>|LLO|", with a leading delimiter and the special tokens
+        let words = vocabulary.words(&tokens(&[4, 6, 5, 4, 3, 4, 7, 1, 7, 8, 4]));
+
+        assert_eq!(words, [("HE".into(), 2, 5), ("LLO".into(), 12, 19)],);
+    }
+
+    #[test]
+    fn words_are_spelled_with_the_tokens() {
+        let vocabulary = vocabulary();
+
+        assert_eq!(
+            vocabulary.lexicon(["hello", "OLE"]).unwrap(),
+            Lexicon::new([vec![6, 5, 7, 7, 8], vec![8, 7, 5]], 4),
+        );
+        assert!(vocabulary.lexicon(["world"]).is_err());
+
+        let vocabulary = Vocabulary::from_json_str(r#"{ "<pad>": 0, "a": 1 }"#).unwrap();
+        assert!(vocabulary.lexicon(["a"]).is_err());
+    }
+}
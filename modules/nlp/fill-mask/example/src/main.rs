@@ -0,0 +1,69 @@
+use std::env;
+
+use ipis::{
+    core::anyhow::{anyhow, bail, Result},
+    env::Infer,
+    path::Path,
+    tokio,
+};
+use ipnis_api::{
+    client::IpnisClientInner,
+    common::{nlp::input::TextInputs, tokenizers::Tokenizer, Ipnis},
+};
+use ipnis_modules_fill_mask::IpnisFillMask;
+use ipsis_api::client::IpsisClient;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // create a client
+    let client = IpnisClientInner::<IpsisClient>::try_infer().await?;
+
+    // load a model (e.g. bert-base-uncased)
+    // NOTE: you can export it manually with: "optimum-cli export onnx --model bert-base-uncased --task fill-mask"
+    let model = client
+        .load_model(&get_path_from_env("IPNIS_FILL_MASK_MODEL")?)
+        .await?;
+
+    // create a tokenizer
+    let tokenizer =
+        Tokenizer::from_file(get_env("IPNIS_FILL_MASK_TOKENIZER")?).map_err(|e| anyhow!(e))?;
+
+    // make a sample inputs
+    let inputs = TextInputs {
+        text: vec!["Paris is the [MASK] of France.".into()],
+    };
+
+    // perform the inference
+    let outputs = client
+        .call_fill_mask(&model, &tokenizer, &Default::default(), inputs)
+        .await?;
+
+    // show the result
+    for (batch, output) in outputs.answers.into_iter().enumerate() {
+        let batch = batch + 1;
+        for mask in output.masks {
+            for candidate in mask.candidates {
+                let sequence = &candidate.sequence;
+                let score = candidate.score;
+                println!("Candidate for data {batch}th = {sequence} ({score})");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn get_env(key: &str) -> Result<String> {
+    env::var(key).map_err(|_| anyhow!("environment variable is not set: {key}"))
+}
+
+/// Parses a stored path, formatted as `{cid}:{length}`.
+fn get_path_from_env(key: &str) -> Result<Path> {
+    let path = get_env(key)?;
+    match path.split_once(':') {
+        Some((value, len)) => Ok(Path {
+            value: value.parse()?,
+            len: len.parse()?,
+        }),
+        None => bail!("malformed path: {path}"),
+    }
+}
@@ -0,0 +1,211 @@
+use ipis::{
+    async_trait::async_trait,
+    core::{
+        anyhow::{anyhow, bail, Result},
+        ndarray,
+        ordered_float::OrderedFloat,
+    },
+};
+use ipnis_common::{
+    model::Model,
+    nlp::{
+        input::{TextInputs, TokenizeOptions, Tokenized},
+        tensor::StringTensorData,
+        tokenizer::Tokenizer,
+    },
+    onnxruntime::tensor::ndarray_tensor::NdArrayTensor,
+    tensor::Tensor,
+    Ipnis,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FillMaskConfig {
+    /// The number of the candidates of each mask.
+    pub top_k: usize,
+    /// Restricts the candidates to these words, if given.
+    pub targets: Option<Vec<String>>,
+}
+
+impl Default for FillMaskConfig {
+    fn default() -> Self {
+        Self {
+            top_k: 5,
+            targets: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outputs {
+    pub answers: Vec<Output>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Output {
+    pub text: String,
+    /// The candidates of each mask, in order of appearance.
+    pub masks: Vec<Mask>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mask {
+    /// The index of the mask token in the tokenized sequence.
+    pub position: usize,
+    /// The candidates, sorted by their scores in descending order.
+    pub candidates: Vec<Candidate>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Candidate {
+    pub token: i64,
+    pub token_str: String,
+    pub score: OrderedFloat<f32>,
+    /// The text with the mask filled by the token.
+    pub sequence: String,
+}
+
+#[async_trait]
+pub trait IpnisFillMask: Ipnis {
+    /// Predicts the tokens hidden behind the mask tokens of the texts.
+    async fn call_fill_mask<T>(
+        &self,
+        model: &Model,
+        tokenizer: &T,
+        config: &FillMaskConfig,
+        inputs: TextInputs,
+    ) -> Result<Outputs>
+    where
+        T: Tokenizer + ?Sized + Sync,
+    {
+        if inputs.text.is_empty() {
+            return Ok(Outputs {
+                answers: Default::default(),
+            });
+        }
+
+        let token_mask = tokenizer
+            .special_tokens()
+            .mask
+            .ok_or_else(|| anyhow!("the tokenizer has no mask token"))?;
+        let targets = match &config.targets {
+            Some(targets) => Some(find_targets(tokenizer, targets)?),
+            None => None,
+        };
+
+        let Tokenized {
+            inputs,
+            inputs_str,
+            encodings,
+            ..
+        } = inputs.tokenize_with(tokenizer, &TokenizeOptions::default().with_model(model))?;
+
+        // find the masks
+        let masks: Vec<Vec<_>> = encodings
+            .iter()
+            .map(|encoding| {
+                encoding
+                    .ids
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, &id)| id == token_mask)
+                    .map(|(position, _)| position)
+                    .collect()
+            })
+            .collect();
+        if let Some(index) = masks.iter().position(Vec::is_empty) {
+            let text = &inputs_str[index].text_1;
+            bail!("no mask token in the input: {text}");
+        }
+
+        let mut outputs = self.call(model, &inputs).await?;
+        if outputs.is_empty() {
+            let outputs = outputs.len();
+            bail!("unexpected outputs: Expected 1, Given {outputs}");
+        }
+
+        let logits: Tensor<StringTensorData> = Tensor::find(&mut outputs, "logits")?.try_into()?;
+
+        match &logits.data {
+            StringTensorData::F32Embedding(logits) => Ok(Outputs {
+                answers: inputs_str
+                    .into_iter()
+                    .zip(encodings)
+                    .zip(masks)
+                    .zip(logits.outer_iter())
+                    .map(|(((input, encoding), masks), logits)| {
+                        Ok(Output {
+                            text: input.text_1,
+                            masks: masks
+                                .into_iter()
+                                .map(|position| {
+                                    let probs = logits.row(position).softmax(ndarray::Axis(0));
+
+                                    // collect the most probable tokens
+                                    let mut tokens: Vec<_> = match &targets {
+                                        Some(targets) => targets
+                                            .iter()
+                                            .copied()
+                                            .filter(|&token| (token as usize) < probs.len())
+                                            .collect(),
+                                        None => (0..probs.len() as i64).collect(),
+                                    };
+                                    tokens.sort_unstable_by(|&a, &b| {
+                                        probs[b as usize].total_cmp(&probs[a as usize])
+                                    });
+                                    tokens.truncate(config.top_k);
+
+                                    Ok(Mask {
+                                        position,
+                                        candidates: tokens
+                                            .into_iter()
+                                            .map(|token| {
+                                                let mut ids = encoding.ids.clone();
+                                                ids[position] = token;
+
+                                                Ok(Candidate {
+                                                    token,
+                                                    token_str: tokenizer
+                                                        .decode(&[token], false)?
+                                                        .trim()
+                                                        .to_string(),
+                                                    score: OrderedFloat(probs[token as usize]),
+                                                    sequence: tokenizer.decode(&ids, true)?,
+                                                })
+                                            })
+                                            .collect::<Result<_>>()?,
+                                    })
+                                })
+                                .collect::<Result<_>>()?,
+                        })
+                    })
+                    .collect::<Result<_>>()?,
+            }),
+            _ => {
+                let logits = logits.shape();
+                bail!("unexpected StringTensorData: {logits:?}")
+            }
+        }
+    }
+}
+
+impl<T: Ipnis + ?Sized> IpnisFillMask for T {}
+
+/// Converts the target words into the tokens, taking the first token of the unknown words.
+fn find_targets<T>(tokenizer: &T, targets: &[String]) -> Result<Vec<i64>>
+where
+    T: Tokenizer + ?Sized,
+{
+    let mut tokens = targets
+        .iter()
+        .map(|target| match tokenizer.token_to_id(target) {
+            Some(token) => Ok(token),
+            None => match tokenizer.encode(target)?.ids.first() {
+                Some(&token) => Ok(token),
+                None => bail!("empty target: {target:?}"),
+            },
+        })
+        .collect::<Result<Vec<_>>>()?;
+    tokens.sort_unstable();
+    tokens.dedup();
+    Ok(tokens)
+}
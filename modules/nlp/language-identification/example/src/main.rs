@@ -0,0 +1,83 @@
+use std::env;
+
+use ipis::{
+    core::{
+        anyhow::{anyhow, bail, Result},
+        value::text::LanguageTag,
+    },
+    env::Infer,
+    path::Path,
+    tokio,
+};
+use ipnis_api::{
+    client::IpnisClientInner,
+    common::{nlp::input::TextInputs, tokenizers::Tokenizer, Ipnis},
+};
+use ipnis_modules_language_identification::{IpnisLanguageIdentification, LabelSet};
+use ipsis_api::client::IpsisClient;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // create a client
+    let client = IpnisClientInner::<IpsisClient>::try_infer().await?;
+
+    // load a model (e.g. papluca/xlm-roberta-base-language-detection)
+    // NOTE: you can export it manually with: "optimum-cli export onnx --model papluca/xlm-roberta-base-language-detection --task text-classification"
+    let model = client
+        .load_model(&get_path_from_env("IPNIS_LANGUAGE_IDENTIFICATION_MODEL")?)
+        .await?;
+
+    // create a tokenizer
+    let tokenizer = Tokenizer::from_file(get_env("IPNIS_LANGUAGE_IDENTIFICATION_TOKENIZER")?)
+        .map_err(|e| anyhow!(e))?;
+
+    // load the labels from the model's config.json
+    let labels = LabelSet::from_config(get_env("IPNIS_LANGUAGE_IDENTIFICATION_CONFIG")?)?;
+
+    // make a sample inputs
+    let inputs = TextInputs {
+        text: vec![
+            "아니 이게 될 리가 없잖아?".into(),
+            "Brevity is the soul of wit.".into(),
+        ],
+    };
+
+    // perform the inference
+    let outputs = client
+        .call_language_identification(&model, &tokenizer, &labels, &Default::default(), inputs)
+        .await?;
+
+    // show the result
+    for (batch, output) in outputs.answers.iter().enumerate() {
+        let batch = batch + 1;
+        for language in &output.languages {
+            let lang = &language.lang;
+            let score = language.score;
+            println!("Language for data {batch}th = {lang} ({score})");
+        }
+    }
+
+    // tag the texts with their languages, e.g. for the translation
+    for text in outputs.into_texts(&LanguageTag::new_en_us()) {
+        let lang = &text.lang;
+        let msg = &text.msg;
+        println!("Text in {lang} = {msg}");
+    }
+    Ok(())
+}
+
+fn get_env(key: &str) -> Result<String> {
+    env::var(key).map_err(|_| anyhow!("environment variable is not set: {key}"))
+}
+
+/// Parses a stored path, formatted as `{cid}:{length}`.
+fn get_path_from_env(key: &str) -> Result<Path> {
+    let path = get_env(key)?;
+    match path.split_once(':') {
+        Some((value, len)) => Ok(Path {
+            value: value.parse()?,
+            len: len.parse()?,
+        }),
+        None => bail!("malformed path: {path}"),
+    }
+}
@@ -0,0 +1,183 @@
+pub use ipnis_modules_text_classification::labels::LabelSet;
+
+use ipis::{
+    async_trait::async_trait,
+    core::{
+        anyhow::{anyhow, Result},
+        ordered_float::OrderedFloat,
+        value::text::{LanguageTag, Text},
+    },
+};
+use ipnis_common::{
+    model::Model,
+    nlp::{input::TextInputs, lang::find_language, tokenizer::Tokenizer},
+};
+use ipnis_modules_text_classification::IpnisTextClassification;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LanguageIdentificationConfig {
+    /// The number of the candidate languages of each text.
+    pub top_k: usize,
+    /// Drops the candidate languages below this score.
+    pub threshold: f32,
+}
+
+impl Default for LanguageIdentificationConfig {
+    fn default() -> Self {
+        Self {
+            top_k: 3,
+            threshold: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outputs {
+    pub answers: Vec<Output>,
+}
+
+impl Outputs {
+    /// Tags each text with its most probable language, or the fallback if unknown.
+    pub fn into_texts(self, fallback: &LanguageTag) -> Vec<Text> {
+        self.answers
+            .into_iter()
+            .map(|output| output.into_text(fallback))
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Output {
+    pub text: String,
+    /// The candidate languages, sorted by their scores in descending order.
+    pub languages: Vec<LanguageScore>,
+}
+
+impl Output {
+    /// Returns the most probable language, if any.
+    pub fn language(&self) -> Option<&LanguageTag> {
+        self.languages.first().map(|language| &language.lang)
+    }
+
+    pub fn into_text(self, fallback: &LanguageTag) -> Text {
+        Text {
+            lang: self.language().unwrap_or(fallback).clone(),
+            msg: self.text,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LanguageScore {
+    pub lang: LanguageTag,
+    pub score: OrderedFloat<f32>,
+}
+
+#[async_trait]
+pub trait IpnisLanguageIdentification: IpnisTextClassification {
+    /// Finds the languages of the texts, labeled with the language tags (e.g. `en` or `eng_Latn`).
+    async fn call_language_identification<T>(
+        &self,
+        model: &Model,
+        tokenizer: &T,
+        labels: &LabelSet,
+        config: &LanguageIdentificationConfig,
+        inputs: TextInputs,
+    ) -> Result<Outputs>
+    where
+        T: Tokenizer + ?Sized + Sync,
+    {
+        if inputs.text.is_empty() {
+            return Ok(Outputs {
+                answers: Default::default(),
+            });
+        }
+
+        // validate labels
+        for label in &labels.names {
+            parse_label(label)?;
+        }
+
+        let outputs = self
+            .call_text_classification(model, tokenizer, inputs, labels)
+            .await?;
+
+        Ok(Outputs {
+            answers: outputs
+                .answers
+                .into_iter()
+                .map(|output| {
+                    Ok(Output {
+                        text: output.context,
+                        languages: output
+                            .labels
+                            .into_iter()
+                            .filter(|label| label.score.0 >= config.threshold)
+                            .take(config.top_k)
+                            .map(|label| {
+                                Ok(LanguageScore {
+                                    lang: parse_label(&label.label)?,
+                                    score: label.score,
+                                })
+                            })
+                            .collect::<Result<_>>()?,
+                    })
+                })
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+impl<T: IpnisTextClassification + ?Sized> IpnisLanguageIdentification for T {}
+
+/// Converts the label into the language tag, e.g. `__label__eng_Latn` into `en`.
+///
+/// The languages are shortened into their ISO 639-1 codes if any,
+/// dropping their default scripts, e.g. `zho_Hant` into `zh-Hant`.
+fn parse_label(label: &str) -> Result<LanguageTag> {
+    let mut subtags = label.trim_start_matches("__label__").split(['_', '-']);
+    let language = subtags.next().unwrap_or_default();
+    let mut subtags: Vec<_> = subtags.collect();
+
+    let language = match find_language(&language.to_lowercase()) {
+        Some((iso_639_1, _, default_script)) => {
+            subtags.retain(|subtag| !subtag.eq_ignore_ascii_case(default_script));
+            iso_639_1
+        }
+        None => language,
+    };
+
+    ::core::iter::once(language)
+        .chain(subtags)
+        .collect::<Vec<_>>()
+        .join("-")
+        .parse()
+        .map_err(|e| anyhow!("malformed language label: {label:?}: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(label: &str) -> String {
+        parse_label(label).unwrap().to_string()
+    }
+
+    #[test]
+    fn labels_are_shortened_into_the_primary_subtags() {
+        assert_eq!(parse("__label__eng_Latn"), "en");
+        assert_eq!(parse("__label__kor_Hang"), "ko");
+        assert_eq!(parse("__label__pes_Arab"), "fa");
+        assert_eq!(parse("__label__en"), "en");
+        assert_eq!(parse("ko"), "ko");
+    }
+
+    #[test]
+    fn labels_keep_the_other_subtags() {
+        // not the default script
+        assert_eq!(parse("__label__zho_Hant"), "zh-Hant");
+        assert_eq!(parse("__label__zho_Hans"), "zh");
+        assert_eq!(parse("__label__uzn_Cyrl"), "uzn-Cyrl");
+        assert_eq!(parse("en-US"), "en-US");
+    }
+}
@@ -52,14 +52,20 @@ async fn main() -> Result<()> {
 
     // perform the inference
     let outputs = client
-        .call_question_answering(&model, &tokenizer, inputs)
+        .call_question_answering(&model, &tokenizer, &Default::default(), inputs)
         .await?;
 
     // show the result
     for (batch, output) in outputs.answers.into_iter().enumerate() {
         let batch = batch + 1;
-        let answer = &output.answer;
-        println!("Answer for data {batch}th = {answer}");
+        match &output.answer {
+            Some(answer) => {
+                let text = &answer.text;
+                let score = answer.score;
+                println!("Answer for data {batch}th = {text} ({score})");
+            }
+            None => println!("No answer for data {batch}th"),
+        }
     }
     Ok(())
 }
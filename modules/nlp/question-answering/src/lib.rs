@@ -3,19 +3,83 @@ use ipis::{
     core::{
         anyhow::{bail, Result},
         ndarray,
+        ordered_float::OrderedFloat,
     },
 };
 use ipnis_common::{
     model::Model,
     nlp::{
-        input::{QAInputs, Tokenized},
+        input::{GenericInput, QAInputs, TokenizeOptions, Tokenized, TruncationStrategy},
         tensor::StringTensorData,
+        tokenizer::{Encoding, Tokenizer},
     },
-    rust_tokenizers::{tokenizer::Tokenizer, vocab::Vocab},
+    onnxruntime::tensor::ndarray_tensor::NdArrayTensor,
     tensor::Tensor,
     Ipnis,
 };
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct QAConfig {
+    /// The number of the tokens shared by the adjacent windows of a long context.
+    pub doc_stride: usize,
+    /// Limits the number of the tokens of each window, including the query.
+    /// The fixed sequence length of the model is applied as well.
+    pub max_length: Option<usize>,
+    /// Limits the number of the tokens of each answer.
+    pub max_answer_len: usize,
+    /// The number of the candidate answers of each input.
+    pub top_k: usize,
+    /// Abstains from answering when the null score exceeds the best score by this margin.
+    /// The model never abstains if not given.
+    pub null_score_threshold: Option<f32>,
+}
+
+impl Default for QAConfig {
+    fn default() -> Self {
+        Self {
+            doc_stride: 128,
+            max_length: Some(384),
+            max_answer_len: 15,
+            top_k: 1,
+            null_score_threshold: None,
+        }
+    }
+}
+
+impl QAConfig {
+    /// Validates the config against the window size, which is limited by the model as well.
+    fn validate<T>(
+        &self,
+        tokenizer: &T,
+        max_length: Option<usize>,
+        queries: &[String],
+    ) -> Result<()>
+    where
+        T: Tokenizer + ?Sized,
+    {
+        let max_length = match max_length {
+            Some(max_length) => max_length,
+            None => return Ok(()),
+        };
+
+        let doc_stride = self.doc_stride;
+        if doc_stride >= max_length {
+            bail!("the doc stride should be less than the max length: {doc_stride} >= {max_length}")
+        }
+
+        // the windows should have more context tokens than the doc stride to proceed
+        let num_special_tokens = tokenizer.num_special_tokens(true)?;
+        for query in queries {
+            let len_query = tokenizer.encode(query)?.len() + num_special_tokens;
+            let len_context = max_length.saturating_sub(len_query);
+            if len_context <= doc_stride {
+                bail!("the query leaves too few context tokens in the max length: {len_context} <= {doc_stride} (doc stride)")
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Outputs {
     pub answers: Vec<Output>,
@@ -25,27 +89,54 @@ pub struct Outputs {
 pub struct Output {
     pub query: String,
     pub context: String,
-    pub answer: String,
+    /// The best answer, which is missing if the context has no answer.
+    pub answer: Option<Answer>,
+    /// The best candidate answers, sorted by their scores.
+    pub candidates: Vec<Answer>,
+    /// The probability of having no answer, which is the lowest one among the windows.
+    pub null_score: Option<OrderedFloat<f32>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Answer {
+    pub text: String,
+    /// The probability of the span, given the window.
+    pub score: OrderedFloat<f32>,
+    /// The character offset of the beginning in the context.
+    pub start: usize,
+    /// The character offset of the end in the context, exclusively.
+    pub end: usize,
 }
 
 #[async_trait]
 pub trait IpnisQuestionAnswering: Ipnis {
-    async fn call_question_answering<T, V>(
+    async fn call_question_answering<T>(
         &self,
         model: &Model,
         tokenizer: &T,
+        config: &QAConfig,
         inputs: QAInputs,
     ) -> Result<Outputs>
     where
-        T: Tokenizer<V> + Sync,
-        V: Vocab,
+        T: Tokenizer + ?Sized + Sync,
     {
+        // split the long contexts into the windows, keeping the queries
+        let options = TokenizeOptions {
+            max_length: config.max_length,
+            truncation: TruncationStrategy::OnlyFirst,
+            stride: Some(config.doc_stride),
+            ..Default::default()
+        }
+        .with_model(model);
+        config.validate(tokenizer, options.max_length, &inputs.query)?;
+
         let Tokenized {
-            input_ids,
             inputs,
             inputs_str,
+            encodings,
+            sample_indices,
             ..
-        } = inputs.tokenize(tokenizer)?;
+        } = inputs.tokenize_with(tokenizer, &options)?;
 
         let mut outputs = self.call(model, &inputs).await?;
         if outputs.len() < 2 {
@@ -60,22 +151,16 @@ pub trait IpnisQuestionAnswering: Ipnis {
 
         match (&start_logits.data, &end_logits.data) {
             (StringTensorData::F32(start_logits), StringTensorData::F32(end_logits)) => {
-                let answers = find_answer(&input_ids, start_logits, end_logits);
                 Ok(Outputs {
-                    answers: inputs_str
-                        .into_iter()
-                        .zip(answers.into_iter())
-                        .map(|(input, answer)| {
-                            Ok(Output {
-                                query: input.text_1,
-                                context: input.text_2.unwrap(),
-                                answer: tokenizer
-                                    .decode(answer.as_slice().unwrap(), true, true)
-                                    .trim()
-                                    .to_string(),
-                            })
-                        })
-                        .collect::<Result<_>>()?,
+                    answers: merge_windows(
+                        config,
+                        tokenizer,
+                        inputs_str,
+                        &encodings,
+                        &sample_indices,
+                        start_logits.0.view(),
+                        end_logits.0.view(),
+                    ),
                 })
             }
             _ => {
@@ -89,48 +174,384 @@ pub trait IpnisQuestionAnswering: Ipnis {
 
 impl<T: Ipnis + ?Sized> IpnisQuestionAnswering for T {}
 
-fn argmax<S>(mat: &ndarray::ArrayBase<S, ndarray::Ix2>) -> ndarray::Array1<usize>
+/// Merges the candidate spans of the windows into the answers of each input.
+fn merge_windows<T>(
+    config: &QAConfig,
+    tokenizer: &T,
+    inputs_str: Vec<GenericInput>,
+    encodings: &[Encoding],
+    sample_indices: &[usize],
+    start_logits: ndarray::ArrayView2<f32>,
+    end_logits: ndarray::ArrayView2<f32>,
+) -> Vec<Output>
 where
-    S: ndarray::Data,
-    S::Elem: PartialOrd,
+    T: Tokenizer + ?Sized,
 {
-    mat.rows()
+    // the null answers are pointed to the CLS token
+    let token_cls = tokenizer.special_tokens().cls;
+
+    // merge the candidates of the windows
+    let mut spans: Vec<Vec<Span>> = vec![vec![]; inputs_str.len()];
+    let mut null_scores: Vec<Option<OrderedFloat<f32>>> = vec![None; inputs_str.len()];
+    for (((encoding, &index), start_logits), end_logits) in encodings
+        .iter()
+        .zip(sample_indices)
+        .zip(start_logits.rows())
+        .zip(end_logits.rows())
+    {
+        let (window_spans, null_score) =
+            find_spans(config, encoding, token_cls, start_logits, end_logits);
+        spans[index].extend(window_spans);
+        null_scores[index] = match (null_scores[index], null_score) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+    }
+
+    inputs_str
         .into_iter()
-        .map(|row| {
-            row.into_iter()
-                .enumerate()
-                .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
-                .unwrap()
-                .0
+        .zip(spans)
+        .zip(null_scores)
+        .map(|((input, mut spans), null_score)| {
+            // the overlapping windows may find the same span
+            spans.sort_by_key(|span| ::core::cmp::Reverse(span.score));
+            let mut candidates: Vec<Answer> = vec![];
+            for span in spans {
+                if candidates.len() == config.top_k.max(1) {
+                    break;
+                }
+                if candidates
+                    .iter()
+                    .any(|answer| answer.start == span.start && answer.end == span.end)
+                {
+                    continue;
+                }
+
+                candidates.push(Answer {
+                    text: input
+                        .text_1
+                        .chars()
+                        .skip(span.start)
+                        .take(span.end - span.start)
+                        .collect(),
+                    score: span.score,
+                    start: span.start,
+                    end: span.end,
+                });
+            }
+
+            // compare the best answer with the null answer
+            let answer = match (candidates.first(), null_score, config.null_score_threshold) {
+                (Some(answer), Some(null_score), Some(threshold))
+                    if null_score.0 - answer.score.0 > threshold =>
+                {
+                    None
+                }
+                (answer, _, _) => answer.cloned(),
+            };
+            candidates.truncate(config.top_k);
+
+            Output {
+                query: input.text_2.unwrap_or_default(),
+                context: input.text_1,
+                answer,
+                candidates,
+                null_score,
+            }
         })
         .collect()
 }
 
-fn find_answer<SM, SL>(
-    mat: &ndarray::ArrayBase<SM, ndarray::Ix2>,
-    start_logits: &ndarray::ArrayBase<SL, ndarray::Ix2>,
-    end_logits: &ndarray::ArrayBase<SL, ndarray::Ix2>,
-) -> Vec<ndarray::Array1<SM::Elem>>
-where
-    SM: ndarray::Data,
-    SM::Elem: Copy,
-    SL: ndarray::Data,
-    SL::Elem: Copy + PartialOrd,
-    i64: TryFrom<<SM as ndarray::RawData>::Elem>,
-    <i64 as TryFrom<<SM as ndarray::RawData>::Elem>>::Error: ::core::fmt::Debug,
-{
-    let start_logits = argmax(start_logits);
-    let end_logits = argmax(end_logits);
-    mat.rows()
+/// A candidate answer, located with the character offsets in the context.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Span {
+    start: usize,
+    end: usize,
+    score: OrderedFloat<f32>,
+}
+
+/// Finds the valid spans in the context tokens of the window, and the null score if available.
+fn find_spans(
+    config: &QAConfig,
+    encoding: &Encoding,
+    token_cls: Option<i64>,
+    start_logits: ndarray::ArrayView1<f32>,
+    end_logits: ndarray::ArrayView1<f32>,
+) -> (Vec<Span>, Option<OrderedFloat<f32>>) {
+    let cls = encoding
+        .ids
+        .iter()
+        .zip(&encoding.special_tokens_mask)
+        .position(|(&id, &special)| special && Some(id) == token_cls);
+
+    // the contexts are the first texts of the inputs
+    let context: Vec<_> = encoding
+        .offsets
+        .iter()
+        .zip(&encoding.sequence_ids)
+        .enumerate()
+        .filter_map(|(index, (offset, sequence_id))| match sequence_id {
+            Some(0) => offset.map(|offset| (index, offset)),
+            _ => None,
+        })
+        .collect();
+
+    // normalize the logits over the context tokens and the CLS token
+    let indices: Vec<_> = cls
         .into_iter()
-        .zip(start_logits)
-        .zip(end_logits)
-        .map(|((row, start), end)| {
-            row.into_iter()
-                .skip(start)
-                .take(end - start + 1)
-                .copied()
+        .chain(context.iter().map(|&(index, _)| index))
+        .collect();
+    let start_probs = start_logits
+        .select(ndarray::Axis(0), &indices)
+        .softmax(ndarray::Axis(0));
+    let end_probs = end_logits
+        .select(ndarray::Axis(0), &indices)
+        .softmax(ndarray::Axis(0));
+    let (null_score, start_probs, end_probs) = match cls {
+        Some(_) => (
+            Some(OrderedFloat(start_probs[0] * end_probs[0])),
+            start_probs.slice(ndarray::s![1..]),
+            end_probs.slice(ndarray::s![1..]),
+        ),
+        None => (None, start_probs.view(), end_probs.view()),
+    };
+
+    let mut spans = vec![];
+    for (i, &(start, (start_offset, _))) in context.iter().enumerate() {
+        for (j, &(end, (_, end_offset))) in context.iter().enumerate().skip(i) {
+            if end - start >= config.max_answer_len {
+                break;
+            }
+            spans.push(Span {
+                start: start_offset,
+                end: end_offset,
+                score: OrderedFloat(start_probs[i] * end_probs[j]),
+            });
+        }
+    }
+    (spans, null_score)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use ipnis_common::rust_tokenizers::{
+        tokenizer::BertTokenizer,
+        vocab::{BertVocab, Vocab},
+    };
+
+    use super::*;
+
+    const CONTEXT: &str = "hello world the quick brown fox jumps";
+
+    fn tokenizer() -> BertTokenizer {
+        let tokens = [
+            "[UNK]", "[CLS]", "[SEP]", "[MASK]", "[PAD]", "the", "quick", "brown", "fox", "jumps",
+            "hello", "world",
+        ];
+
+        let values: HashMap<_, _> = tokens
+            .iter()
+            .enumerate()
+            .map(|(id, token)| (token.to_string(), id as i64))
+            .collect();
+        let special_values: HashMap<_, _> = tokens[..5]
+            .iter()
+            .map(|token| (token.to_string(), values[*token]))
+            .collect();
+
+        let swap = |values: &HashMap<String, i64>| {
+            values
+                .iter()
+                .map(|(token, id)| (*id, token.clone()))
                 .collect()
-        })
-        .collect()
+        };
+        let vocab = BertVocab {
+            indices: swap(&values),
+            special_indices: swap(&special_values),
+            values,
+            special_values,
+            unknown_value: BertVocab::unknown_value(),
+        };
+        BertTokenizer::from_existing_vocab(vocab, true, true)
+    }
+
+    /// Splits the context into 3 windows of 4 context tokens, sharing 2 tokens.
+    fn config() -> QAConfig {
+        QAConfig {
+            doc_stride: 2,
+            max_length: Some(8),
+            top_k: 3,
+            ..Default::default()
+        }
+    }
+
+    /// Answers with the logits of each token, which are given as `(start, end)`.
+    fn answer(config: &QAConfig, logits: impl Fn(Option<usize>, &str) -> (f32, f32)) -> Output {
+        let tokenizer = tokenizer();
+        let inputs = QAInputs {
+            query: vec!["hello".into()],
+            context: vec![CONTEXT.into()],
+        };
+        let options = TokenizeOptions {
+            max_length: config.max_length,
+            truncation: TruncationStrategy::OnlyFirst,
+            stride: Some(config.doc_stride),
+            ..Default::default()
+        };
+        config
+            .validate(&tokenizer, options.max_length, &inputs.query)
+            .unwrap();
+
+        let Tokenized {
+            inputs_str,
+            encodings,
+            sample_indices,
+            ..
+        } = inputs.tokenize_with(&tokenizer, &options).unwrap();
+        assert_eq!(sample_indices, [0, 0, 0]);
+
+        let len = encodings.iter().map(Encoding::len).max().unwrap();
+        let mut start_logits = ndarray::Array2::zeros((encodings.len(), len));
+        let mut end_logits = ndarray::Array2::zeros((encodings.len(), len));
+        for (row, encoding) in encodings.iter().enumerate() {
+            for (index, (&id, &sequence_id)) in
+                encoding.ids.iter().zip(&encoding.sequence_ids).enumerate()
+            {
+                let token = tokenizer.id_to_token(id).unwrap();
+                let (start, end) = logits(sequence_id, &token);
+                start_logits[(row, index)] = start;
+                end_logits[(row, index)] = end;
+            }
+        }
+
+        let mut outputs = merge_windows(
+            config,
+            &tokenizer,
+            inputs_str,
+            &encodings,
+            &sample_indices,
+            start_logits.view(),
+            end_logits.view(),
+        );
+        assert_eq!(outputs.len(), 1);
+        outputs.remove(0)
+    }
+
+    /// Points to "brown fox", while the query tokens are rated even higher.
+    fn brown_fox(sequence_id: Option<usize>, token: &str) -> (f32, f32) {
+        match (sequence_id, token) {
+            (Some(1), _) => (10.0, 10.0),
+            (_, "brown") => (4.0, 0.0),
+            (_, "fox") => (0.0, 4.0),
+            (_, "quick") => (2.0, 2.0),
+            _ => (0.0, 0.0),
+        }
+    }
+
+    #[test]
+    fn best_span_is_merged_across_windows() {
+        let output = answer(&config(), brown_fox);
+
+        let answer = output.answer.unwrap();
+        assert_eq!(answer.text, "brown fox");
+        assert_eq!((answer.start, answer.end), (22, 31));
+        assert_eq!(output.candidates[0], answer);
+
+        // the windows sharing the span are merged
+        assert_eq!(output.candidates.len(), 3);
+        for (i, a) in output.candidates.iter().enumerate() {
+            for b in &output.candidates[i + 1..] {
+                assert!(a.score >= b.score);
+                assert_ne!((a.start, a.end), (b.start, b.end));
+            }
+        }
+    }
+
+    #[test]
+    fn offsets_point_into_the_context() {
+        let config = QAConfig {
+            top_k: 10,
+            ..config()
+        };
+        let output = answer(&config, brown_fox);
+
+        assert_eq!(output.context, CONTEXT);
+        assert_eq!(output.query, "hello");
+        for candidate in output.candidates {
+            let text: String = CONTEXT
+                .chars()
+                .skip(candidate.start)
+                .take(candidate.end - candidate.start)
+                .collect();
+            assert_eq!(candidate.text, text);
+        }
+    }
+
+    #[test]
+    fn long_spans_are_skipped() {
+        let config = QAConfig {
+            max_answer_len: 1,
+            top_k: 5,
+            ..config()
+        };
+        let output = answer(&config, brown_fox);
+
+        assert_eq!(output.candidates.len(), 5);
+        for candidate in &output.candidates {
+            assert!(!candidate.text.contains(' '), "{}", candidate.text);
+        }
+    }
+
+    #[test]
+    fn top_k_limits_the_candidates() {
+        for top_k in [0, 1, 2] {
+            let config = QAConfig { top_k, ..config() };
+            let output = answer(&config, brown_fox);
+
+            assert_eq!(output.candidates.len(), top_k);
+            assert_eq!(output.answer.unwrap().text, "brown fox");
+        }
+    }
+
+    #[test]
+    fn null_answers_abstain_with_the_threshold() {
+        let cls = |sequence_id, token: &str| match token {
+            "[CLS]" => (10.0, 10.0),
+            _ => brown_fox(sequence_id, token),
+        };
+
+        let output = answer(&config(), cls);
+        assert!(output.null_score.unwrap() > output.candidates[0].score);
+        assert_eq!(output.answer.unwrap().text, "brown fox");
+
+        let config = QAConfig {
+            null_score_threshold: Some(0.0),
+            ..config()
+        };
+        let output = answer(&config, cls);
+        assert_eq!(output.answer, None);
+        assert_eq!(output.candidates[0].text, "brown fox");
+    }
+
+    #[test]
+    fn queries_should_leave_the_context_tokens() {
+        let tokenizer = tokenizer();
+        let validate = |doc_stride, max_length, query: &str| {
+            QAConfig {
+                doc_stride,
+                ..Default::default()
+            }
+            .validate(&tokenizer, max_length, &[query.into()])
+        };
+
+        assert!(validate(2, Some(8), "hello").is_ok());
+        assert!(validate(2, None, "the quick brown fox jumps").is_ok());
+        assert!(validate(8, Some(8), "hello").is_err());
+        // 4 tokens with the special tokens, leaving only the doc stride
+        assert!(validate(2, Some(6), "hello").is_err());
+        // no tokens are left for the context
+        assert!(validate(2, Some(8), "the quick brown fox jumps").is_err());
+    }
 }
@@ -0,0 +1,70 @@
+use std::env;
+
+use ipis::{
+    core::anyhow::{anyhow, bail, Result},
+    env::Infer,
+    path::Path,
+    tokio,
+};
+use ipnis_api::{
+    client::IpnisClientInner,
+    common::{nlp::input::RerankInputs, tokenizers::Tokenizer, Ipnis},
+};
+use ipnis_modules_reranking::IpnisReranking;
+use ipsis_api::client::IpsisClient;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // create a client
+    let client = IpnisClientInner::<IpsisClient>::try_infer().await?;
+
+    // load a model (e.g. cross-encoder/ms-marco-MiniLM-L-6-v2)
+    // NOTE: you can export it manually with: "optimum-cli export onnx --model cross-encoder/ms-marco-MiniLM-L-6-v2 --task text-classification"
+    let model = client
+        .load_model(&get_path_from_env("IPNIS_RERANKING_MODEL")?)
+        .await?;
+
+    // create a tokenizer
+    let tokenizer =
+        Tokenizer::from_file(get_env("IPNIS_RERANKING_TOKENIZER")?).map_err(|e| anyhow!(e))?;
+
+    // make a sample inputs
+    let inputs = RerankInputs {
+        query: "How many people live in Berlin?".into(),
+        passages: vec![
+            "New York City is famous for the Metropolitan Museum of Art.".into(),
+            "Berlin has a population of 3,520,031 registered inhabitants in an area of 891.82 square kilometers.".into(),
+            "Berlin is well known for its museums.".into(),
+        ],
+    };
+
+    // perform the inference
+    let outputs = client
+        .call_reranking(&model, &tokenizer, &Default::default(), inputs)
+        .await?;
+
+    // show the result
+    for (rank, output) in outputs.answers.into_iter().enumerate() {
+        let rank = rank + 1;
+        let passage = &output.passage;
+        let score = output.score;
+        println!("Passage of rank {rank} = {passage} ({score})");
+    }
+    Ok(())
+}
+
+fn get_env(key: &str) -> Result<String> {
+    env::var(key).map_err(|_| anyhow!("environment variable is not set: {key}"))
+}
+
+/// Parses a stored path, formatted as `{cid}:{length}`.
+fn get_path_from_env(key: &str) -> Result<Path> {
+    let path = get_env(key)?;
+    match path.split_once(':') {
+        Some((value, len)) => Ok(Path {
+            value: value.parse()?,
+            len: len.parse()?,
+        }),
+        None => bail!("malformed path: {path}"),
+    }
+}
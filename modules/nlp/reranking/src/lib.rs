@@ -0,0 +1,293 @@
+use ipis::{
+    async_trait::async_trait,
+    core::{
+        anyhow::{bail, Result},
+        ndarray,
+        ordered_float::OrderedFloat,
+    },
+};
+use ipnis_common::{
+    model::Model,
+    nlp::{
+        input::{GenericInput, RerankInputs, TokenizeOptions, Tokenized, TruncationStrategy},
+        tensor::StringTensorData,
+        tokenizer::Tokenizer,
+    },
+    onnxruntime::tensor::ndarray_tensor::NdArrayTensor,
+    tensor::Tensor,
+    Ipnis,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RerankConfig {
+    /// The maximum number of the tokens of a batch, including the padding.
+    pub max_batch_tokens: usize,
+    /// Returns only the most relevant passages, if given.
+    pub top_k: Option<usize>,
+}
+
+impl Default for RerankConfig {
+    fn default() -> Self {
+        Self {
+            max_batch_tokens: 8192,
+            top_k: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outputs {
+    /// The passages, sorted by their scores in descending order.
+    pub answers: Vec<Output>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Output {
+    /// The index of the passage in the inputs.
+    pub index: usize,
+    pub passage: String,
+    pub score: OrderedFloat<f32>,
+}
+
+#[async_trait]
+pub trait IpnisReranking: Ipnis {
+    /// Scores the relevance of each passage to the query with a cross-encoder.
+    async fn call_reranking<T>(
+        &self,
+        model: &Model,
+        tokenizer: &T,
+        config: &RerankConfig,
+        inputs: RerankInputs,
+    ) -> Result<Outputs>
+    where
+        T: Tokenizer + ?Sized + Sync,
+    {
+        // keep the query, truncating the passages only
+        let options = TokenizeOptions {
+            truncation: TruncationStrategy::OnlySecond,
+            ..Default::default()
+        }
+        .with_model(model);
+
+        let inputs: Vec<GenericInput> = inputs.into();
+        let mut answers = Vec::with_capacity(inputs.len());
+        for batch in split_batches(tokenizer, &inputs, &options, config.max_batch_tokens)? {
+            let Tokenized {
+                inputs: tensors,
+                inputs_str,
+                ..
+            } = GenericInput::tokenize_batch_with(
+                batch.iter().map(|&index| inputs[index].clone()).collect(),
+                tokenizer,
+                &options,
+            )?;
+
+            let mut outputs = self.call(model, &tensors).await?;
+            if outputs.is_empty() {
+                let outputs = outputs.len();
+                bail!("unexpected outputs: Expected 1, Given {outputs}");
+            }
+
+            let logits: Tensor<StringTensorData> =
+                Tensor::find(&mut outputs, "logits")?.try_into()?;
+
+            match &logits.data {
+                StringTensorData::F32(logits) => {
+                    answers.extend(collect_scores(batch, inputs_str, logits.0.view()));
+                }
+                _ => {
+                    let logits = logits.shape();
+                    bail!("unexpected StringTensorData: {logits:?}")
+                }
+            }
+        }
+
+        Ok(Outputs {
+            answers: rank(answers, config.top_k),
+        })
+    }
+}
+
+impl<T: Ipnis + ?Sized> IpnisReranking for T {}
+
+/// Groups the inputs of the similar lengths, so that each batch fits in the token budget.
+fn split_batches<T>(
+    tokenizer: &T,
+    inputs: &[GenericInput],
+    options: &TokenizeOptions,
+    max_batch_tokens: usize,
+) -> Result<Vec<Vec<usize>>>
+where
+    T: Tokenizer + ?Sized,
+{
+    let Tokenized { encodings, .. } =
+        GenericInput::tokenize_batch_without_tensors_with(inputs.to_vec(), tokenizer, options)?;
+
+    // the longest inputs first, so that the first one of each batch is the longest
+    let mut indices: Vec<_> = (0..encodings.len()).collect();
+    indices.sort_by_key(|&index| ::core::cmp::Reverse(encodings[index].len()));
+
+    let mut batches: Vec<Vec<usize>> = vec![];
+    for index in indices {
+        match batches.last_mut() {
+            Some(batch) if (batch.len() + 1) * encodings[batch[0]].len() <= max_batch_tokens => {
+                batch.push(index)
+            }
+            _ => batches.push(vec![index]),
+        }
+    }
+    Ok(batches)
+}
+
+/// Converts the logits of the batch into the relevance scores.
+fn collect_scores(
+    batch: Vec<usize>,
+    inputs_str: Vec<GenericInput>,
+    logits: ndarray::ArrayView2<f32>,
+) -> Vec<Output> {
+    let scores = match logits.ncols() {
+        // a single logit of the relevance
+        1 => logits.mapv(|logit| 1.0 / (1.0 + (-logit).exp())),
+        // the probability of the last label, e.g. `relevant`
+        _ => logits.softmax(ndarray::Axis(1)),
+    };
+
+    batch
+        .into_iter()
+        .zip(inputs_str)
+        .zip(scores.rows())
+        .map(|((index, input), scores)| Output {
+            index,
+            passage: input.text_2.unwrap_or_default(),
+            score: OrderedFloat(scores.last().copied().unwrap_or_default()),
+        })
+        .collect()
+}
+
+/// Sorts the passages by their scores, keeping the most relevant ones only.
+fn rank(mut answers: Vec<Output>, top_k: Option<usize>) -> Vec<Output> {
+    answers.sort_by_key(|answer| ::core::cmp::Reverse(answer.score));
+    if let Some(top_k) = top_k {
+        answers.truncate(top_k);
+    }
+    answers
+}
+
+#[cfg(test)]
+mod tests {
+    use ipnis_common::nlp::tokenizer::Encoding;
+
+    use super::*;
+
+    /// Splits the texts by the whitespaces, joining them as `[CLS] first [SEP] second [SEP]`.
+    struct WhitespaceTokenizer;
+
+    impl Tokenizer for WhitespaceTokenizer {
+        fn encode(&self, text: &str) -> Result<Encoding> {
+            let len = text.split_whitespace().count();
+            Ok(Encoding {
+                ids: vec![1; len],
+                type_ids: vec![0; len],
+                special_tokens_mask: vec![false; len],
+                offsets: vec![None; len],
+                sequence_ids: vec![Some(0); len],
+            })
+        }
+
+        fn build_inputs(&self, first: Encoding, second: Option<Encoding>) -> Result<Encoding> {
+            let len = 2 + first.len() + second.map(|second| second.len() + 1).unwrap_or(0);
+            Ok(Encoding {
+                ids: vec![1; len],
+                type_ids: vec![0; len],
+                special_tokens_mask: vec![false; len],
+                offsets: vec![None; len],
+                sequence_ids: vec![None; len],
+            })
+        }
+
+        fn decode(&self, _: &[i64], _: bool) -> Result<String> {
+            Ok(Default::default())
+        }
+
+        fn token_to_id(&self, _: &str) -> Option<i64> {
+            None
+        }
+
+        fn id_to_token(&self, _: i64) -> Option<String> {
+            None
+        }
+    }
+
+    /// Creates the inputs of 5, 9, 6, 9 and 7 tokens, including the query and the special tokens.
+    fn inputs() -> Vec<GenericInput> {
+        RerankInputs {
+            query: "query".into(),
+            passages: vec![
+                "a".into(),
+                "a b c d e".into(),
+                "a b".into(),
+                "a b c d e".into(),
+                "a b c".into(),
+            ],
+        }
+        .into()
+    }
+
+    fn split(options: &TokenizeOptions, max_batch_tokens: usize) -> Vec<Vec<usize>> {
+        split_batches(&WhitespaceTokenizer, &inputs(), options, max_batch_tokens).unwrap()
+    }
+
+    #[test]
+    fn batches_fit_in_the_token_budget() {
+        let options = TokenizeOptions::default();
+
+        // the padded batches, which are 2 * 9 and 2 * 7 tokens
+        assert_eq!(split(&options, 18), [vec![1, 3], vec![4, 2], vec![0]]);
+        // the longer inputs than the budget are left alone
+        assert_eq!(
+            split(&options, 4),
+            [vec![1], vec![3], vec![4], vec![2], vec![0]],
+        );
+        assert_eq!(split(&options, 1 << 20), [vec![1, 3, 4, 2, 0]]);
+    }
+
+    #[test]
+    fn batches_are_measured_after_the_truncation() {
+        let options = TokenizeOptions {
+            max_length: Some(6),
+            truncation: TruncationStrategy::OnlySecond,
+            ..Default::default()
+        };
+
+        assert_eq!(split(&options, 18), [vec![1, 2, 3], vec![4, 0]]);
+    }
+
+    #[test]
+    fn passages_are_ranked_by_relevance() {
+        let inputs = inputs();
+        let batch = vec![2, 0, 1];
+        let batch_str = batch.iter().map(|&index| inputs[index].clone()).collect();
+
+        // a single logit of the relevance
+        let logits = ndarray::arr2(&[[0.0], [2.0], [-1.0]]);
+        let answers = rank(
+            collect_scores(batch.clone(), batch_str, logits.view()),
+            Some(2),
+        );
+
+        let indices: Vec<_> = answers.iter().map(|answer| answer.index).collect();
+        assert_eq!(indices, [0, 2]);
+        assert_eq!(answers[0].passage, "a");
+        assert_eq!(answers[1].score, OrderedFloat(0.5));
+
+        // the probability of the last label
+        let batch_str = batch.iter().map(|&index| inputs[index].clone()).collect();
+        let logits = ndarray::arr2(&[[1.0, 0.0], [0.0, 0.0], [0.0, 1.0]]);
+        let answers = rank(collect_scores(batch, batch_str, logits.view()), None);
+
+        let indices: Vec<_> = answers.iter().map(|answer| answer.index).collect();
+        assert_eq!(indices, [1, 0, 2]);
+        assert_eq!(answers[1].score, OrderedFloat(0.5));
+        assert!((answers[0].score.0 + answers[2].score.0 - 1.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,78 @@
+use std::env;
+
+use ipis::{
+    core::anyhow::{anyhow, bail, Result},
+    env::Infer,
+    path::Path,
+    tokio,
+};
+use ipnis_api::{
+    client::IpnisClientInner,
+    common::{nlp::input::TextInputs, tokenizers::Tokenizer, Ipnis},
+};
+use ipnis_modules_sentence_embedding::{index::EmbeddingIndex, IpnisSentenceEmbedding};
+use ipsis_api::client::IpsisClient;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // create a client
+    let client = IpnisClientInner::<IpsisClient>::try_infer().await?;
+
+    // load a model (e.g. sentence-transformers/all-MiniLM-L6-v2)
+    // NOTE: you can export it manually with: "optimum-cli export onnx --model sentence-transformers/all-MiniLM-L6-v2 --task feature-extraction"
+    let model = client
+        .load_model(&get_path_from_env("IPNIS_SENTENCE_EMBEDDING_MODEL")?)
+        .await?;
+
+    // create a tokenizer
+    let tokenizer = Tokenizer::from_file(get_env("IPNIS_SENTENCE_EMBEDDING_TOKENIZER")?)
+        .map_err(|e| anyhow!(e))?;
+
+    // make a sample inputs
+    let inputs = TextInputs {
+        text: vec![
+            "A man is eating food.".into(),
+            "A man is riding a horse.".into(),
+            "A monkey is playing drums.".into(),
+            "What is the man eating?".into(),
+        ],
+    };
+
+    // perform the inference
+    let outputs = client
+        .call_sentence_embedding(&model, &tokenizer, &Default::default(), inputs)
+        .await?;
+
+    // search the most similar texts of the last one
+    let mut answers = outputs.answers;
+    let query = answers.pop().unwrap();
+    let mut index = EmbeddingIndex::default();
+    for output in answers {
+        index.insert(output.text, output.embedding)?;
+    }
+
+    // show the result
+    println!("Query = {}", &query.text);
+    for result in index.search(&query.embedding, 2)? {
+        let text = result.key;
+        let score = result.score;
+        println!("Similar text = {text} ({score})");
+    }
+    Ok(())
+}
+
+fn get_env(key: &str) -> Result<String> {
+    env::var(key).map_err(|_| anyhow!("environment variable is not set: {key}"))
+}
+
+/// Parses a stored path, formatted as `{cid}:{length}`.
+fn get_path_from_env(key: &str) -> Result<Path> {
+    let path = get_env(key)?;
+    match path.split_once(':') {
+        Some((value, len)) => Ok(Path {
+            value: value.parse()?,
+            len: len.parse()?,
+        }),
+        None => bail!("malformed path: {path}"),
+    }
+}
@@ -0,0 +1,107 @@
+use ipis::core::{
+    anyhow::{bail, Result},
+    ordered_float::OrderedFloat,
+};
+
+use crate::cosine_similarity;
+
+/// Stores the embeddings in memory, searching them by the cosine similarity.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EmbeddingIndex<K> {
+    /// The dimension of the embeddings, given by the first one.
+    dimension: Option<usize>,
+    entries: Vec<(K, Vec<f32>)>,
+}
+
+impl<K> Default for EmbeddingIndex<K> {
+    fn default() -> Self {
+        Self {
+            dimension: None,
+            entries: Default::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SearchResult<'a, K> {
+    pub key: &'a K,
+    pub score: OrderedFloat<f32>,
+}
+
+impl<K> EmbeddingIndex<K> {
+    pub fn insert(&mut self, key: K, embedding: Vec<f32>) -> Result<()> {
+        self.check_dimension(&embedding)?;
+        self.dimension = Some(embedding.len());
+        self.entries.push((key, embedding));
+        Ok(())
+    }
+
+    pub fn dimension(&self) -> Option<usize> {
+        self.dimension
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the `top_k` most similar embeddings, sorted by their scores in descending order.
+    pub fn search(&self, query: &[f32], top_k: usize) -> Result<Vec<SearchResult<'_, K>>> {
+        self.check_dimension(query)?;
+
+        let mut results = self
+            .entries
+            .iter()
+            .map(|(key, embedding)| {
+                Ok(SearchResult {
+                    key,
+                    score: OrderedFloat(cosine_similarity(query, embedding)?),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        results.sort_by_key(|result| ::core::cmp::Reverse(result.score));
+        results.truncate(top_k);
+        Ok(results)
+    }
+
+    fn check_dimension(&self, embedding: &[f32]) -> Result<()> {
+        match self.dimension {
+            Some(dimension) if dimension != embedding.len() => {
+                let given = embedding.len();
+                bail!("unexpected embedding dimension: Expected {dimension}, Given {given}")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_sorts_by_the_similarity() {
+        let mut index = EmbeddingIndex::default();
+        index.insert("x", vec![1.0, 0.0]).unwrap();
+        index.insert("y", vec![0.0, 1.0]).unwrap();
+        index.insert("xy", vec![1.0, 1.0]).unwrap();
+
+        let results = index.search(&[1.0, 0.1], 2).unwrap();
+        let keys: Vec<_> = results.iter().map(|result| *result.key).collect();
+        assert_eq!(keys, ["x", "xy"]);
+    }
+
+    #[test]
+    fn mismatched_dimensions_are_rejected() {
+        let mut index = EmbeddingIndex::default();
+        index.insert("x", vec![1.0, 0.0]).unwrap();
+        assert_eq!(index.dimension(), Some(2));
+
+        assert!(index.insert("z", vec![1.0, 0.0, 0.0]).is_err());
+        assert!(index.search(&[1.0], 1).is_err());
+        assert_eq!(index.len(), 1);
+    }
+}
@@ -0,0 +1,244 @@
+pub mod index;
+
+use ipis::{
+    async_trait::async_trait,
+    core::{
+        anyhow::{bail, Result},
+        ndarray,
+    },
+};
+use ipnis_common::{
+    model::Model,
+    nlp::{
+        input::{TextInputs, TokenizeOptions, Tokenized},
+        tensor::StringTensorData,
+        tokenizer::Tokenizer,
+    },
+    tensor::Tensor,
+    Ipnis,
+};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Pooling {
+    /// Averages the token embeddings, skipping the padding.
+    #[default]
+    Mean,
+    /// Takes the embedding of the first token.
+    Cls,
+    /// Takes the maximum of each dimension, skipping the padding.
+    Max,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SentenceEmbeddingConfig {
+    pub pooling: Pooling,
+    /// Scales the embeddings to the unit length, so that the dot product is the cosine similarity.
+    pub normalize: bool,
+}
+
+impl Default for SentenceEmbeddingConfig {
+    fn default() -> Self {
+        Self {
+            pooling: Default::default(),
+            normalize: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Outputs {
+    pub answers: Vec<Output>,
+}
+
+impl Outputs {
+    pub fn into_embeddings(self) -> Vec<Vec<f32>> {
+        self.answers
+            .into_iter()
+            .map(|output| output.embedding)
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Output {
+    pub text: String,
+    pub embedding: Vec<f32>,
+}
+
+#[async_trait]
+pub trait IpnisSentenceEmbedding: Ipnis {
+    /// Embeds the texts into the fixed-size vectors, pooling the token embeddings.
+    async fn call_sentence_embedding<T>(
+        &self,
+        model: &Model,
+        tokenizer: &T,
+        config: &SentenceEmbeddingConfig,
+        inputs: TextInputs,
+    ) -> Result<Outputs>
+    where
+        T: Tokenizer + ?Sized + Sync,
+    {
+        if inputs.text.is_empty() {
+            return Ok(Outputs {
+                answers: Default::default(),
+            });
+        }
+
+        let Tokenized {
+            inputs,
+            inputs_str,
+            attention_mask,
+            ..
+        } = inputs.tokenize_with(tokenizer, &TokenizeOptions::default().with_model(model))?;
+
+        let mut outputs = self.call(model, &inputs).await?;
+        if outputs.is_empty() {
+            let outputs = outputs.len();
+            bail!("unexpected outputs: Expected 1, Given {outputs}");
+        }
+
+        let last_hidden_state: Tensor<StringTensorData> =
+            Tensor::find(&mut outputs, "last_hidden_state")?.try_into()?;
+
+        match &last_hidden_state.data {
+            StringTensorData::F32Embedding(last_hidden_state) => Ok(Outputs {
+                answers: inputs_str
+                    .into_iter()
+                    .zip(last_hidden_state.outer_iter())
+                    .zip(attention_mask.rows())
+                    .map(|((input, tokens), mask)| {
+                        let mut embedding = pool(config.pooling, tokens, mask);
+                        if config.normalize {
+                            normalize(&mut embedding);
+                        }
+
+                        Output {
+                            text: input.text_1,
+                            embedding,
+                        }
+                    })
+                    .collect(),
+            }),
+            _ => {
+                let last_hidden_state = last_hidden_state.shape();
+                bail!("unexpected StringTensorData: {last_hidden_state:?}")
+            }
+        }
+    }
+}
+
+impl<T: Ipnis + ?Sized> IpnisSentenceEmbedding for T {}
+
+/// Returns the cosine similarity of the embeddings, which is in `[-1, 1]`.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> Result<f32> {
+    let dot = dot(a, b)?;
+    let norm = norm(a) * norm(b);
+    if norm == 0.0 {
+        return Ok(0.0);
+    }
+    Ok(dot / norm)
+}
+
+fn pool(
+    pooling: Pooling,
+    tokens: ndarray::ArrayView2<f32>,
+    mask: ndarray::ArrayView1<i64>,
+) -> Vec<f32> {
+    // the real tokens, except the padding
+    let real_tokens = || {
+        tokens
+            .outer_iter()
+            .zip(mask)
+            .filter(|(_, &mask)| mask != 0)
+            .map(|(token, _)| token)
+    };
+
+    match pooling {
+        Pooling::Mean => {
+            let mut embedding = ndarray::Array1::zeros(tokens.ncols());
+            let mut count = 0;
+            for token in real_tokens() {
+                embedding += &token;
+                count += 1;
+            }
+            (embedding / count.max(1) as f32).to_vec()
+        }
+        Pooling::Cls => tokens.row(0).to_vec(),
+        Pooling::Max => {
+            let mut embedding = ndarray::Array1::from_elem(tokens.ncols(), f32::NEG_INFINITY);
+            for token in real_tokens() {
+                embedding.zip_mut_with(&token, |max, &value| *max = max.max(value));
+            }
+            embedding.to_vec()
+        }
+    }
+}
+
+fn normalize(embedding: &mut [f32]) {
+    let norm = norm(embedding);
+    if norm > 0.0 {
+        embedding.iter_mut().for_each(|value| *value /= norm);
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> Result<f32> {
+    check_dimensions(a, b)?;
+    Ok(a.iter().zip(b).map(|(a, b)| a * b).sum())
+}
+
+fn norm(embedding: &[f32]) -> f32 {
+    embedding
+        .iter()
+        .map(|value| value * value)
+        .sum::<f32>()
+        .sqrt()
+}
+
+fn check_dimensions(a: &[f32], b: &[f32]) -> Result<()> {
+    if a.len() != b.len() {
+        let (a, b) = (a.len(), b.len());
+        bail!("mismatched embedding dimensions: {a} != {b}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two real tokens followed by a padding token.
+    fn tokens() -> (ndarray::Array2<f32>, ndarray::Array1<i64>) {
+        let tokens = ndarray::arr2(&[[1.0, -2.0], [3.0, 4.0], [100.0, 100.0]]);
+        let mask = ndarray::arr1(&[1, 1, 0]);
+        (tokens, mask)
+    }
+
+    #[test]
+    fn pooling_skips_the_padding() {
+        let (tokens, mask) = tokens();
+        let pool = |pooling| pool(pooling, tokens.view(), mask.view());
+
+        assert_eq!(pool(Pooling::Mean), [2.0, 1.0]);
+        assert_eq!(pool(Pooling::Cls), [1.0, -2.0]);
+        assert_eq!(pool(Pooling::Max), [3.0, 4.0]);
+    }
+
+    #[test]
+    fn normalize_scales_to_the_unit_length() {
+        let mut embedding = vec![3.0, 4.0];
+        normalize(&mut embedding);
+        assert_eq!(embedding, [0.6, 0.8]);
+
+        // the zero vectors are kept as they are
+        let mut embedding = vec![0.0, 0.0];
+        normalize(&mut embedding);
+        assert_eq!(embedding, [0.0, 0.0]);
+    }
+
+    #[test]
+    fn cosine_similarity_checks_the_dimensions() {
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]).unwrap(), 1.0);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 0.0]).unwrap(), 0.0);
+        assert!(cosine_similarity(&[1.0, 0.0], &[1.0]).is_err());
+    }
+}
@@ -0,0 +1,70 @@
+use std::env;
+
+use ipis::{
+    core::anyhow::{anyhow, bail, Result},
+    env::Infer,
+    path::Path,
+    tokio,
+};
+use ipnis_api::{
+    client::IpnisClientInner,
+    common::{nlp::input::TextInputs, tokenizers::Tokenizer, Ipnis},
+};
+use ipnis_modules_summarization::{IpnisSummarization, SummarizationConfig};
+use ipsis_api::client::IpsisClient;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // create a client
+    let client = IpnisClientInner::<IpsisClient>::try_infer().await?;
+
+    // load a model (e.g. facebook/bart-large-cnn)
+    // NOTE: you can export it manually with: "optimum-cli export onnx --model facebook/bart-large-cnn --task text2text-generation --monolith"
+    let model = client
+        .load_model(&get_path_from_env("IPNIS_SUMMARIZATION_MODEL")?)
+        .await?;
+
+    // create a tokenizer
+    let tokenizer =
+        Tokenizer::from_file(get_env("IPNIS_SUMMARIZATION_TOKENIZER")?).map_err(|e| anyhow!(e))?;
+
+    // make a sample inputs
+    let inputs = TextInputs {
+        text: vec![
+            "The tower is 324 metres (1,063 ft) tall, about the same height as an 81-storey building, and the tallest structure in Paris. Its base is square, measuring 125 metres (410 ft) on each side. During its construction, the Eiffel Tower surpassed the Washington Monument to become the tallest man-made structure in the world, a title it held for 41 years until the Chrysler Building in New York City was finished in 1930.".into(),
+        ],
+    };
+
+    // perform the inference
+    let mut config = SummarizationConfig::bart();
+    config.generation.min_new_tokens = 10;
+    config.generation.max_new_tokens = 60;
+
+    let outputs = client
+        .call_summarization(&model, &tokenizer, &config, inputs)
+        .await?;
+
+    // show the result
+    for (batch, output) in outputs.answers.into_iter().enumerate() {
+        let batch = batch + 1;
+        let summary = &output.summary;
+        println!("Summary for data {batch}th = {summary}");
+    }
+    Ok(())
+}
+
+fn get_env(key: &str) -> Result<String> {
+    env::var(key).map_err(|_| anyhow!("environment variable is not set: {key}"))
+}
+
+/// Parses a stored path, formatted as `{cid}:{length}`.
+fn get_path_from_env(key: &str) -> Result<Path> {
+    let path = get_env(key)?;
+    match path.split_once(':') {
+        Some((value, len)) => Ok(Path {
+            value: value.parse()?,
+            len: len.parse()?,
+        }),
+        None => bail!("malformed path: {path}"),
+    }
+}
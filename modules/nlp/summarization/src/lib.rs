@@ -0,0 +1,545 @@
+pub use ipnis_common::nlp::seq2seq::SplitModels;
+
+use std::future::Future;
+
+use ipis::{
+    async_trait::async_trait,
+    core::anyhow::{anyhow, bail, Result},
+};
+use ipnis_common::{
+    model::Model,
+    nlp::{
+        generation::GenerationConfig,
+        input::{TextInputs, TokenizeOptions, Tokenized},
+        seq2seq::{Seq2Seq, Seq2SeqModels},
+        tokenizer::Tokenizer,
+    },
+    Ipnis,
+};
+
+/// The tokens which the decoder starts with.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum DecoderStart {
+    /// The EOS token followed by the BOS token.
+    #[default]
+    Bart,
+    /// The padding token.
+    T5,
+    Custom(Vec<i64>),
+}
+
+impl DecoderStart {
+    pub fn decoder_prefix<T>(&self, tokenizer: &T) -> Result<Vec<i64>>
+    where
+        T: Tokenizer + ?Sized,
+    {
+        let special_tokens = tokenizer.special_tokens();
+        match self {
+            Self::Bart => match (special_tokens.eos, special_tokens.bos) {
+                (Some(token_eos), Some(token_bos)) => Ok(vec![token_eos, token_bos]),
+                (Some(token_eos), None) => Ok(vec![token_eos]),
+                (None, _) => bail!("the tokenizer has no EOS token"),
+            },
+            Self::T5 => match special_tokens.pad {
+                Some(token_pad) => Ok(vec![token_pad]),
+                None => bail!("the tokenizer has no padding token"),
+            },
+            Self::Custom(tokens) => Ok(tokens.clone()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SummarizationConfig {
+    /// Prepended to each text, e.g. `summarize: ` for T5.
+    pub prefix: Option<String>,
+    pub decoder_start: DecoderStart,
+    /// The minimum and the maximum length of the summaries are given as the new tokens.
+    pub generation: GenerationConfig,
+    /// The maximum number of the input tokens, limited by the model's sequence length as well.
+    pub max_input_length: usize,
+    /// Splits the longer texts into the chunks sharing this number of tokens,
+    /// summarizing the joined summaries of the chunks again.
+    pub chunk_overlap: usize,
+}
+
+impl Default for SummarizationConfig {
+    fn default() -> Self {
+        Self::bart()
+    }
+}
+
+impl SummarizationConfig {
+    pub fn bart() -> Self {
+        Self {
+            prefix: None,
+            decoder_start: DecoderStart::Bart,
+            generation: GenerationConfig {
+                max_new_tokens: 142,
+                min_new_tokens: 56,
+                num_beams: 4,
+                length_penalty: 2.0,
+                no_repeat_ngram_size: 3,
+                ..Default::default()
+            },
+            max_input_length: 1024,
+            chunk_overlap: 0,
+        }
+    }
+
+    pub fn t5() -> Self {
+        Self {
+            prefix: Some("summarize: ".into()),
+            decoder_start: DecoderStart::T5,
+            generation: GenerationConfig {
+                max_new_tokens: 200,
+                min_new_tokens: 30,
+                num_beams: 4,
+                length_penalty: 2.0,
+                no_repeat_ngram_size: 3,
+                ..Default::default()
+            },
+            max_input_length: 512,
+            chunk_overlap: 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outputs {
+    pub answers: Vec<Output>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Output {
+    pub text: String,
+    pub summary: String,
+}
+
+#[async_trait]
+pub trait IpnisSummarization: Ipnis {
+    /// Summarizes the texts, splitting the longer texts into the chunks.
+    async fn call_summarization<T>(
+        &self,
+        model: &Model,
+        tokenizer: &T,
+        config: &SummarizationConfig,
+        inputs: TextInputs,
+    ) -> Result<Outputs>
+    where
+        T: Tokenizer + ?Sized + Sync,
+    {
+        summarize(
+            self,
+            Seq2SeqModels::Single(model),
+            tokenizer,
+            config,
+            inputs,
+        )
+        .await
+    }
+
+    /// Summarizes with the split exports, running the encoder only once
+    /// and feeding only the new tokens into the decoder.
+    async fn call_summarization_with_cache<T>(
+        &self,
+        models: &SplitModels<'_>,
+        tokenizer: &T,
+        config: &SummarizationConfig,
+        inputs: TextInputs,
+    ) -> Result<Outputs>
+    where
+        T: Tokenizer + ?Sized + Sync,
+    {
+        summarize(
+            self,
+            Seq2SeqModels::Split(*models),
+            tokenizer,
+            config,
+            inputs,
+        )
+        .await
+    }
+}
+
+impl<T: Ipnis + ?Sized> IpnisSummarization for T {}
+
+/// Summarizes the texts, splitting the longer texts into the chunks which fit in the model.
+async fn summarize<C, T>(
+    client: &C,
+    models: Seq2SeqModels<'_>,
+    tokenizer: &T,
+    config: &SummarizationConfig,
+    inputs: TextInputs,
+) -> Result<Outputs>
+where
+    C: Ipnis + ?Sized + Sync,
+    T: Tokenizer + ?Sized + Sync,
+{
+    let max_length = TokenizeOptions {
+        max_length: Some(config.max_input_length),
+        ..Default::default()
+    }
+    .with_model(models.encoder())
+    .max_length
+    .unwrap_or(config.max_input_length);
+
+    // the tokens left for the text
+    let num_prefix_tokens = match &config.prefix {
+        Some(prefix) => tokenizer.encode(prefix)?.len(),
+        None => 0,
+    };
+    let chunk_length = max_length
+        .checked_sub(tokenizer.num_special_tokens(false)? + num_prefix_tokens)
+        .filter(|&len| len > config.chunk_overlap)
+        .ok_or_else(|| anyhow!("the model is too short to summarize: {max_length}"))?;
+
+    let summaries = map_reduce(
+        tokenizer,
+        config,
+        chunk_length,
+        inputs.text.clone(),
+        |texts| run(client, models, tokenizer, config, texts),
+    )
+    .await?;
+
+    Ok(Outputs {
+        answers: inputs
+            .text
+            .into_iter()
+            .zip(summaries)
+            .map(|(text, summary)| Output { text, summary })
+            .collect(),
+    })
+}
+
+/// Summarizes the chunks of the longer texts with `summarize`,
+/// joining their summaries until each text fits in a chunk.
+async fn map_reduce<T, F, Fut>(
+    tokenizer: &T,
+    config: &SummarizationConfig,
+    chunk_length: usize,
+    mut texts: Vec<String>,
+    mut summarize: F,
+) -> Result<Vec<String>>
+where
+    T: Tokenizer + ?Sized,
+    F: FnMut(Vec<String>) -> Fut,
+    Fut: Future<Output = Result<Vec<String>>>,
+{
+    let mut lengths: Vec<Option<usize>> = vec![None; texts.len()];
+    loop {
+        let (num_tokens, chunks): (Vec<_>, Vec<_>) = texts
+            .iter()
+            .map(|text| split_text(tokenizer, text, chunk_length, config.chunk_overlap))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .unzip();
+
+        // the joined summaries should be shorter than the texts, or it would never end
+        for ((length, num_tokens), chunks) in lengths.iter_mut().zip(num_tokens).zip(&chunks) {
+            match *length {
+                Some(last) if chunks.len() > 1 && num_tokens >= last => {
+                    bail!("the summaries are not getting shorter: {num_tokens} >= {last} tokens")
+                }
+                _ => *length = Some(num_tokens),
+            }
+        }
+
+        // reduce: summarize the texts which fit in the model
+        if chunks.iter().all(|chunks| chunks.len() <= 1) {
+            return summarize(texts).await;
+        }
+
+        // the summaries should be shorter than the chunks to be reduced
+        if config.generation.max_new_tokens >= chunk_length {
+            let max_new_tokens = config.generation.max_new_tokens;
+            bail!("the summaries are too long to be reduced: {max_new_tokens} >= {chunk_length}");
+        }
+
+        // map: summarize each chunk of the longer texts, joining the summaries of each text
+        let num_chunks: Vec<_> = chunks.iter().map(Vec::len).collect();
+        let mut summaries = summarize(
+            chunks
+                .into_iter()
+                .filter(|chunks| chunks.len() > 1)
+                .flatten()
+                .collect(),
+        )
+        .await?
+        .into_iter();
+        for (text, len) in texts.iter_mut().zip(num_chunks) {
+            if len > 1 {
+                *text = summaries.by_ref().take(len).collect::<Vec<_>>().join(" ");
+            }
+        }
+    }
+}
+
+/// Summarizes the texts in a batch.
+async fn run<C, T>(
+    client: &C,
+    models: Seq2SeqModels<'_>,
+    tokenizer: &T,
+    config: &SummarizationConfig,
+    texts: Vec<String>,
+) -> Result<Vec<String>>
+where
+    C: Ipnis + ?Sized + Sync,
+    T: Tokenizer + ?Sized + Sync,
+{
+    if texts.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let num_inputs = texts.len();
+    let inputs = TextInputs {
+        text: match &config.prefix {
+            Some(prefix) => texts
+                .into_iter()
+                .map(|text| prefix.clone() + &text)
+                .collect(),
+            None => texts,
+        },
+    };
+    let Tokenized {
+        input_ids,
+        attention_mask,
+        ..
+    } = inputs.tokenize_with(
+        tokenizer,
+        &TokenizeOptions {
+            max_length: Some(config.max_input_length),
+            ..Default::default()
+        }
+        .with_model(models.encoder()),
+    )?;
+
+    let session = Seq2Seq::new(
+        client,
+        models,
+        &config.generation,
+        input_ids,
+        attention_mask,
+        vec![config.decoder_start.decoder_prefix(tokenizer)?; num_inputs],
+        &tokenizer.special_tokens(),
+    )?;
+    session
+        .run()
+        .await?
+        .into_iter()
+        .map(|hypothesis| {
+            tokenizer
+                .decode(hypothesis.generated(), true)
+                .map(|summary| summary.trim().to_string())
+        })
+        .collect()
+}
+
+/// Splits the text into the chunks of the tokens, sharing `overlap` tokens with the previous chunk.
+///
+/// Returns the number of the tokens of the text as well.
+fn split_text<T>(
+    tokenizer: &T,
+    text: &str,
+    len: usize,
+    overlap: usize,
+) -> Result<(usize, Vec<String>)>
+where
+    T: Tokenizer + ?Sized,
+{
+    let encoding = tokenizer.encode(text)?;
+    if encoding.len() <= len {
+        return Ok((encoding.len(), vec![text.to_string()]));
+    }
+
+    let chars: Vec<_> = text.chars().collect();
+    let mut chunks = vec![];
+    let mut begin = 0;
+    while begin < encoding.len() {
+        let end = (begin + len).min(encoding.len());
+        let offsets = &encoding.offsets[begin..end];
+        let start = offsets.iter().flatten().map(|&(start, _)| start).next();
+        let stop = offsets.iter().flatten().map(|&(_, stop)| stop).next_back();
+        if let (Some(start), Some(stop)) = (start, stop) {
+            chunks.push(chars[start..stop.min(chars.len())].iter().collect());
+        }
+
+        if end == encoding.len() {
+            break;
+        }
+        begin = end - overlap;
+    }
+    Ok((encoding.len(), chunks))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use ipis::futures::executor::block_on;
+    use ipnis_common::nlp::tokenizer::Encoding;
+
+    use super::*;
+
+    /// Splits the texts by the whitespaces.
+    struct WhitespaceTokenizer;
+
+    impl Tokenizer for WhitespaceTokenizer {
+        fn encode(&self, text: &str) -> Result<Encoding> {
+            let mut offsets = vec![];
+            let mut start = None;
+            for (index, c) in text.chars().chain([' ']).enumerate() {
+                match (start, c.is_whitespace()) {
+                    (None, false) => start = Some(index),
+                    (Some(begin), true) => {
+                        offsets.push(Some((begin, index)));
+                        start = None;
+                    }
+                    _ => (),
+                }
+            }
+
+            let len = offsets.len();
+            Ok(Encoding {
+                ids: vec![1; len],
+                type_ids: vec![0; len],
+                special_tokens_mask: vec![false; len],
+                offsets,
+                sequence_ids: vec![Some(0); len],
+            })
+        }
+
+        fn build_inputs(&self, first: Encoding, _: Option<Encoding>) -> Result<Encoding> {
+            Ok(first)
+        }
+
+        fn decode(&self, _: &[i64], _: bool) -> Result<String> {
+            Ok(Default::default())
+        }
+
+        fn token_to_id(&self, _: &str) -> Option<i64> {
+            None
+        }
+
+        fn id_to_token(&self, _: i64) -> Option<String> {
+            None
+        }
+    }
+
+    fn words(range: ::core::ops::Range<usize>) -> String {
+        range
+            .map(|index| format!("w{index}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn config(chunk_overlap: usize) -> SummarizationConfig {
+        SummarizationConfig {
+            generation: GenerationConfig {
+                max_new_tokens: 4,
+                ..Default::default()
+            },
+            chunk_overlap,
+            ..Default::default()
+        }
+    }
+
+    /// Summarizes with `summarize`, recording the inputs of each call.
+    fn map_reduce_with(
+        config: &SummarizationConfig,
+        texts: Vec<String>,
+        summarize: impl Fn(&str) -> String,
+    ) -> (Result<Vec<String>>, Vec<Vec<String>>) {
+        let calls = RefCell::new(vec![]);
+        let summaries = block_on(map_reduce(
+            &WhitespaceTokenizer,
+            config,
+            8,
+            texts,
+            |texts: Vec<String>| {
+                let summaries = texts.iter().map(|text| summarize(text)).collect();
+                calls.borrow_mut().push(texts);
+                async move { Ok(summaries) }
+            },
+        ));
+        (summaries, calls.into_inner())
+    }
+
+    /// Keeps the first 2 words.
+    fn first_words(text: &str) -> String {
+        text.split_whitespace()
+            .take(2)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn texts_are_split_into_the_overlapping_chunks() {
+        let split = |overlap| split_text(&WhitespaceTokenizer, &words(0..10), 4, overlap).unwrap();
+
+        assert_eq!(split(0), (10, vec![words(0..4), words(4..8), words(8..10)]),);
+        assert_eq!(split(1), (10, vec![words(0..4), words(3..7), words(6..10)]),);
+        assert_eq!(
+            split_text(&WhitespaceTokenizer, &words(0..4), 4, 1).unwrap(),
+            (4, vec![words(0..4)]),
+        );
+    }
+
+    #[test]
+    fn short_texts_are_summarized_at_once() {
+        let texts = vec![words(0..8), words(0..3)];
+        let (summaries, calls) = map_reduce_with(&config(0), texts.clone(), first_words);
+
+        assert_eq!(summaries.unwrap(), [words(0..2), words(0..2)]);
+        assert_eq!(calls, [texts]);
+    }
+
+    #[test]
+    fn long_texts_are_mapped_and_reduced() {
+        let texts = vec![words(0..20), words(100..103)];
+        let (summaries, calls) = map_reduce_with(&config(0), texts, first_words);
+
+        assert_eq!(summaries.unwrap(), [words(0..2), words(100..102)]);
+        assert_eq!(
+            calls,
+            [
+                // map: the chunks of the long text only
+                vec![words(0..8), words(8..16), words(16..20)],
+                // reduce: the joined summaries with the short text
+                vec!["w0 w1 w8 w9 w16 w17".into(), words(100..103)],
+            ],
+        );
+    }
+
+    #[test]
+    fn summaries_should_get_shorter() {
+        // the summaries are as long as the chunks
+        let (summaries, calls) = map_reduce_with(&config(0), vec![words(0..20)], str::to_string);
+        assert!(summaries.is_err());
+        assert_eq!(calls.len(), 1);
+
+        // the overlapping chunks are even longer
+        let (summaries, calls) = map_reduce_with(&config(2), vec![words(0..20)], str::to_string);
+        assert!(summaries.is_err());
+        assert_eq!(calls.len(), 1);
+    }
+
+    #[test]
+    fn summaries_should_be_shorter_than_the_chunks() {
+        let config = SummarizationConfig {
+            generation: GenerationConfig {
+                max_new_tokens: 8,
+                ..Default::default()
+            },
+            ..config(0)
+        };
+
+        let (summaries, calls) = map_reduce_with(&config, vec![words(0..20)], first_words);
+        assert!(summaries.is_err());
+        assert!(calls.is_empty());
+
+        let (summaries, _) = map_reduce_with(&config, vec![words(0..8)], first_words);
+        assert_eq!(summaries.unwrap(), [words(0..2)]);
+    }
+}
@@ -1,3 +1,8 @@
+use std::collections::BTreeMap;
+
+use ipis::core::anyhow::{bail, Result};
+
+/// The NLI label indices, used by the zero-shot classification.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Labels {
     pub contradiction: Option<usize>,
@@ -20,3 +25,184 @@ impl Labels {
         }
     }
 }
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ProblemType {
+    /// Picks one of the labels with softmax.
+    #[default]
+    SingleLabel,
+    /// Scores each label independently with sigmoid.
+    MultiLabel,
+}
+
+/// The named labels, ordered by the indices of the logits.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LabelSet {
+    pub names: Vec<String>,
+    pub problem_type: ProblemType,
+}
+
+impl LabelSet {
+    pub fn new<I>(names: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: ToString,
+    {
+        Self {
+            names: names.into_iter().map(|name| name.to_string()).collect(),
+            problem_type: ProblemType::default(),
+        }
+    }
+
+    pub fn multi_label(mut self) -> Self {
+        self.problem_type = ProblemType::MultiLabel;
+        self
+    }
+
+    /// Loads a label file, which has a label per line.
+    pub fn from_file(path: impl AsRef<::std::path::Path>) -> Result<Self> {
+        let names = ::std::fs::read_to_string(path)?;
+        Ok(Self::new(
+            names
+                .lines()
+                .map(|name| name.trim_end_matches('\r'))
+                .filter(|name| !name.is_empty()),
+        ))
+    }
+
+    /// Loads the `id2label` and `problem_type` from the model's `config.json`.
+    pub fn from_config(path: impl AsRef<::std::path::Path>) -> Result<Self> {
+        Self::from_config_str(&::std::fs::read_to_string(path)?)
+    }
+
+    pub fn from_config_str(config: &str) -> Result<Self> {
+        Self::from_config_value(&::serde_json::from_str(config)?)
+    }
+
+    /// Loads the `id2label` and `problem_type` from the model's metadata,
+    /// e.g. the custom metadata properties of an ONNX model.
+    ///
+    /// The `id2label` property is stored as a JSON string.
+    pub fn from_metadata<'a, I>(metadata: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        let mut config = ::serde_json::Map::default();
+        for (key, value) in metadata {
+            match key {
+                "id2label" => {
+                    config.insert(key.into(), ::serde_json::from_str(value)?);
+                }
+                "problem_type" => {
+                    config.insert(key.into(), value.into());
+                }
+                _ => continue,
+            }
+        }
+        Self::from_config_value(&config.into())
+    }
+
+    fn from_config_value(config: &::serde_json::Value) -> Result<Self> {
+        let id2label = match config.get("id2label").and_then(|labels| labels.as_object()) {
+            Some(id2label) => id2label
+                .iter()
+                .map(|(id, label)| match (id.parse::<usize>(), label.as_str()) {
+                    (Ok(id), Some(label)) => Ok((id, label)),
+                    _ => bail!("malformed label: {id} => {label}"),
+                })
+                .collect::<Result<BTreeMap<_, _>>>()?,
+            None => bail!("'id2label' is required"),
+        };
+
+        // the labels should cover all the logits
+        if let Some((index, _)) = id2label.keys().enumerate().find(|(index, id)| index != *id) {
+            bail!("missing label: {index}");
+        }
+
+        let problem_type = match config.get("problem_type").and_then(|ty| ty.as_str()) {
+            Some("multi_label_classification") => ProblemType::MultiLabel,
+            Some("single_label_classification") | None => ProblemType::SingleLabel,
+            Some(ty) => bail!("unsupported problem type: {ty}"),
+        };
+
+        Ok(Self {
+            names: id2label.into_values().map(Into::into).collect(),
+            problem_type,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_are_ordered_by_ids() {
+        let config = r#"{
+            "id2label": { "10": "k", "2": "c", "0": "a", "1": "b", "3": "d", "4": "e",
+                          "5": "f", "6": "g", "7": "h", "8": "i", "9": "j" },
+            "architectures": ["BertForSequenceClassification"]
+        }"#;
+        let labels = LabelSet::from_config_str(config).unwrap();
+
+        assert_eq!(labels.names, LabelSet::new("abcdefghijk".chars()).names);
+        assert_eq!(labels.problem_type, ProblemType::SingleLabel);
+    }
+
+    #[test]
+    fn problem_types_are_parsed() {
+        let parse = |ty: &str| {
+            let config = format!(r#"{{ "id2label": {{ "0": "a" }}, "problem_type": "{ty}" }}"#);
+            LabelSet::from_config_str(&config).map(|labels| labels.problem_type)
+        };
+
+        assert_eq!(ProblemType::default(), ProblemType::SingleLabel);
+        assert_eq!(
+            parse("single_label_classification").unwrap(),
+            ProblemType::SingleLabel,
+        );
+        assert_eq!(
+            parse("multi_label_classification").unwrap(),
+            ProblemType::MultiLabel,
+        );
+        assert!(parse("regression").is_err());
+    }
+
+    #[test]
+    fn malformed_configs_are_rejected() {
+        for config in [
+            r#"{}"#,
+            r#"{ "id2label": ["a", "b"] }"#,
+            r#"{ "id2label": { "zero": "a" } }"#,
+            r#"{ "id2label": { "0": 1 } }"#,
+            r#"{ "id2label": { "0": "a", "2": "c" } }"#,
+            r#"not a json"#,
+        ] {
+            assert!(LabelSet::from_config_str(config).is_err(), "{config}");
+        }
+    }
+
+    #[test]
+    fn labels_are_loaded_from_metadata() {
+        let metadata = [
+            ("producer", "optimum"),
+            ("id2label", r#"{ "1": "POSITIVE", "0": "NEGATIVE" }"#),
+            ("problem_type", "multi_label_classification"),
+        ];
+        let labels = LabelSet::from_metadata(metadata).unwrap();
+
+        assert_eq!(
+            labels,
+            LabelSet::new(["NEGATIVE", "POSITIVE"]).multi_label()
+        );
+        assert!(LabelSet::from_metadata([("producer", "optimum")]).is_err());
+    }
+}
@@ -11,17 +11,16 @@ use ipis::{
 use ipnis_common::{
     model::Model,
     nlp::{
-        input::{SCInputs, Tokenized},
-        output::TextLabel,
+        input::{GenericInput, TokenizeOptions, Tokenized},
         tensor::StringTensorData,
+        tokenizer::Tokenizer,
     },
     onnxruntime::tensor::ndarray_tensor::NdArrayTensor,
-    rust_tokenizers::{tokenizer::Tokenizer, vocab::Vocab},
     tensor::Tensor,
     Ipnis,
 };
 
-use crate::labels::Labels;
+use crate::labels::{LabelSet, Labels, ProblemType};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Outputs {
@@ -30,9 +29,18 @@ pub struct Outputs {
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Output {
-    pub query: String,
+    /// The first text, e.g. the premise.
     pub context: String,
-    pub label: Option<TextLabel>,
+    /// The second text, e.g. the hypothesis, if given.
+    pub query: Option<String>,
+    /// The labels, sorted by their scores in descending order.
+    pub labels: Vec<LabelScore>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LabelScore {
+    pub label: String,
+    pub score: OrderedFloat<f32>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -42,8 +50,10 @@ pub struct RawOutputs {
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct RawOutput {
-    pub query: String,
+    /// The first text, e.g. the premise.
     pub context: String,
+    /// The second text, e.g. the hypothesis, if given.
+    pub query: Option<String>,
     pub prob_contradiction: Option<OrderedFloat<f32>>,
     pub prob_entailment: Option<OrderedFloat<f32>>,
     pub prob_neutral: Option<OrderedFloat<f32>>,
@@ -51,67 +61,141 @@ pub struct RawOutput {
 
 #[async_trait]
 pub trait IpnisTextClassification: Ipnis {
-    async fn call_text_classification<T, V>(
+    async fn call_text_classification<I, T>(
         &self,
         model: &Model,
         tokenizer: &T,
-        inputs: SCInputs,
-        labels: Labels,
+        inputs: I,
+        labels: &LabelSet,
     ) -> Result<Outputs>
     where
-        T: Tokenizer<V> + Sync,
-        V: Vocab,
+        I: Into<Vec<GenericInput>> + Send,
+        T: Tokenizer + ?Sized + Sync,
     {
-        let outputs = self
-            .call_text_classification_raw(model, tokenizer, inputs, labels)
+        let (inputs_str, logits) = self
+            .call_text_classification_logits(model, tokenizer, inputs)
             .await?;
 
+        if logits.ncols() != labels.len() {
+            let expected = labels.len();
+            let given = logits.ncols();
+            bail!("unexpected labels: Expected {expected}, Given {given}");
+        }
+
+        // convert logits into scores
+        let probs = match labels.problem_type {
+            ProblemType::SingleLabel => logits.softmax(ndarray::Axis(1)),
+            ProblemType::MultiLabel => logits.mapv(|logit| 1.0 / (1.0 + (-logit).exp())),
+        };
+
         Ok(Outputs {
-            answers: outputs
-                .answers
+            answers: inputs_str
                 .into_iter()
-                .map(|output| {
-                    let probs = [
-                        output.prob_contradiction,
-                        output.prob_entailment,
-                        output.prob_neutral,
-                    ];
+                .zip(probs.rows().into_iter())
+                .map(|(input, probs)| {
+                    let mut labels: Vec<_> = labels
+                        .names
+                        .iter()
+                        .zip(probs)
+                        .map(|(label, &prob)| LabelScore {
+                            label: label.clone(),
+                            score: OrderedFloat(prob),
+                        })
+                        .collect();
+                    labels.sort_by_key(|label| ::core::cmp::Reverse(label.score));
 
                     Output {
-                        query: output.query,
-                        context: output.context,
-                        label: probs
-                            .into_iter()
-                            .enumerate()
-                            .max_by_key(|(_, prob)| *prob)
-                            .and_then(|(idx, prob)| {
-                                prob.map(|_| match idx {
-                                    0 => TextLabel::Contradiction,
-                                    1 => TextLabel::Entailment,
-                                    2 => TextLabel::Neutral,
-                                    _ => unreachable!(),
-                                })
-                            }),
+                        context: input.text_1,
+                        query: input.text_2,
+                        labels,
                     }
                 })
                 .collect(),
         })
     }
 
-    async fn call_text_classification_raw<T, V>(
+    async fn call_text_classification_raw<I, T>(
         &self,
         model: &Model,
         tokenizer: &T,
-        inputs: SCInputs,
+        inputs: I,
         labels: Labels,
     ) -> Result<RawOutputs>
     where
-        T: Tokenizer<V> + Sync,
-        V: Vocab,
+        I: Into<Vec<GenericInput>> + Send,
+        T: Tokenizer + ?Sized + Sync,
+    {
+        let (inputs_str, logits) = self
+            .call_text_classification_logits(model, tokenizer, inputs)
+            .await?;
+
+        // collect logits
+        let logits = {
+            let mut arrays = vec![];
+
+            let mut append = |idx| {
+                if let Some(idx) = idx {
+                    arrays.push(logits.slice(ndarray::s![.., idx..idx + 1]));
+                }
+            };
+
+            append(labels.contradiction);
+            append(labels.entailment);
+            append(labels.neutral);
+
+            ndarray::concatenate(ndarray::Axis(1), &arrays)?
+        };
+
+        // execute softmax
+        let probs = logits.softmax(ndarray::Axis(1));
+
+        Ok(RawOutputs {
+            answers: inputs_str
+                .into_iter()
+                .zip(probs.rows().into_iter())
+                .map(|(input, probs)| {
+                    let mut probs = probs.into_iter().copied();
+
+                    let prob_contradiction = labels
+                        .contradiction
+                        .and_then(|_| probs.next())
+                        .map(OrderedFloat);
+                    let prob_entailment = labels
+                        .entailment
+                        .and_then(|_| probs.next())
+                        .map(OrderedFloat);
+                    let prob_neutral = labels.neutral.and_then(|_| probs.next()).map(OrderedFloat);
+
+                    RawOutput {
+                        context: input.text_1,
+                        query: input.text_2,
+                        prob_contradiction,
+                        prob_entailment,
+                        prob_neutral,
+                    }
+                })
+                .collect(),
+        })
+    }
+
+    /// Returns the logits of each input, e.g. to normalize them across the inputs.
+    async fn call_text_classification_logits<I, T>(
+        &self,
+        model: &Model,
+        tokenizer: &T,
+        inputs: I,
+    ) -> Result<(Vec<GenericInput>, ndarray::Array2<f32>)>
+    where
+        I: Into<Vec<GenericInput>> + Send,
+        T: Tokenizer + ?Sized + Sync,
     {
         let Tokenized {
             inputs, inputs_str, ..
-        } = inputs.tokenize(tokenizer)?;
+        } = GenericInput::tokenize_batch_with(
+            inputs.into(),
+            tokenizer,
+            &TokenizeOptions::default().with_model(model),
+        )?;
 
         let mut outputs = self.call(model, &inputs).await?;
         if outputs.is_empty() {
@@ -121,57 +205,8 @@ pub trait IpnisTextClassification: Ipnis {
 
         let logits: Tensor<StringTensorData> = Tensor::find(&mut outputs, "logits")?.try_into()?;
 
-        match &logits.data {
-            StringTensorData::F32(logits) => {
-                // collect logits
-                let logits = {
-                    let mut arrays = vec![];
-
-                    let mut append = |idx| {
-                        if let Some(idx) = idx {
-                            arrays.push(logits.slice(ndarray::s![.., idx..idx + 1]));
-                        }
-                    };
-
-                    append(labels.contradiction);
-                    append(labels.entailment);
-                    append(labels.neutral);
-
-                    ndarray::concatenate(ndarray::Axis(1), &arrays)?
-                };
-
-                // execute softmax
-                let probs = logits.softmax(ndarray::Axis(1));
-
-                Ok(RawOutputs {
-                    answers: inputs_str
-                        .into_iter()
-                        .zip(probs.rows().into_iter())
-                        .map(|(input, probs)| {
-                            let mut probs = probs.into_iter().copied();
-
-                            let prob_contradiction = labels
-                                .contradiction
-                                .and_then(|_| probs.next())
-                                .map(OrderedFloat);
-                            let prob_entailment = labels
-                                .entailment
-                                .and_then(|_| probs.next())
-                                .map(OrderedFloat);
-                            let prob_neutral =
-                                labels.neutral.and_then(|_| probs.next()).map(OrderedFloat);
-
-                            RawOutput {
-                                query: input.text_1,
-                                context: input.text_2.unwrap(),
-                                prob_contradiction,
-                                prob_entailment,
-                                prob_neutral,
-                            }
-                        })
-                        .collect(),
-                })
-            }
+        match logits.data {
+            StringTensorData::F32(logits) => Ok((inputs_str, logits.0.into_owned())),
             _ => {
                 let logits = logits.shape();
                 bail!("unexpected StringTensorData: {logits:?}")
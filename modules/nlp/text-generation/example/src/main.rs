@@ -0,0 +1,90 @@
+use std::{env, io::Write};
+
+use ipis::{
+    core::anyhow::{anyhow, bail, Result},
+    env::Infer,
+    futures::TryStreamExt,
+    path::Path,
+    tokio,
+};
+use ipnis_api::{
+    client::IpnisClientInner,
+    common::{nlp::input::TextInputs, tokenizers::Tokenizer, Ipnis},
+};
+use ipnis_modules_text_generation::{IpnisTextGeneration, TextGenerationConfig};
+use ipsis_api::client::IpsisClient;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // create a client
+    let client = IpnisClientInner::<IpsisClient>::try_infer().await?;
+
+    // load a model (e.g. gpt2, exported with the past key-values)
+    // NOTE: you can export it manually with: "optimum-cli export onnx --model gpt2 --task text-generation-with-past"
+    let model = client
+        .load_model(&get_path_from_env("IPNIS_TEXT_GENERATION_MODEL")?)
+        .await?;
+
+    // create a tokenizer
+    let tokenizer = Tokenizer::from_file(get_env("IPNIS_TEXT_GENERATION_TOKENIZER")?)
+        .map_err(|e| anyhow!(e))?;
+
+    // make a sample inputs
+    let inputs = TextInputs {
+        text: vec![
+            "Once upon a time,".into(),
+            "The capital city of France is".into(),
+        ],
+    };
+
+    // perform the inference
+    let mut config = TextGenerationConfig::default();
+    config.generation.max_new_tokens = 32;
+    config.stop_sequences.push("\n".into());
+
+    let outputs = client
+        .call_text_generation(&model, &tokenizer, &config, inputs)
+        .await?;
+
+    // show the result
+    for (batch, output) in outputs.answers.into_iter().enumerate() {
+        let batch = batch + 1;
+        let prompt = &output.prompt;
+        let text = &output.text;
+        let logprob: f32 = output.tokens.iter().map(|token| token.logprob.0).sum();
+        println!("Text for data {batch}th = {prompt}{text} ({logprob})");
+    }
+
+    // stream the result
+    let prompt = "Once upon a time,";
+    let inputs = TextInputs {
+        text: vec![prompt.into()],
+    };
+
+    let mut stream = client.call_text_generation_stream(&model, &tokenizer, &config, inputs);
+    print!("Streaming text = {prompt}");
+    while let Some(deltas) = stream.try_next().await? {
+        for delta in deltas {
+            print!("{}", delta.text);
+        }
+        std::io::stdout().flush()?;
+    }
+    println!();
+    Ok(())
+}
+
+fn get_env(key: &str) -> Result<String> {
+    env::var(key).map_err(|_| anyhow!("environment variable is not set: {key}"))
+}
+
+/// Parses a stored path, formatted as `{cid}:{length}`.
+fn get_path_from_env(key: &str) -> Result<Path> {
+    let path = get_env(key)?;
+    match path.split_once(':') {
+        Some((value, len)) => Ok(Path {
+            value: value.parse()?,
+            len: len.parse()?,
+        }),
+        None => bail!("malformed path: {path}"),
+    }
+}
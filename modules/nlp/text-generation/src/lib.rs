@@ -0,0 +1,385 @@
+use ipis::{
+    async_trait::async_trait,
+    core::{
+        anyhow::{anyhow, Result},
+        ordered_float::OrderedFloat,
+    },
+    futures::stream::{self, BoxStream, StreamExt},
+};
+use ipnis_common::{
+    model::Model,
+    nlp::{
+        causal_lm::CausalLm,
+        generation::{GenerationConfig, Hypothesis},
+        input::{GenericInput, Padding, TextInputs, TokenizeOptions, Tokenized},
+        session::{stream_generation, to_rows, GenerationModels, GenerationRequest},
+        stream::TextDelta,
+        tokenizer::Tokenizer,
+    },
+    Ipnis,
+};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TextGenerationConfig {
+    pub generation: GenerationConfig,
+    /// Stops generating when any of them is generated, which is excluded from the text.
+    pub stop_sequences: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outputs {
+    pub answers: Vec<Output>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Output {
+    pub prompt: String,
+    pub text: String,
+    /// The generated tokens, including the EOS token and the stop sequence if any.
+    pub tokens: Vec<GeneratedToken>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GeneratedToken {
+    pub id: i64,
+    pub text: String,
+    pub logprob: OrderedFloat<f32>,
+}
+
+#[async_trait]
+pub trait IpnisTextGeneration: Ipnis {
+    /// Continues the prompts with a decoder-only language model.
+    ///
+    /// The past key-values are reused between the steps if the model consumes them.
+    async fn call_text_generation<T>(
+        &self,
+        model: &Model,
+        tokenizer: &T,
+        config: &TextGenerationConfig,
+        inputs: TextInputs,
+    ) -> Result<Outputs>
+    where
+        T: Tokenizer + ?Sized + Sync,
+    {
+        let token_eos = tokenizer
+            .special_tokens()
+            .eos
+            .ok_or_else(|| anyhow!("the tokenizer has no EOS token"))?;
+        if inputs.text.is_empty() {
+            return Ok(Outputs { answers: vec![] });
+        }
+
+        let Tokenized {
+            input_ids,
+            attention_mask,
+            inputs_str,
+            encodings,
+            ..
+        } = encode(model, tokenizer, inputs)?;
+
+        let mut session = CausalLm::new(
+            self,
+            model,
+            &config.generation,
+            input_ids,
+            attention_mask,
+            encodings.into_iter().map(|encoding| encoding.ids).collect(),
+            token_eos,
+        )?;
+        while !session.is_done() {
+            session.step().await?;
+
+            // finish the hypotheses with the stop sequences
+            if !config.stop_sequences.is_empty() {
+                session.stop_if(|hypothesis| {
+                    ends_with_stop_sequence(tokenizer, config, hypothesis.generated())
+                })?;
+            }
+        }
+        finish(tokenizer, config, inputs_str, session.finish())
+    }
+
+    /// Continues the prompts like [`call_text_generation`](Self::call_text_generation),
+    /// yielding the newly generated text of each prompt step by step.
+    ///
+    /// The tokens are generated by the service and decoded here,
+    /// and the generation is cancelled when the stream is dropped.
+    fn call_text_generation_stream<'a, T>(
+        &'a self,
+        model: &'a Model,
+        tokenizer: &'a T,
+        config: &'a TextGenerationConfig,
+        inputs: TextInputs,
+    ) -> BoxStream<'a, Result<Vec<TextDelta>>>
+    where
+        Self: Sync,
+        T: Tokenizer + ?Sized + Sync,
+    {
+        if inputs.text.is_empty() {
+            return stream::empty().boxed();
+        }
+
+        let request = encode(model, tokenizer, inputs).map(|tokenized| GenerationRequest {
+            models: GenerationModels::CausalLm(model.clone()),
+            config: config.generation.clone(),
+            input_ids: to_rows(&tokenized.input_ids),
+            attention_mask: to_rows(&tokenized.attention_mask),
+            prefixes: tokenized
+                .encodings
+                .into_iter()
+                .map(|encoding| encoding.ids)
+                .collect(),
+            special_tokens: tokenizer.special_tokens(),
+        });
+        let request = match request {
+            Ok(request) => request,
+            Err(error) => return stream::iter([Err(error)]).boxed(),
+        };
+
+        stream_generation(self, request, move |generated, is_finished| {
+            decode(tokenizer, config, generated, is_finished)
+        })
+    }
+}
+
+impl<T: Ipnis + ?Sized> IpnisTextGeneration for T {}
+
+/// Tokenizes the prompts, which are aligned to the right, next to the generated tokens.
+fn encode<T>(model: &Model, tokenizer: &T, inputs: TextInputs) -> Result<Tokenized>
+where
+    T: Tokenizer + ?Sized,
+{
+    inputs.tokenize_with(
+        tokenizer,
+        &TokenizeOptions {
+            padding: Padding::Left,
+            ..Default::default()
+        }
+        .with_model(model),
+    )
+}
+
+fn finish<T>(
+    tokenizer: &T,
+    config: &TextGenerationConfig,
+    inputs_str: Vec<GenericInput>,
+    hypotheses: Vec<Hypothesis>,
+) -> Result<Outputs>
+where
+    T: Tokenizer + ?Sized,
+{
+    Ok(Outputs {
+        answers: inputs_str
+            .into_iter()
+            .zip(hypotheses)
+            .map(|(input, hypothesis)| {
+                let generated = hypothesis.generated();
+                let mut text = tokenizer.decode(generated, true)?;
+                if let Some(index) = find_stop_sequence(config, &text) {
+                    text.truncate(index);
+                }
+
+                Ok(Output {
+                    prompt: input.text_1,
+                    text,
+                    tokens: generated
+                        .iter()
+                        .zip(&hypothesis.logprobs)
+                        .map(|(&id, &logprob)| {
+                            Ok(GeneratedToken {
+                                id,
+                                text: tokenizer.decode(&[id], false)?,
+                                logprob: OrderedFloat(logprob),
+                            })
+                        })
+                        .collect::<Result<_>>()?,
+                })
+            })
+            .collect::<Result<_>>()?,
+    })
+}
+
+/// Decodes the generated text so far, holding back the possible beginning of a stop sequence.
+///
+/// Returns whether a stop sequence is generated as well.
+fn decode<T>(
+    tokenizer: &T,
+    config: &TextGenerationConfig,
+    generated: &[i64],
+    is_finished: bool,
+) -> Option<(String, bool)>
+where
+    T: Tokenizer + ?Sized,
+{
+    let mut text = tokenizer.decode(generated, true).ok()?;
+    if let Some(index) = find_stop_sequence(config, &text) {
+        text.truncate(index);
+        return Some((text, true));
+    }
+
+    if !is_finished {
+        let len = config
+            .stop_sequences
+            .iter()
+            .filter_map(|stop_sequence| {
+                (1..stop_sequence.len())
+                    .rev()
+                    .filter_map(|len| stop_sequence.get(..len))
+                    .find(|prefix| text.ends_with(prefix))
+                    .map(str::len)
+            })
+            .max()
+            .unwrap_or_default();
+        text.truncate(text.len() - len);
+    }
+    Some((text, false))
+}
+
+/// Returns whether a stop sequence is generated, decoding only the last tokens.
+///
+/// As each step is checked, a new stop sequence should end with the last token.
+/// Each token is assumed to be decoded into a character at least.
+fn ends_with_stop_sequence<T>(
+    tokenizer: &T,
+    config: &TextGenerationConfig,
+    generated: &[i64],
+) -> bool
+where
+    T: Tokenizer + ?Sized,
+{
+    // one more token, as the first one may be decoded differently without the previous ones
+    let window = config
+        .stop_sequences
+        .iter()
+        .map(|stop_sequence| stop_sequence.chars().count() + 1)
+        .max()
+        .unwrap_or_default();
+
+    let generated = &generated[generated.len().saturating_sub(window)..];
+    match tokenizer.decode(generated, true) {
+        Ok(text) => find_stop_sequence(config, &text).is_some(),
+        Err(_) => false,
+    }
+}
+
+/// Returns the byte index of the first stop sequence in the text, if any.
+fn find_stop_sequence(config: &TextGenerationConfig, text: &str) -> Option<usize> {
+    config
+        .stop_sequences
+        .iter()
+        .filter(|stop_sequence| !stop_sequence.is_empty())
+        .filter_map(|stop_sequence| text.find(stop_sequence.as_str()))
+        .min()
+}
+
+#[cfg(test)]
+mod tests {
+    use ipnis_common::nlp::tokenizer::Encoding;
+
+    use super::*;
+
+    /// Decodes each token into a character, skipping the negative ids.
+    struct CharTokenizer;
+
+    impl Tokenizer for CharTokenizer {
+        fn encode(&self, text: &str) -> Result<Encoding> {
+            let len = text.chars().count();
+            Ok(Encoding {
+                ids: text.chars().map(|c| c as i64).collect(),
+                type_ids: vec![0; len],
+                special_tokens_mask: vec![false; len],
+                offsets: vec![None; len],
+                sequence_ids: vec![Some(0); len],
+            })
+        }
+
+        fn build_inputs(&self, first: Encoding, _: Option<Encoding>) -> Result<Encoding> {
+            Ok(first)
+        }
+
+        fn decode(&self, ids: &[i64], _: bool) -> Result<String> {
+            ids.iter()
+                .filter(|&&id| id >= 0)
+                .map(|&id| char::from_u32(id as u32).ok_or_else(|| anyhow!("unknown token: {id}")))
+                .collect()
+        }
+
+        fn token_to_id(&self, _: &str) -> Option<i64> {
+            None
+        }
+
+        fn id_to_token(&self, _: i64) -> Option<String> {
+            None
+        }
+    }
+
+    fn config() -> TextGenerationConfig {
+        TextGenerationConfig {
+            stop_sequences: vec!["\nUser:".into(), "###".into()],
+            ..Default::default()
+        }
+    }
+
+    fn ids(text: &str) -> Vec<i64> {
+        CharTokenizer.encode(text).unwrap().ids
+    }
+
+    #[test]
+    fn stop_sequences_are_found_in_the_last_tokens() {
+        let config = config();
+        let check = |text| ends_with_stop_sequence(&CharTokenizer, &config, &ids(text));
+
+        assert!(check("Hello!\nUser:"));
+        assert!(check("a long long answer, then ###"));
+        assert!(!check("Hello!\nUser"));
+        assert!(!check("##"));
+
+        // the earlier ones have been checked on the previous steps
+        assert!(!check("###, and then a long long answer"));
+    }
+
+    #[test]
+    fn text_is_cut_before_the_stop_sequence() {
+        let config = config();
+        let generated = ids("Hi ### there\nUser: bye");
+
+        assert_eq!(
+            decode(&CharTokenizer, &config, &generated, false),
+            Some(("Hi ".into(), true)),
+        );
+        assert_eq!(
+            finish(
+                &CharTokenizer,
+                &config,
+                vec![GenericInput {
+                    text_1: "prompt".into(),
+                    text_2: None,
+                }],
+                vec![Hypothesis {
+                    input: 0,
+                    logprobs: vec![0.0; generated.len()],
+                    tokens: generated,
+                    num_prefix_tokens: 0,
+                }],
+            )
+            .unwrap()
+            .answers[0]
+                .text,
+            "Hi ",
+        );
+    }
+
+    #[test]
+    fn partial_stop_sequences_are_held_back() {
+        let config = config();
+
+        assert_eq!(
+            decode(&CharTokenizer, &config, &ids("Hello\nUs"), false),
+            Some(("Hello".into(), false)),
+        );
+        assert_eq!(
+            decode(&CharTokenizer, &config, &ids("Hello\nUs"), true),
+            Some(("Hello\nUs".into(), false)),
+        );
+    }
+}
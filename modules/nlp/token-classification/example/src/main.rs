@@ -0,0 +1,71 @@
+use std::env;
+
+use ipis::{
+    core::anyhow::{anyhow, bail, Result},
+    env::Infer,
+    path::Path,
+    tokio,
+};
+use ipnis_api::{
+    client::IpnisClientInner,
+    common::{nlp::input::TextInputs, tokenizers::Tokenizer, Ipnis},
+};
+use ipnis_modules_token_classification::{IpnisTokenClassification, LabelSet};
+use ipsis_api::client::IpsisClient;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // create a client
+    let client = IpnisClientInner::<IpsisClient>::try_infer().await?;
+
+    // load a model (e.g. dslim/bert-base-NER)
+    // NOTE: you can export it manually with: "optimum-cli export onnx --model dslim/bert-base-NER --task token-classification"
+    let model = client
+        .load_model(&get_path_from_env("IPNIS_TOKEN_CLASSIFICATION_MODEL")?)
+        .await?;
+
+    // create a tokenizer
+    let tokenizer = Tokenizer::from_file(get_env("IPNIS_TOKEN_CLASSIFICATION_TOKENIZER")?)
+        .map_err(|e| anyhow!(e))?;
+
+    // load the labels from the model's config.json
+    let labels = LabelSet::from_config(get_env("IPNIS_TOKEN_CLASSIFICATION_CONFIG")?)?;
+
+    // make a sample inputs
+    let inputs = TextInputs {
+        text: vec!["My name is Wolfgang and I live in Berlin.".into()],
+    };
+
+    // perform the inference
+    let outputs = client
+        .call_token_classification(&model, &tokenizer, &labels, &Default::default(), inputs)
+        .await?;
+
+    // show the result
+    for (batch, output) in outputs.answers.into_iter().enumerate() {
+        let batch = batch + 1;
+        for entity in output.entities {
+            let label = &entity.label;
+            let word = &entity.word;
+            let score = entity.score;
+            println!("Entity for data {batch}th = {word} ({label}, {score})");
+        }
+    }
+    Ok(())
+}
+
+fn get_env(key: &str) -> Result<String> {
+    env::var(key).map_err(|_| anyhow!("environment variable is not set: {key}"))
+}
+
+/// Parses a stored path, formatted as `{cid}:{length}`.
+fn get_path_from_env(key: &str) -> Result<Path> {
+    let path = get_env(key)?;
+    match path.split_once(':') {
+        Some((value, len)) => Ok(Path {
+            value: value.parse()?,
+            len: len.parse()?,
+        }),
+        None => bail!("malformed path: {path}"),
+    }
+}
@@ -0,0 +1,433 @@
+pub use ipnis_modules_text_classification::labels::LabelSet;
+
+use ipis::{
+    async_trait::async_trait,
+    core::{
+        anyhow::{bail, Result},
+        ndarray,
+        ordered_float::OrderedFloat,
+    },
+};
+use ipnis_common::{
+    model::Model,
+    nlp::{
+        input::{TextInputs, TokenizeOptions, Tokenized},
+        tensor::StringTensorData,
+        tokenizer::{Encoding, Tokenizer},
+    },
+    onnxruntime::tensor::ndarray_tensor::NdArrayTensor,
+    tensor::Tensor,
+    Ipnis,
+};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum AggregationStrategy {
+    /// Returns each token as is, without grouping.
+    None,
+    /// Groups the adjacent tokens with the same entity.
+    #[default]
+    Simple,
+    /// Labels each word with its first token.
+    First,
+    /// Labels each word with the average scores of its tokens.
+    Average,
+    /// Labels each word with its most confident token.
+    Max,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenClassificationConfig {
+    pub aggregation_strategy: AggregationStrategy,
+    /// Drops the entities with these labels, e.g. the outside tag.
+    pub ignore_labels: Vec<String>,
+}
+
+impl Default for TokenClassificationConfig {
+    fn default() -> Self {
+        Self {
+            aggregation_strategy: Default::default(),
+            ignore_labels: vec!["O".into()],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outputs {
+    pub answers: Vec<Output>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Output {
+    pub text: String,
+    pub entities: Vec<Entity>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Entity {
+    /// The label, without the `B-` and `I-` prefixes unless the tokens are not grouped.
+    pub label: String,
+    pub score: OrderedFloat<f32>,
+    pub word: String,
+    /// The `start` and `end` character offsets in the text.
+    pub start: usize,
+    pub end: usize,
+}
+
+#[async_trait]
+pub trait IpnisTokenClassification: Ipnis {
+    /// Labels each token, e.g. the named entities or the part-of-speech tags.
+    async fn call_token_classification<T>(
+        &self,
+        model: &Model,
+        tokenizer: &T,
+        labels: &LabelSet,
+        config: &TokenClassificationConfig,
+        inputs: TextInputs,
+    ) -> Result<Outputs>
+    where
+        T: Tokenizer + ?Sized + Sync,
+    {
+        if inputs.text.is_empty() {
+            return Ok(Outputs {
+                answers: Default::default(),
+            });
+        }
+
+        let Tokenized {
+            inputs,
+            inputs_str,
+            encodings,
+            ..
+        } = inputs.tokenize_with(tokenizer, &TokenizeOptions::default().with_model(model))?;
+
+        let mut outputs = self.call(model, &inputs).await?;
+        if outputs.is_empty() {
+            let outputs = outputs.len();
+            bail!("unexpected outputs: Expected 1, Given {outputs}");
+        }
+
+        let logits: Tensor<StringTensorData> = Tensor::find(&mut outputs, "logits")?.try_into()?;
+
+        match &logits.data {
+            StringTensorData::F32Embedding(logits) => {
+                if logits.shape()[2] != labels.len() {
+                    let expected = labels.len();
+                    let given = logits.shape()[2];
+                    bail!("unexpected labels: Expected {expected}, Given {given}");
+                }
+
+                // convert logits into scores
+                let probs = logits.softmax(ndarray::Axis(2));
+
+                Ok(Outputs {
+                    answers: inputs_str
+                        .into_iter()
+                        .zip(&encodings)
+                        .zip(probs.outer_iter())
+                        .map(|((input, encoding), probs)| {
+                            let text: Vec<_> = input.text_1.chars().collect();
+                            let tokens = find_tokens(&text, encoding, probs);
+
+                            Output {
+                                entities: find_entities(&text, labels, config, tokens),
+                                text: input.text_1,
+                            }
+                        })
+                        .collect(),
+                })
+            }
+            _ => {
+                let logits = logits.shape();
+                bail!("unexpected StringTensorData: {logits:?}")
+            }
+        }
+    }
+}
+
+impl<T: Ipnis + ?Sized> IpnisTokenClassification for T {}
+
+/// A token or a word, with the scores of each label.
+struct Token {
+    start: usize,
+    end: usize,
+    probs: Vec<f32>,
+}
+
+impl Token {
+    fn label(&self) -> (usize, f32) {
+        self.probs
+            .iter()
+            .copied()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .unwrap_or_default()
+    }
+
+    fn into_entity(self, text: &[char], labels: &LabelSet) -> Entity {
+        let (label, score) = self.label();
+        Entity {
+            label: labels.names[label].clone(),
+            score: OrderedFloat(score),
+            word: text[self.start..self.end].iter().collect(),
+            start: self.start,
+            end: self.end,
+        }
+    }
+}
+
+/// Collects the tokens of the text, skipping the special tokens and the whitespaces.
+fn find_tokens(text: &[char], encoding: &Encoding, probs: ndarray::ArrayView2<f32>) -> Vec<Token> {
+    encoding
+        .offsets
+        .iter()
+        .zip(probs.outer_iter())
+        .filter_map(|(offset, probs)| {
+            let (mut start, end) = (*offset)?;
+            let end = end.min(text.len());
+            while start < end && text[start].is_whitespace() {
+                start += 1;
+            }
+
+            (start < end).then(|| Token {
+                start,
+                end,
+                probs: probs.to_vec(),
+            })
+        })
+        .collect()
+}
+
+/// Aggregates the tokens into the entities, dropping the ignored labels.
+fn find_entities(
+    text: &[char],
+    labels: &LabelSet,
+    config: &TokenClassificationConfig,
+    tokens: Vec<Token>,
+) -> Vec<Entity> {
+    let entities = match config.aggregation_strategy {
+        AggregationStrategy::None => tokens
+            .into_iter()
+            .map(|token| token.into_entity(text, labels))
+            .collect(),
+        AggregationStrategy::Simple => group_entities(text, labels, tokens),
+        strategy => {
+            let words = find_words(text, tokens)
+                .into_iter()
+                .map(|word| merge_word(strategy, word))
+                .collect();
+            group_entities(text, labels, words)
+        }
+    };
+
+    entities
+        .into_iter()
+        .filter(|entity| !config.ignore_labels.contains(&entity.label))
+        .collect()
+}
+
+/// Groups the sub-words, which are adjacent to each other without any punctuations.
+fn find_words(text: &[char], tokens: Vec<Token>) -> Vec<Vec<Token>> {
+    let mut words: Vec<Vec<Token>> = vec![];
+    for token in tokens {
+        match words.last_mut().and_then(|word| word.last()) {
+            Some(last)
+                if last.end == token.start
+                    && text[last.end - 1].is_alphanumeric()
+                    && text[token.start].is_alphanumeric() =>
+            {
+                words.last_mut().unwrap().push(token);
+            }
+            _ => words.push(vec![token]),
+        }
+    }
+    words
+}
+
+/// Labels the word with its tokens.
+fn merge_word(strategy: AggregationStrategy, word: Vec<Token>) -> Token {
+    let start = word.first().map(|token| token.start).unwrap_or_default();
+    let end = word.last().map(|token| token.end).unwrap_or_default();
+
+    let probs = match strategy {
+        AggregationStrategy::Average => {
+            let mut probs = vec![0.0; word[0].probs.len()];
+            for token in &word {
+                for (sum, prob) in probs.iter_mut().zip(&token.probs) {
+                    *sum += prob / word.len() as f32;
+                }
+            }
+            probs
+        }
+        AggregationStrategy::Max => word
+            .into_iter()
+            .max_by(|a, b| a.label().1.total_cmp(&b.label().1))
+            .map(|token| token.probs)
+            .unwrap_or_default(),
+        _ => word
+            .into_iter()
+            .next()
+            .map(|token| token.probs)
+            .unwrap_or_default(),
+    };
+    Token { start, end, probs }
+}
+
+/// Groups the adjacent tokens with the same entity, following the `B-` and `I-` prefixes.
+fn group_entities(text: &[char], labels: &LabelSet, tokens: Vec<Token>) -> Vec<Entity> {
+    let mut groups: Vec<(Entity, Vec<f32>)> = vec![];
+    for token in tokens {
+        let entity = token.into_entity(text, labels);
+        let (is_begin, label) = match entity.label.split_once('-') {
+            Some(("B", label)) => (true, label.to_string()),
+            Some(("I", label)) => (false, label.to_string()),
+            _ => (false, entity.label.clone()),
+        };
+
+        match groups.last_mut() {
+            Some((group, scores)) if !is_begin && group.label == label => {
+                group.word = text[group.start..entity.end].iter().collect();
+                group.end = entity.end;
+                scores.push(entity.score.0);
+            }
+            _ => groups.push((
+                Entity {
+                    label,
+                    score: entity.score,
+                    word: entity.word,
+                    start: entity.start,
+                    end: entity.end,
+                },
+                vec![entity.score.0],
+            )),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(mut group, scores)| {
+            group.score = OrderedFloat(scores.iter().sum::<f32>() / scores.len() as f32);
+            group
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "John Smith lives in Seoul";
+
+    fn labels() -> LabelSet {
+        LabelSet::new(["O", "B-PER", "I-PER", "B-LOC", "I-LOC", "MISC"])
+    }
+
+    /// Returns a token of the text, mostly labeled with the given index.
+    fn token(start: usize, end: usize, label: usize, score: f32) -> Token {
+        let mut probs = vec![(1.0 - score) / 5.0; 6];
+        probs[label] = score;
+        Token { start, end, probs }
+    }
+
+    fn text() -> Vec<char> {
+        TEXT.chars().collect()
+    }
+
+    #[test]
+    fn entities_are_grouped_by_the_prefixes() {
+        let text = text();
+        let tokens = vec![
+            token(0, 4, 1, 0.9),   // John: B-PER
+            token(5, 10, 2, 0.7),  // Smith: I-PER
+            token(11, 16, 0, 0.9), // lives: O
+            token(17, 19, 0, 0.9), // in: O
+            token(20, 23, 3, 0.8), // Seo: B-LOC
+            token(23, 25, 3, 0.6), // ul: B-LOC, which begins another entity
+        ];
+
+        let entities = group_entities(&text, &labels(), tokens);
+        let summary: Vec<_> = entities
+            .iter()
+            .map(|entity| (entity.label.as_str(), entity.word.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("PER", "John Smith"),
+                ("O", "lives in"),
+                ("LOC", "Seo"),
+                ("LOC", "ul")
+            ],
+        );
+        assert_eq!(entities[0].score, OrderedFloat((0.9 + 0.7) / 2.0));
+        assert_eq!((entities[0].start, entities[0].end), (0, 10));
+    }
+
+    #[test]
+    fn labels_without_prefixes_are_grouped_when_adjacent() {
+        let text = text();
+        let tokens = vec![
+            token(0, 4, 5, 0.9),   // John: MISC
+            token(5, 10, 5, 0.9),  // Smith: MISC
+            token(11, 16, 2, 0.9), // lives: I-PER, which continues nothing
+        ];
+
+        let entities = group_entities(&text, &labels(), tokens);
+        let summary: Vec<_> = entities
+            .iter()
+            .map(|entity| (entity.label.as_str(), entity.word.as_str()))
+            .collect();
+        assert_eq!(summary, [("MISC", "John Smith"), ("PER", "lives")]);
+    }
+
+    #[test]
+    fn sub_words_are_merged_into_words() {
+        let text = text();
+        let tokens = || {
+            vec![
+                token(0, 4, 1, 0.9),   // John
+                token(20, 23, 0, 0.6), // Seo
+                token(23, 25, 3, 0.8), // ul
+            ]
+        };
+
+        let words = find_words(&text, tokens());
+        let spans: Vec<Vec<_>> = words
+            .iter()
+            .map(|word| word.iter().map(|token| (token.start, token.end)).collect())
+            .collect();
+        assert_eq!(spans, [vec![(0, 4)], vec![(20, 23), (23, 25)]]);
+
+        let label = |strategy| {
+            let word = find_words(&text, tokens()).pop().unwrap();
+            let word = merge_word(strategy, word);
+            assert_eq!((word.start, word.end), (20, 25));
+            word.label()
+        };
+        // the first token is outside
+        assert_eq!(label(AggregationStrategy::First).0, 0);
+        // the last token is more confident
+        assert_eq!(label(AggregationStrategy::Max).0, 3);
+        // the scores are averaged over the tokens
+        let (index, score) = label(AggregationStrategy::Average);
+        assert_eq!(index, 3);
+        assert!((score - (0.08 + 0.8) / 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn ignored_labels_are_dropped() {
+        let text = text();
+        let tokens = vec![
+            token(0, 4, 1, 0.9),   // John: B-PER
+            token(11, 16, 0, 0.9), // lives: O
+            token(20, 25, 3, 0.9), // Seoul: B-LOC
+        ];
+
+        let config = TokenClassificationConfig {
+            ignore_labels: vec!["O".into(), "LOC".into()],
+            ..Default::default()
+        };
+        let entities = find_entities(&text, &labels(), &config, tokens);
+        let words: Vec<_> = entities.iter().map(|entity| entity.word.as_str()).collect();
+        assert_eq!(words, ["John"]);
+    }
+}
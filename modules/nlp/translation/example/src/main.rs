@@ -76,7 +76,9 @@ async fn main() -> Result<()> {
     };
 
     // perform the inference
-    let outputs = client.call_translation(&model, &tokenizer, inputs).await?;
+    let outputs = client
+        .call_translation(&model, &tokenizer, &Default::default(), inputs)
+        .await?;
 
     // show the result
     for (batch, output) in outputs.answers.into_iter().enumerate() {
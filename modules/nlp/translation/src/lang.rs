@@ -0,0 +1,238 @@
+use std::collections::BTreeMap;
+
+use ipis::core::{
+    anyhow::{bail, Result},
+    value::text::LanguageTag,
+};
+use ipnis_common::nlp::{lang::find_language, tokenizer::Tokenizer};
+
+/// Maps the language tags into the model-specific language tokens.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum LanguageTokens {
+    /// `__ko__`, or `>>ko.<<` on the `rust_tokenizers` vocabularies.
+    #[default]
+    M2M100,
+    /// `>>ko<<`, prepended to the source texts to select the target language.
+    /// Nothing is prepended if the vocabulary has no such token, e.g. on the bilingual models.
+    Marian,
+    /// `kor_Hang`.
+    Nllb,
+    /// Maps the language tags (e.g. `ko-KR`) or their primary subtags (e.g. `ko`) into the tokens,
+    /// placed as the M2M100 language tokens.
+    Custom(BTreeMap<String, String>),
+}
+
+impl LanguageTokens {
+    /// Returns the token prepended to the source text, if any.
+    pub fn source_token<T>(
+        &self,
+        tokenizer: &T,
+        source: &LanguageTag,
+        target: &LanguageTag,
+    ) -> Result<Option<i64>>
+    where
+        T: Tokenizer + ?Sized,
+    {
+        match self {
+            Self::Marian => Ok(self.find(tokenizer, target).ok()),
+            _ => self.find(tokenizer, source).map(Some),
+        }
+    }
+
+    /// Returns the tokens which the decoder starts with.
+    pub fn decoder_prefix<T>(&self, tokenizer: &T, target: &LanguageTag) -> Result<Vec<i64>>
+    where
+        T: Tokenizer + ?Sized,
+    {
+        let special_tokens = tokenizer.special_tokens();
+        match self {
+            Self::Marian => match special_tokens.pad {
+                Some(token_pad) => Ok(vec![token_pad]),
+                None => bail!("the tokenizer has no padding token"),
+            },
+            _ => match special_tokens.eos {
+                Some(token_eos) => Ok(vec![token_eos, self.find(tokenizer, target)?]),
+                None => bail!("the tokenizer has no EOS token"),
+            },
+        }
+    }
+
+    /// Finds the token of the language in the vocabulary.
+    pub fn find<T>(&self, tokenizer: &T, lang: &LanguageTag) -> Result<i64>
+    where
+        T: Tokenizer + ?Sized,
+    {
+        let candidates = self.candidates(lang);
+        match candidates
+            .iter()
+            .find_map(|token| tokenizer.token_to_id(token))
+        {
+            Some(id) => Ok(id),
+            None => bail!("unsupported language: {lang} (no such tokens: {candidates:?})"),
+        }
+    }
+
+    fn candidates(&self, lang: &LanguageTag) -> Vec<String> {
+        let tag = lang.to_string();
+        let mut subtags = tag.split(['-', '_']);
+        let language = subtags.next().unwrap_or_default().to_lowercase();
+        let script = subtags.find(|subtag| subtag.len() == 4);
+
+        match self {
+            Self::M2M100 if language.len() == 2 => {
+                vec![format!("__{language}__"), format!(">>{language}.<<")]
+            }
+            Self::M2M100 => vec![format!("__{language}__"), format!(">>{language}<<")],
+            Self::Marian => vec![format!(">>{language}<<")],
+            Self::Nllb => match find_nllb_language(&language) {
+                Some((code, default_script)) => {
+                    let script = match script {
+                        Some(script) => {
+                            let (head, tail) = script.split_at(1);
+                            head.to_uppercase() + &tail.to_lowercase()
+                        }
+                        None => default_script.to_string(),
+                    };
+                    vec![format!("{code}_{script}")]
+                }
+                None => vec![],
+            },
+            Self::Custom(tokens) => [&tag, &language]
+                .into_iter()
+                .filter_map(|key| tokens.get(key.as_str()))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+/// Returns the ISO 639-3 code and the default script of the language.
+fn find_nllb_language(language: &str) -> Option<(&'static str, &'static str)> {
+    find_language(language).map(|(_, code, script)| (code, script))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use ipnis_common::rust_tokenizers::{
+        tokenizer::BertTokenizer,
+        vocab::{BertVocab, Vocab},
+    };
+
+    use super::*;
+
+    /// Creates a tokenizer, whose token ids are the indices of the tokens.
+    fn tokenizer(tokens: &[&str]) -> BertTokenizer {
+        let specials = ["[UNK]", "<pad>", "</s>"];
+        let values: HashMap<_, _> = specials
+            .iter()
+            .chain(tokens)
+            .enumerate()
+            .map(|(id, token)| (token.to_string(), id as i64))
+            .collect();
+        let special_values: HashMap<_, _> = specials
+            .iter()
+            .map(|token| (token.to_string(), values[*token]))
+            .collect();
+
+        let swap = |values: &HashMap<String, i64>| {
+            values
+                .iter()
+                .map(|(token, id)| (*id, token.clone()))
+                .collect()
+        };
+        let vocab = BertVocab {
+            indices: swap(&values),
+            special_indices: swap(&special_values),
+            values,
+            special_values,
+            unknown_value: BertVocab::unknown_value(),
+        };
+        BertTokenizer::from_existing_vocab(vocab, false, false)
+    }
+
+    fn lang(tag: &str) -> LanguageTag {
+        tag.parse().unwrap()
+    }
+
+    #[test]
+    fn m2m100_tokens_are_found() {
+        let tokenizer = tokenizer(&["__ko__", ">>en.<<", ">>haw<<"]);
+        let tokens = LanguageTokens::M2M100;
+
+        assert_eq!(tokens.find(&tokenizer, &lang("ko-KR")).unwrap(), 3);
+        assert_eq!(tokens.find(&tokenizer, &lang("en")).unwrap(), 4);
+        assert_eq!(tokens.find(&tokenizer, &lang("haw")).unwrap(), 5);
+        assert!(tokens.find(&tokenizer, &lang("fr")).is_err());
+
+        assert_eq!(
+            tokens
+                .source_token(&tokenizer, &lang("ko"), &lang("en"))
+                .unwrap(),
+            Some(3),
+        );
+        assert_eq!(
+            tokens.decoder_prefix(&tokenizer, &lang("en")).unwrap(),
+            [2, 4],
+        );
+    }
+
+    #[test]
+    fn marian_tokens_select_the_target_language() {
+        let tokenizer = tokenizer(&[">>ko<<", ">>fr<<"]);
+        let tokens = LanguageTokens::Marian;
+
+        assert_eq!(
+            tokens
+                .source_token(&tokenizer, &lang("en"), &lang("ko-KR"))
+                .unwrap(),
+            Some(3),
+        );
+        // the bilingual models have no language tokens
+        assert_eq!(
+            tokens
+                .source_token(&tokenizer, &lang("en"), &lang("de"))
+                .unwrap(),
+            None,
+        );
+        assert_eq!(tokens.decoder_prefix(&tokenizer, &lang("ko")).unwrap(), [1],);
+    }
+
+    #[test]
+    fn nllb_tokens_have_the_scripts() {
+        let tokenizer = tokenizer(&["kor_Hang", "eng_Latn", "zho_Hans", "zho_Hant"]);
+        let tokens = LanguageTokens::Nllb;
+
+        assert_eq!(tokens.find(&tokenizer, &lang("ko-KR")).unwrap(), 3);
+        assert_eq!(tokens.find(&tokenizer, &lang("eng-Latn")).unwrap(), 4);
+        assert_eq!(tokens.find(&tokenizer, &lang("zh")).unwrap(), 5);
+        assert_eq!(tokens.find(&tokenizer, &lang("zh-hant-TW")).unwrap(), 6);
+        assert!(tokens.find(&tokenizer, &lang("ja")).is_err());
+        assert!(tokens.find(&tokenizer, &lang("xx")).is_err());
+    }
+
+    #[test]
+    fn custom_tokens_fall_back_to_the_primary_subtags() {
+        let tokenizer = tokenizer(&["<ko_kr>", "<en>"]);
+        let tokens = LanguageTokens::Custom(
+            [("ko-KR", "<ko_kr>"), ("en", "<en>")]
+                .into_iter()
+                .map(|(tag, token)| (tag.into(), token.into()))
+                .collect(),
+        );
+
+        assert_eq!(tokens.find(&tokenizer, &lang("ko-KR")).unwrap(), 3);
+        assert_eq!(tokens.find(&tokenizer, &lang("en-US")).unwrap(), 4);
+        assert!(tokens.find(&tokenizer, &lang("ko")).is_err());
+    }
+
+    #[test]
+    fn nllb_languages_are_found_by_both_codes() {
+        assert_eq!(find_nllb_language("en"), Some(("eng", "Latn")));
+        assert_eq!(find_nllb_language("eng"), Some(("eng", "Latn")));
+        assert_eq!(find_nllb_language("ko"), Some(("kor", "Hang")));
+        assert_eq!(find_nllb_language("fa"), Some(("pes", "Arab")));
+        assert_eq!(find_nllb_language("xx"), None);
+    }
+}
@@ -1,22 +1,36 @@
+mod lang;
+
+pub use self::lang::LanguageTokens;
+pub use ipnis_common::nlp::seq2seq::SplitModels;
+
 use ipis::{
     async_trait::async_trait,
-    core::{
-        anyhow::{bail, Result},
-        ndarray,
-        value::array::Array,
+    core::{anyhow::Result, ndarray},
+    futures::{
+        future,
+        stream::{self, BoxStream},
+        StreamExt,
     },
 };
 use ipnis_common::{
     model::Model,
     nlp::{
-        input::{Tokenized, TranslationInputs},
-        tensor::StringTensorData,
+        generation::{GenerationConfig, Hypothesis},
+        input::{GenericInput, Tokenized, TranslationInputs},
+        seq2seq::{Seq2Seq, Seq2SeqModels},
+        session::{stream_generation, to_rows, GenerationRequest},
+        stream::TextDelta,
+        tokenizer::Tokenizer,
     },
-    rust_tokenizers::{tokenizer::Tokenizer, vocab::Vocab},
-    tensor::{Tensor, TensorData, ToTensor},
     Ipnis,
 };
 
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TranslationConfig {
+    pub languages: LanguageTokens,
+    pub generation: GenerationConfig,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Outputs {
     pub answers: Vec<Output>,
@@ -30,178 +44,212 @@ pub struct Output {
 
 #[async_trait]
 pub trait IpnisTranslation: Ipnis {
-    async fn call_translation<T, V>(
+    async fn call_translation<T>(
         &self,
         model: &Model,
         tokenizer: &T,
+        config: &TranslationConfig,
         inputs: TranslationInputs,
     ) -> Result<Outputs>
     where
-        T: Tokenizer<V> + Sync,
-        V: Vocab,
+        T: Tokenizer + ?Sized + Sync,
     {
-        if inputs.context.is_empty() {
-            return Ok(Outputs {
-                answers: Default::default(),
-            });
-        }
-
-        let num_inputs = inputs.context.len();
-        let Tokenized {
-            mut input_ids,
-            mut inputs,
-            inputs_str,
-            ..
-        } = inputs.tokenize_without_tensors(tokenizer)?;
-
-        // acquire special tokens
-        let token_eos = tokenizer.vocab().token_to_id("</s>");
-        let token_pad = tokenizer.vocab().token_to_id("<pad>");
-        // let token_unk = tokenizer.vocab().token_to_id("<unk>");
-
-        // acquire language tokens
-        // TODO: acquire from inputs, not hardcoded
-        let lang_src = tokenizer.vocab().token_to_id(">>ko.<<");
-        let lang_tgt = tokenizer.vocab().token_to_id(">>en.<<");
-
-        // add language tags on inputs
-        {
-            input_ids = ndarray::concatenate![
-                ndarray::Axis(1),
-                ndarray::Array::from_elem((num_inputs, 1), lang_src).view(),
-                input_ids.view(),
-            ];
-            let attention_mask = ndarray::Array::ones(input_ids.dim());
-
-            inputs.insert(
-                "input_ids".into(),
-                Box::new(TensorData::from(StringTensorData::I64(Array(
-                    input_ids.clone().into(),
-                )))) as Box<dyn ToTensor + Send + Sync>,
-            );
-            inputs.insert(
-                "attention_mask".into(),
-                Box::new(TensorData::from(StringTensorData::I64(Array(
-                    attention_mask.into(),
-                )))) as Box<dyn ToTensor + Send + Sync>,
-            );
-        }
-
-        // decoded inputs
-        let mut decoder_input_ids: Vec<_> = (0..num_inputs)
-            .map(|_| vec![token_eos, lang_tgt, token_pad])
-            .collect();
-
-        loop {
-            // filter finished works
-            let indices: Vec<_> = decoder_input_ids
-                .iter()
-                .enumerate()
-                .filter(|(_, e)| e[e.len() - 2] != token_eos)
-                .map(|(idx, _)| idx)
-                .collect();
-            if indices.is_empty() {
-                break;
-            }
-
-            {
-                let decoder_input_ids = ndarray::stack(
-                    ndarray::Axis(0),
-                    indices
-                        .iter()
-                        .map(|idx| &decoder_input_ids[*idx])
-                        .map(|input| ndarray::ArrayView::from_shape((input.len(),), input).unwrap())
-                        .collect::<Vec<_>>()
-                        .as_slice(),
-                )?;
-                let decoder_attention_mask = ndarray::Array::ones(decoder_input_ids.dim());
-
-                inputs.insert(
-                    "decoder_input_ids".into(),
-                    Box::new(TensorData::from(StringTensorData::I64(Array(
-                        decoder_input_ids.into(),
-                    )))) as Box<dyn ToTensor + Send + Sync>,
-                );
-                inputs.insert(
-                    "decoder_attention_mask".into(),
-                    Box::new(TensorData::from(StringTensorData::I64(Array(
-                        decoder_attention_mask.into(),
-                    )))) as Box<dyn ToTensor + Send + Sync>,
-                );
-            }
-
-            let mut outputs = self.call(model, &inputs).await?;
-            if outputs.is_empty() {
-                let outputs = outputs.len();
-                bail!("unexpected outputs: Expected 1, Given {outputs}");
-            }
-
-            let logits: Tensor<StringTensorData> =
-                Tensor::find(&mut outputs, "logits")?.try_into()?;
-
-            match &logits.data {
-                StringTensorData::F32Embedding(logits) => {
-                    let answers = find_answer(logits);
-                    for (idx, answer) in indices.iter().copied().zip(answers) {
-                        *decoder_input_ids[idx].last_mut().unwrap() = answer.try_into()?;
-                        decoder_input_ids[idx].push(token_pad);
-                    }
-                }
-                _ => {
-                    let logits = logits.shape();
-                    bail!("unexpected StringTensorData: {logits:?}")
-                }
-            }
-        }
-
-        Ok(Outputs {
-            answers: inputs_str
-                .into_iter()
-                .zip(decoder_input_ids)
-                .map(|(input, answer)| {
-                    Ok(Output {
-                        query: input.text_1,
-                        answer: tokenizer.decode(&answer, true, true).trim().to_string(),
-                    })
-                })
-                .collect::<Result<_>>()?,
+        let encoded = encode(tokenizer, config, inputs)?;
+        let session = Seq2Seq::new(
+            self,
+            Seq2SeqModels::Single(model),
+            &config.generation,
+            encoded.input_ids,
+            encoded.attention_mask,
+            encoded.prefixes,
+            &tokenizer.special_tokens(),
+        )?;
+        decode(tokenizer, encoded.inputs_str, session.run().await?)
+    }
+
+    /// Translates with the split exports, running the encoder only once
+    /// and feeding only the new tokens into the decoder.
+    async fn call_translation_with_cache<T>(
+        &self,
+        models: &SplitModels<'_>,
+        tokenizer: &T,
+        config: &TranslationConfig,
+        inputs: TranslationInputs,
+    ) -> Result<Outputs>
+    where
+        T: Tokenizer + ?Sized + Sync,
+    {
+        let encoded = encode(tokenizer, config, inputs)?;
+        let session = Seq2Seq::new(
+            self,
+            Seq2SeqModels::Split(*models),
+            &config.generation,
+            encoded.input_ids,
+            encoded.attention_mask,
+            encoded.prefixes,
+            &tokenizer.special_tokens(),
+        )?;
+        decode(tokenizer, encoded.inputs_str, session.run().await?)
+    }
+
+    /// Translates like [`call_translation`](Self::call_translation),
+    /// yielding the newly translated text of each input step by step.
+    ///
+    /// The translation is cancelled when the stream is dropped.
+    fn call_translation_stream<'a, T>(
+        &'a self,
+        model: &'a Model,
+        tokenizer: &'a T,
+        config: &'a TranslationConfig,
+        inputs: TranslationInputs,
+    ) -> BoxStream<'a, Result<Vec<TextDelta>>>
+    where
+        Self: Sync,
+        T: Tokenizer + ?Sized + Sync,
+    {
+        let encoded = match encode(tokenizer, config, inputs) {
+            Ok(encoded) => encoded,
+            Err(error) => return stream::once(future::ready(Err(error))).boxed(),
+        };
+        let request = GenerationRequest {
+            models: Seq2SeqModels::Single(model).into(),
+            config: config.generation.clone(),
+            input_ids: to_rows(&encoded.input_ids),
+            attention_mask: to_rows(&encoded.attention_mask),
+            prefixes: encoded.prefixes,
+            special_tokens: tokenizer.special_tokens(),
+        };
+        stream_generation(self, request, move |tokens, is_finished| {
+            let text = tokenizer.decode(tokens, true).ok()?;
+            let text = match is_finished {
+                true => text.trim(),
+                false => text.trim_start(),
+            };
+            Some((text.to_string(), false))
+        })
+    }
+
+    /// Translates like [`call_translation_with_cache`](Self::call_translation_with_cache),
+    /// yielding the newly translated text of each input step by step.
+    ///
+    /// The translation is cancelled when the stream is dropped.
+    fn call_translation_with_cache_stream<'a, T>(
+        &'a self,
+        models: &SplitModels<'a>,
+        tokenizer: &'a T,
+        config: &'a TranslationConfig,
+        inputs: TranslationInputs,
+    ) -> BoxStream<'a, Result<Vec<TextDelta>>>
+    where
+        Self: Sync,
+        T: Tokenizer + ?Sized + Sync,
+    {
+        let encoded = match encode(tokenizer, config, inputs) {
+            Ok(encoded) => encoded,
+            Err(error) => return stream::once(future::ready(Err(error))).boxed(),
+        };
+        let request = GenerationRequest {
+            models: Seq2SeqModels::Split(*models).into(),
+            config: config.generation.clone(),
+            input_ids: to_rows(&encoded.input_ids),
+            attention_mask: to_rows(&encoded.attention_mask),
+            prefixes: encoded.prefixes,
+            special_tokens: tokenizer.special_tokens(),
+        };
+        stream_generation(self, request, move |tokens, is_finished| {
+            let text = tokenizer.decode(tokens, true).ok()?;
+            let text = match is_finished {
+                true => text.trim(),
+                false => text.trim_start(),
+            };
+            Some((text.to_string(), false))
         })
     }
 }
 
 impl<T: Ipnis + ?Sized> IpnisTranslation for T {}
 
-fn argmax<S>(tensor: &ndarray::ArrayBase<S, ndarray::Ix3>) -> ndarray::Array2<usize>
+/// The inputs of the encoder and the decoder.
+struct Encoded {
+    input_ids: ndarray::Array2<i64>,
+    attention_mask: ndarray::Array2<i64>,
+    prefixes: Vec<Vec<i64>>,
+    inputs_str: Vec<GenericInput>,
+}
+
+/// Tokenizes the inputs with the language tokens, preparing the decoding.
+fn encode<T>(
+    tokenizer: &T,
+    config: &TranslationConfig,
+    inputs: TranslationInputs,
+) -> Result<Encoded>
 where
-    S: ndarray::Data,
-    S::Elem: PartialOrd,
+    T: Tokenizer + ?Sized,
 {
-    let shape = tensor.shape();
-
-    tensor
-        .rows()
-        .into_iter()
-        .map(|row| {
-            row.into_iter()
-                .enumerate()
-                .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
-                .unwrap()
-                .0
+    // acquire language tokens
+    let target = inputs.target.clone();
+    let lang_src = inputs
+        .context
+        .iter()
+        .map(|text| {
+            config
+                .languages
+                .source_token(tokenizer, &text.lang, &target)
         })
-        .collect::<ndarray::Array1<_>>()
-        .into_shape((shape[0], shape[1]))
-        .unwrap()
+        .collect::<Result<Option<Vec<_>>>>()?;
+    let lang_tgt = config.languages.decoder_prefix(tokenizer, &target)?;
+
+    let num_inputs = inputs.context.len();
+    let Tokenized {
+        mut input_ids,
+        mut attention_mask,
+        inputs_str,
+        ..
+    } = inputs.tokenize_without_tensors(tokenizer)?;
+
+    // add language tags on inputs
+    if let Some(lang_src) = lang_src {
+        input_ids = ndarray::concatenate![
+            ndarray::Axis(1),
+            ndarray::Array::from_shape_vec((num_inputs, 1), lang_src)?.view(),
+            input_ids.view(),
+        ];
+        attention_mask = ndarray::concatenate![
+            ndarray::Axis(1),
+            ndarray::Array::ones((num_inputs, 1)).view(),
+            attention_mask.view(),
+        ];
+    }
+
+    Ok(Encoded {
+        input_ids,
+        attention_mask,
+        prefixes: vec![lang_tgt; num_inputs],
+        inputs_str,
+    })
 }
 
-fn find_answer<S>(tensor: &ndarray::ArrayBase<S, ndarray::Ix3>) -> ndarray::Array1<usize>
+fn decode<T>(
+    tokenizer: &T,
+    inputs_str: Vec<GenericInput>,
+    hypotheses: Vec<Hypothesis>,
+) -> Result<Outputs>
 where
-    S: ndarray::Data,
-    S::Elem: PartialOrd,
+    T: Tokenizer + ?Sized,
 {
-    let shape = tensor.shape();
-
-    argmax(tensor).index_axis_move(
-        ndarray::Axis(1),
-        shape[1] - 2, // skip the last EOS token
-    )
+    Ok(Outputs {
+        answers: inputs_str
+            .into_iter()
+            .zip(hypotheses)
+            .map(|(input, hypothesis)| {
+                Ok(Output {
+                    query: input.text_1,
+                    answer: tokenizer
+                        .decode(hypothesis.generated(), true)?
+                        .trim()
+                        .to_string(),
+                })
+            })
+            .collect::<Result<_>>()?,
+    })
 }
@@ -1,5 +1,8 @@
 use ipis::{
-    core::anyhow::{anyhow, Result},
+    core::{
+        anyhow::{anyhow, Result},
+        value::text::LanguageTag,
+    },
     env::Infer,
     path::Path,
     tokio,
@@ -8,8 +11,7 @@ use ipnis_api::{
     client::IpnisClientInner,
     common::{nlp::input::SCInputs, rust_tokenizers::tokenizer::RobertaTokenizer, Ipnis},
 };
-use ipnis_modules_text_classification::labels::Labels;
-use ipnis_modules_zero_shot_classification::IpnisZeroShotClassification;
+use ipnis_modules_zero_shot_classification::{IpnisZeroShotClassification, ZeroShotConfig};
 use ipsis_api::client::IpsisClient;
 use ipsis_modules_gdown::IpsisGdown;
 use ipsis_modules_web::IpsisWeb;
@@ -68,20 +70,25 @@ async fn main() -> Result<()> {
         ],
     };
 
-    // define and filter the logits
-    let mut logits = Labels::default();
-    logits.neutral.take();
+    // define the hypothesis template and the mode
+    let config = ZeroShotConfig {
+        multi_label: false,
+        ..ZeroShotConfig::for_language(&LanguageTag::new_en_us())
+    };
 
     // perform the inference
     let outputs = client
-        .call_zero_shot_classification(&model, &tokenizer, inputs, logits)
+        .call_zero_shot_classification(&model, &tokenizer, &config, inputs)
         .await?;
 
     // show the result
     for (batch, output) in outputs.answers.into_iter().enumerate() {
         let batch = batch + 1;
-        let prob = output.prob;
-        println!("Probability for data {batch}th = {prob}");
+        for label in output.labels {
+            let name = label.label;
+            let score = label.score;
+            println!("Score of {name:?} for data {batch}th = {score}");
+        }
     }
     Ok(())
 }
@@ -1,16 +1,62 @@
+pub mod template;
+
 use ipis::{
     async_trait::async_trait,
     core::{
         anyhow::{bail, Result},
+        ndarray,
         ordered_float::OrderedFloat,
+        value::text::LanguageTag,
     },
 };
 use ipnis_common::{
     model::Model,
-    nlp::input::SCInputs,
-    rust_tokenizers::{tokenizer::Tokenizer, vocab::Vocab},
+    nlp::{
+        input::{GenericInput, SCInputs},
+        tokenizer::Tokenizer,
+    },
+    onnxruntime::tensor::ndarray_tensor::NdArrayTensor,
 };
-use ipnis_modules_text_classification::{labels::Labels, IpnisTextClassification};
+use ipnis_modules_text_classification::{labels::Labels, IpnisTextClassification, LabelScore};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ZeroShotConfig {
+    /// Converts each candidate label into a hypothesis, replacing `{}` with the label.
+    pub hypothesis_template: String,
+    /// Scores each label independently, rather than normalizing the scores across the labels.
+    pub multi_label: bool,
+    /// The NLI label indices; the `neutral` label is ignored.
+    pub labels: Labels,
+    /// The number of the context-hypothesis pairs of each inference.
+    pub batch_size: usize,
+}
+
+impl Default for ZeroShotConfig {
+    fn default() -> Self {
+        Self {
+            hypothesis_template: template::DEFAULT_TEMPLATE.into(),
+            multi_label: false,
+            labels: Default::default(),
+            batch_size: 8,
+        }
+    }
+}
+
+impl ZeroShotConfig {
+    /// Uses the built-in hypothesis template of the language, falling back to the English one.
+    pub fn for_language(lang: &LanguageTag) -> Self {
+        Self {
+            hypothesis_template: template::find_template(lang)
+                .unwrap_or(template::DEFAULT_TEMPLATE)
+                .into(),
+            ..Default::default()
+        }
+    }
+
+    fn hypothesis(&self, label: &str) -> String {
+        self.hypothesis_template.replace("{}", label)
+    }
+}
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Outputs {
@@ -19,66 +65,260 @@ pub struct Outputs {
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Output {
-    pub query: String,
     pub context: String,
-    pub prob: OrderedFloat<f32>,
+    /// The candidate labels, sorted by their scores in descending order.
+    pub labels: Vec<LabelScore>,
 }
 
 #[async_trait]
 pub trait IpnisZeroShotClassification: IpnisTextClassification {
-    async fn call_zero_shot_classification<T, V>(
+    /// Classifies each context into the candidate labels given as the queries.
+    async fn call_zero_shot_classification<T>(
         &self,
         model: &Model,
         tokenizer: &T,
+        config: &ZeroShotConfig,
         inputs: SCInputs,
-        mut labels: Labels,
     ) -> Result<Outputs>
     where
-        T: Tokenizer<V> + Sync,
-        V: Vocab,
+        T: Tokenizer + ?Sized + Sync,
     {
         // validate labels
-        if labels.contradiction.is_none() {
-            bail!("'contradiction' label is required");
+        let (label_contradiction, label_entailment) =
+            match (config.labels.contradiction, config.labels.entailment) {
+                (Some(contradiction), Some(entailment)) => (contradiction, entailment),
+                (None, _) => bail!("'contradiction' label is required"),
+                (_, None) => bail!("'entailment' label is required"),
+            };
+        if !config.hypothesis_template.contains("{}") {
+            let template = &config.hypothesis_template;
+            bail!("no placeholder '{{}}' in the hypothesis template: {template:?}");
         }
-        if labels.entailment.is_none() {
-            bail!("'entailment' label is required");
+
+        if inputs.query.is_empty() || inputs.context.is_empty() {
+            return Ok(Outputs {
+                answers: inputs
+                    .context
+                    .into_iter()
+                    .map(|context| Output {
+                        context,
+                        labels: Default::default(),
+                    })
+                    .collect(),
+            });
+        }
+
+        // convert the candidate labels into the hypotheses
+        let candidates = inputs.query;
+        let contexts = inputs.context;
+        let inputs: Vec<GenericInput> = SCInputs {
+            query: candidates
+                .iter()
+                .map(|label| config.hypothesis(label))
+                .collect(),
+            context: contexts.clone(),
+        }
+        .into();
+
+        // perform the sequence classification
+        let mut logits = vec![];
+        for batch in inputs.chunks(config.batch_size.max(1)) {
+            let (_, batch_logits) = self
+                .call_text_classification_logits(model, tokenizer, batch.to_vec())
+                .await?;
+            logits.push(batch_logits);
         }
+        let logits = ndarray::concatenate(
+            ndarray::Axis(0),
+            &logits
+                .iter()
+                .map(|logits| logits.view())
+                .collect::<Vec<_>>(),
+        )?;
+
+        collect_scores(
+            config,
+            (label_contradiction, label_entailment),
+            candidates,
+            contexts,
+            logits,
+        )
+    }
+}
+
+impl<T: IpnisTextClassification + ?Sized> IpnisZeroShotClassification for T {}
+
+/// Converts the logits of the context-hypothesis pairs into the scores of the candidate labels.
+fn collect_scores(
+    config: &ZeroShotConfig,
+    (label_contradiction, label_entailment): (usize, usize),
+    candidates: Vec<String>,
+    contexts: Vec<String>,
+    logits: ndarray::Array2<f32>,
+) -> Result<Outputs> {
+    if logits.ncols() <= label_contradiction.max(label_entailment) {
+        let expected = label_contradiction.max(label_entailment) + 1;
+        let given = logits.ncols();
+        bail!("unexpected labels: Expected at least {expected}, Given {given}");
+    }
 
-        // ignore `neutral` label
-        labels.neutral.take();
+    // the pairs are ordered by the candidate labels, and then by the contexts
+    let num_classes = logits.ncols();
+    let logits = logits.into_shape((candidates.len(), contexts.len(), num_classes))?;
+    let scores = match config.multi_label {
+        // entailment vs. contradiction of each label
+        true => logits
+            .select(ndarray::Axis(2), &[label_contradiction, label_entailment])
+            .softmax(ndarray::Axis(2))
+            .index_axis_move(ndarray::Axis(2), 1),
+        // entailment across the labels
+        false => logits
+            .index_axis(ndarray::Axis(2), label_entailment)
+            .softmax(ndarray::Axis(0)),
+    };
 
-        // convert queries to `sequence_classification` format
-        let query: Vec<_> = inputs
-            .query
+    Ok(Outputs {
+        answers: contexts
             .into_iter()
-            .map(|category| format!("This example is {category}."))
-            .collect();
+            .zip(scores.columns())
+            .map(|(context, scores)| {
+                let mut labels: Vec<_> = candidates
+                    .iter()
+                    .zip(scores)
+                    .map(|(label, &score)| LabelScore {
+                        label: label.clone(),
+                        score: OrderedFloat(score),
+                    })
+                    .collect();
+                labels.sort_by_key(|label| ::core::cmp::Reverse(label.score));
 
-        // rebuild the inputs
-        let inputs = SCInputs {
-            query,
-            context: inputs.context,
+                Output { context, labels }
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The indices of the contradiction and entailment logits.
+    const LABELS: (usize, usize) = (0, 1);
+
+    fn candidates() -> Vec<String> {
+        vec!["sports".into(), "politics".into(), "science".into()]
+    }
+
+    fn contexts() -> Vec<String> {
+        vec![
+            "The match ended in a draw.".into(),
+            "The bill passed.".into(),
+        ]
+    }
+
+    /// Creates the logits of the pairs, ordered by the candidates and then by the contexts.
+    fn logits(entailment: [[f32; 2]; 3], contradiction: [[f32; 2]; 3]) -> ndarray::Array2<f32> {
+        let mut logits = ndarray::Array2::zeros((6, 3));
+        for candidate in 0..3 {
+            for context in 0..2 {
+                let row = candidate * 2 + context;
+                logits[(row, LABELS.0)] = contradiction[candidate][context];
+                logits[(row, LABELS.1)] = entailment[candidate][context];
+            }
+        }
+        logits
+    }
+
+    fn scores(output: &Output) -> Vec<(&str, f32)> {
+        output
+            .labels
+            .iter()
+            .map(|label| (label.label.as_str(), label.score.0))
+            .collect()
+    }
+
+    #[test]
+    fn pairs_are_grouped_by_the_contexts() {
+        let logits = logits([[3.0, 0.0], [0.0, 3.0], [1.0, 1.0]], [[0.0; 2]; 3]);
+        let outputs = collect_scores(
+            &Default::default(),
+            LABELS,
+            candidates(),
+            contexts(),
+            logits,
+        )
+        .unwrap();
+
+        assert_eq!(outputs.answers.len(), 2);
+        assert_eq!(outputs.answers[0].context, contexts()[0]);
+        assert_eq!(outputs.answers[0].labels[0].label, "sports");
+        assert_eq!(outputs.answers[1].context, contexts()[1]);
+        assert_eq!(outputs.answers[1].labels[0].label, "politics");
+        assert_eq!(outputs.answers[1].labels[1].label, "science");
+    }
+
+    #[test]
+    fn single_label_is_normalized_across_the_candidates() {
+        let logits = logits([[2.0, 1.0], [2.0, 1.0], [0.0, 1.0]], [[5.0; 2]; 3]);
+        let outputs = collect_scores(
+            &Default::default(),
+            LABELS,
+            candidates(),
+            contexts(),
+            logits,
+        )
+        .unwrap();
+
+        for output in &outputs.answers {
+            let sum: f32 = scores(output).iter().map(|(_, score)| score).sum();
+            assert!((sum - 1.0).abs() < 1e-6);
+        }
+        // the contradictions are ignored
+        for (_, score) in scores(&outputs.answers[1]) {
+            assert!((score - 1.0 / 3.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn multi_label_compares_entailment_with_contradiction() {
+        let config = ZeroShotConfig {
+            multi_label: true,
+            ..Default::default()
         };
+        let logits = logits(
+            [[2.0, 0.0], [2.0, 0.0], [0.0, 0.0]],
+            [[0.0, 0.0], [2.0, 0.0], [2.0, 0.0]],
+        );
+        let outputs = collect_scores(&config, LABELS, candidates(), contexts(), logits).unwrap();
 
-        // perform the sequence classification
-        let outputs = self
-            .call_text_classification_raw(model, tokenizer, inputs, labels)
-            .await?;
-
-        // collect probabilities
-        Ok(Outputs {
-            answers: outputs
-                .answers
-                .into_iter()
-                .map(|output| Output {
-                    query: output.query,
-                    context: output.context,
-                    prob: output.prob_entailment.unwrap(),
-                })
-                .collect(),
-        })
+        let sigmoid = |x: f32| 1.0 / (1.0 + (-x).exp());
+        let expected = [
+            ("sports", sigmoid(2.0)),
+            ("politics", 0.5),
+            ("science", sigmoid(-2.0)),
+        ];
+        for ((label, score), (expected_label, expected_score)) in
+            scores(&outputs.answers[0]).into_iter().zip(expected)
+        {
+            assert_eq!(label, expected_label);
+            assert!((score - expected_score).abs() < 1e-6);
+        }
+        // each label is scored independently
+        for (_, score) in scores(&outputs.answers[1]) {
+            assert!((score - 0.5).abs() < 1e-6);
+        }
     }
-}
 
-impl<T: IpnisTextClassification + ?Sized> IpnisZeroShotClassification for T {}
+    #[test]
+    fn missing_labels_are_rejected() {
+        let logits = ndarray::Array2::zeros((6, 1));
+        assert!(collect_scores(
+            &Default::default(),
+            LABELS,
+            candidates(),
+            contexts(),
+            logits
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,49 @@
+use ipis::core::value::text::LanguageTag;
+
+/// The hypothesis template of the English, used when the language is unknown.
+pub const DEFAULT_TEMPLATE: &str = "This example is {}.";
+
+/// Returns the built-in hypothesis template of the language, matching its primary subtag.
+pub fn find_template(lang: &LanguageTag) -> Option<&'static str> {
+    let tag = lang.to_string();
+    let language = tag.split(['-', '_']).next().unwrap_or_default();
+
+    match language.to_lowercase().as_str() {
+        "de" => Some("Dieses Beispiel ist {}."),
+        "en" => Some(DEFAULT_TEMPLATE),
+        "es" => Some("Este ejemplo es {}."),
+        "fr" => Some("Cet exemple est {}."),
+        "ja" => Some("この例は{}です。"),
+        "ko" => Some("이 예시는 {}입니다."),
+        "zh" => Some("这个例子是{}。"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ZeroShotConfig;
+
+    fn lang(tag: &str) -> LanguageTag {
+        tag.parse().unwrap()
+    }
+
+    #[test]
+    fn templates_match_the_primary_subtags() {
+        assert_eq!(find_template(&lang("ko-KR")), Some("이 예시는 {}입니다."));
+        assert_eq!(find_template(&lang("FR_ca")), Some("Cet exemple est {}."));
+        assert_eq!(find_template(&lang("en-US")), Some(DEFAULT_TEMPLATE));
+        assert_eq!(find_template(&lang("xx")), None);
+    }
+
+    #[test]
+    fn unknown_languages_fall_back_to_the_english() {
+        let config = ZeroShotConfig::for_language(&lang("xx-YY"));
+        assert_eq!(config.hypothesis_template, DEFAULT_TEMPLATE);
+        assert_eq!(config.hypothesis("sports"), "This example is sports.");
+
+        let config = ZeroShotConfig::for_language(&lang("de"));
+        assert_eq!(config.hypothesis("Sport"), "Dieses Beispiel ist Sport.");
+    }
+}
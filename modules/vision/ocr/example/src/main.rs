@@ -0,0 +1,71 @@
+use std::env;
+
+use ipis::{
+    core::anyhow::{anyhow, bail, Result},
+    env::Infer,
+    path::Path,
+    tokio,
+};
+use ipnis_api::{
+    client::IpnisClientInner,
+    common::{image::io::Reader as ImageReader, Ipnis},
+};
+use ipnis_modules_ocr::{recognition::Dictionary, IpnisOcr, OcrConfig};
+use ipsis_api::client::IpsisClient;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // create a client
+    let client = IpnisClientInner::<IpsisClient>::try_infer().await?;
+
+    // load models (PaddleOCR text detection & recognition)
+    // NOTE: you can export them manually with: "https://github.com/PaddlePaddle/Paddle2ONNX"
+    let detection = client
+        .load_model(&get_path_from_env("IPNIS_OCR_DETECTION_MODEL")?)
+        .await?;
+    let recognition = client
+        .load_model(&get_path_from_env("IPNIS_OCR_RECOGNITION_MODEL")?)
+        .await?;
+
+    // load a character dictionary
+    let dictionary = Dictionary::from_file(get_env("IPNIS_OCR_DICTIONARY")?)?.with_space();
+
+    // make a sample inputs
+    let images = vec![ImageReader::open(get_env("IPNIS_OCR_IMAGE")?)?.decode()?];
+
+    // perform the inference
+    let config = OcrConfig::default();
+    let outputs = client
+        .call_ocr(&detection, &recognition, &dictionary, &config, &images)
+        .await?;
+
+    // show the result
+    for (batch, output) in outputs.answers.into_iter().enumerate() {
+        let batch = batch + 1;
+        for region in output.regions {
+            let (x, y, width, height) = region.text_box.bounding_box();
+            let text = &region.text;
+            let score = region.score;
+            println!(
+                "Text for image {batch}th at ({x}, {y}, {width}, {height}) = {text} ({score})"
+            );
+        }
+    }
+    Ok(())
+}
+
+fn get_env(key: &str) -> Result<String> {
+    env::var(key).map_err(|_| anyhow!("environment variable is not set: {key}"))
+}
+
+/// Parses a stored path, formatted as `{cid}:{length}`.
+fn get_path_from_env(key: &str) -> Result<Path> {
+    let path = get_env(key)?;
+    match path.split_once(':') {
+        Some((value, len)) => Ok(Path {
+            value: value.parse()?,
+            len: len.parse()?,
+        }),
+        None => bail!("malformed path: {path}"),
+    }
+}
@@ -0,0 +1,493 @@
+use std::collections::VecDeque;
+
+use ipis::core::{ndarray, ordered_float::OrderedFloat};
+use ipnis_common::image::{DynamicImage, Rgb, RgbImage};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DetectionConfig {
+    /// Limits the longest side of the image when the model accepts dynamic sizes.
+    pub limit_side_len: usize,
+    /// Binarizes the probability map with this threshold.
+    pub threshold: f32,
+    /// Drops the regions whose mean probability is lower than this threshold.
+    pub box_threshold: f32,
+    /// Expands the detected regions, as the shrunk text kernels are detected.
+    pub unclip_ratio: f32,
+    /// Drops the regions whose shorter side is smaller than this size.
+    pub min_size: f32,
+    pub max_candidates: usize,
+    pub mean: [f32; 3],
+    pub std: [f32; 3],
+}
+
+impl Default for DetectionConfig {
+    fn default() -> Self {
+        Self {
+            limit_side_len: 960,
+            threshold: 0.3,
+            box_threshold: 0.6,
+            unclip_ratio: 1.5,
+            min_size: 3.0,
+            max_candidates: 1000,
+            mean: [0.485, 0.456, 0.406],
+            std: [0.229, 0.224, 0.225],
+        }
+    }
+}
+
+impl DetectionConfig {
+    /// Returns the `(height, width)` to resize the image into, as multiples of 32.
+    pub(crate) fn fit(&self, height: usize, width: usize) -> (usize, usize) {
+        let ratio = match height.max(width) {
+            side if side > self.limit_side_len => self.limit_side_len as f32 / side as f32,
+            _ => 1.0,
+        };
+
+        let fit = |side: usize| ((((side as f32 * ratio) / 32.0).round() as usize) * 32).max(32);
+        (fit(height), fit(width))
+    }
+
+    pub(crate) fn find_text_boxes(
+        &self,
+        map: ndarray::ArrayView2<f32>,
+        width: u32,
+        height: u32,
+    ) -> Vec<TextBox> {
+        let (map_height, map_width) = map.dim();
+        let scale_x = width as f32 / map_width as f32;
+        let scale_y = height as f32 / map_height as f32;
+
+        let mut text_boxes: Vec<_> = find_components(map, self.threshold)
+            .into_iter()
+            .filter_map(|pixels| {
+                // drop the uncertain regions
+                let score =
+                    pixels.iter().map(|&(y, x)| map[(y, x)]).sum::<f32>() / pixels.len() as f32;
+                if score < self.box_threshold {
+                    return None;
+                }
+
+                // find the minimum area rectangle
+                let points: Vec<_> = pixels
+                    .iter()
+                    .flat_map(|&(y, x)| {
+                        let (x, y) = (x as f32, y as f32);
+                        [(x, y), (x + 1.0, y), (x, y + 1.0), (x + 1.0, y + 1.0)]
+                    })
+                    .collect();
+                let rect = Rect::min_area(&convex_hull(points))?;
+                if rect.short_side() < self.min_size {
+                    return None;
+                }
+
+                // expand the shrunk region
+                let rect = rect.unclip(self.unclip_ratio);
+                if rect.short_side() < self.min_size + 2.0 {
+                    return None;
+                }
+
+                // rescale into the original image
+                let points = rect.corners().map(|(x, y)| {
+                    (
+                        (x * scale_x).round().clamp(0.0, (width - 1) as f32) as u32,
+                        (y * scale_y).round().clamp(0.0, (height - 1) as f32) as u32,
+                    )
+                });
+                Some(TextBox {
+                    points,
+                    score: OrderedFloat(score),
+                })
+            })
+            .collect();
+
+        // keep the most certain regions
+        text_boxes.sort_by_key(|text_box| ::core::cmp::Reverse(text_box.score));
+        text_boxes.truncate(self.max_candidates);
+
+        // sort in the reading order
+        text_boxes.sort_by_key(|text_box| (text_box.points[0].1, text_box.points[0].0));
+        text_boxes
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TextBox {
+    /// The corners in the order of top-left, top-right, bottom-right and bottom-left.
+    pub points: [(u32, u32); 4],
+    pub score: OrderedFloat<f32>,
+}
+
+impl TextBox {
+    /// Returns the axis-aligned bounding box as `(x, y, width, height)`.
+    pub fn bounding_box(&self) -> (u32, u32, u32, u32) {
+        let xs = self.points.map(|(x, _)| x);
+        let ys = self.points.map(|(_, y)| y);
+
+        let x_min = xs.iter().copied().min().unwrap();
+        let y_min = ys.iter().copied().min().unwrap();
+        let x_max = xs.iter().copied().max().unwrap();
+        let y_max = ys.iter().copied().max().unwrap();
+        (x_min, y_min, x_max - x_min + 1, y_max - y_min + 1)
+    }
+
+    /// Crops the (rotated) region and rectifies it into an upright image.
+    pub fn crop(&self, image: &RgbImage) -> DynamicImage {
+        let [p0, p1, p2, p3] = self.points.map(|(x, y)| (x as f32, y as f32));
+        let distance = |(ax, ay): (f32, f32), (bx, by): (f32, f32)| (ax - bx).hypot(ay - by);
+
+        let width = distance(p0, p1).max(distance(p3, p2)).round().max(1.0) as u32;
+        let height = distance(p0, p3).max(distance(p1, p2)).round().max(1.0) as u32;
+
+        // map each target pixel back onto the source parallelogram
+        let axis_x = ((p1.0 - p0.0) / width as f32, (p1.1 - p0.1) / width as f32);
+        let axis_y = ((p3.0 - p0.0) / height as f32, (p3.1 - p0.1) / height as f32);
+        let crop = RgbImage::from_fn(width, height, |u, v| {
+            let (u, v) = (u as f32 + 0.5, v as f32 + 0.5);
+            let x = p0.0 + axis_x.0 * u + axis_y.0 * v;
+            let y = p0.1 + axis_x.1 * u + axis_y.1 * v;
+            sample_bilinear(image, x - 0.5, y - 0.5)
+        });
+        let crop = DynamicImage::ImageRgb8(crop);
+
+        // vertical texts are read from the rotated image
+        if height as f32 >= width as f32 * 1.5 {
+            crop.rotate270()
+        } else {
+            crop
+        }
+    }
+}
+
+fn sample_bilinear(image: &RgbImage, x: f32, y: f32) -> Rgb<u8> {
+    let x = x.clamp(0.0, (image.width() - 1) as f32);
+    let y = y.clamp(0.0, (image.height() - 1) as f32);
+
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = (
+        (x0 + 1).min(image.width() - 1),
+        (y0 + 1).min(image.height() - 1),
+    );
+    let (dx, dy) = (x - x0 as f32, y - y0 as f32);
+
+    let p00 = image.get_pixel(x0, y0).0;
+    let p10 = image.get_pixel(x1, y0).0;
+    let p01 = image.get_pixel(x0, y1).0;
+    let p11 = image.get_pixel(x1, y1).0;
+    Rgb([0, 1, 2].map(|c| {
+        let top = p00[c] as f32 * (1.0 - dx) + p10[c] as f32 * dx;
+        let bottom = p01[c] as f32 * (1.0 - dx) + p11[c] as f32 * dx;
+        (top * (1.0 - dy) + bottom * dy).round() as u8
+    }))
+}
+
+/// Collects the 4-connected regions of the binarized map as `(y, x)` pixels.
+fn find_components(map: ndarray::ArrayView2<f32>, threshold: f32) -> Vec<Vec<(usize, usize)>> {
+    let (height, width) = map.dim();
+    let mut visited = ndarray::Array2::from_elem((height, width), false);
+
+    let mut components = vec![];
+    for ((y, x), &prob) in map.indexed_iter() {
+        if visited[(y, x)] || prob <= threshold {
+            continue;
+        }
+
+        let mut pixels = vec![];
+        let mut queue = VecDeque::from([(y, x)]);
+        visited[(y, x)] = true;
+        while let Some((y, x)) = queue.pop_front() {
+            pixels.push((y, x));
+
+            let neighbors = [
+                (y.wrapping_sub(1), x),
+                (y + 1, x),
+                (y, x.wrapping_sub(1)),
+                (y, x + 1),
+            ];
+            for (y, x) in neighbors {
+                if y < height && x < width && !visited[(y, x)] && map[(y, x)] > threshold {
+                    visited[(y, x)] = true;
+                    queue.push_back((y, x));
+                }
+            }
+        }
+        components.push(pixels);
+    }
+    components
+}
+
+/// Andrew's monotone chain, returning the hull in counter-clockwise order.
+fn convex_hull(mut points: Vec<(f32, f32)>) -> Vec<(f32, f32)> {
+    fn cross(o: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+        (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+    }
+
+    points.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+
+    let mut lower: Vec<(f32, f32)> = Vec::with_capacity(points.len());
+    for &point in &points {
+        while lower.len() >= 2
+            && cross(lower[lower.len() - 2], lower[lower.len() - 1], point) <= 0.0
+        {
+            lower.pop();
+        }
+        lower.push(point);
+    }
+
+    let mut upper: Vec<(f32, f32)> = Vec::with_capacity(points.len());
+    for &point in points.iter().rev() {
+        while upper.len() >= 2
+            && cross(upper[upper.len() - 2], upper[upper.len() - 1], point) <= 0.0
+        {
+            upper.pop();
+        }
+        upper.push(point);
+    }
+
+    lower.pop();
+    upper.pop();
+    lower.extend(upper);
+    lower
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Rect {
+    axis: (f32, f32),
+    u: (f32, f32),
+    v: (f32, f32),
+}
+
+impl Rect {
+    /// Finds the minimum area rectangle of the convex hull with rotating calipers.
+    fn min_area(hull: &[(f32, f32)]) -> Option<Self> {
+        if hull.len() < 3 {
+            return None;
+        }
+
+        (0..hull.len())
+            .filter_map(|i| {
+                let (a, b) = (hull[i], hull[(i + 1) % hull.len()]);
+                let length = (b.0 - a.0).hypot(b.1 - a.1);
+                if length == 0.0 {
+                    return None;
+                }
+                let axis = ((b.0 - a.0) / length, (b.1 - a.1) / length);
+
+                let project = |f: &dyn Fn((f32, f32)) -> f32| {
+                    hull.iter()
+                        .map(|&point| f(point))
+                        .fold((f32::MAX, f32::MIN), |(min, max), value| {
+                            (min.min(value), max.max(value))
+                        })
+                };
+                Some(Self {
+                    axis,
+                    u: project(&|(x, y)| x * axis.0 + y * axis.1),
+                    v: project(&|(x, y)| -x * axis.1 + y * axis.0),
+                })
+            })
+            .min_by(|a, b| a.area().partial_cmp(&b.area()).unwrap())
+    }
+
+    fn width(&self) -> f32 {
+        self.u.1 - self.u.0
+    }
+
+    fn height(&self) -> f32 {
+        self.v.1 - self.v.0
+    }
+
+    fn area(&self) -> f32 {
+        self.width() * self.height()
+    }
+
+    fn short_side(&self) -> f32 {
+        self.width().min(self.height())
+    }
+
+    /// Offsets the sides outward, approximating the polygon clipping of DB.
+    fn unclip(self, ratio: f32) -> Self {
+        let perimeter = 2.0 * (self.width() + self.height());
+        let distance = self.area() * ratio / perimeter;
+
+        Self {
+            axis: self.axis,
+            u: (self.u.0 - distance, self.u.1 + distance),
+            v: (self.v.0 - distance, self.v.1 + distance),
+        }
+    }
+
+    /// Returns the corners in the order of top-left, top-right, bottom-right and bottom-left.
+    fn corners(&self) -> [(f32, f32); 4] {
+        let point = |u: f32, v: f32| {
+            (
+                u * self.axis.0 - v * self.axis.1,
+                u * self.axis.1 + v * self.axis.0,
+            )
+        };
+        let corners = [
+            point(self.u.0, self.v.0),
+            point(self.u.1, self.v.0),
+            point(self.u.1, self.v.1),
+            point(self.u.0, self.v.1),
+        ];
+
+        let find = |f: fn(&(f32, f32)) -> f32| {
+            *corners
+                .iter()
+                .min_by(|a, b| f(a).partial_cmp(&f(b)).unwrap())
+                .unwrap()
+        };
+        [
+            find(|(x, y)| x + y),
+            find(|(x, y)| y - x),
+            find(|(x, y)| -(x + y)),
+            find(|(x, y)| x - y),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(given: (f32, f32), expected: (f32, f32)) {
+        assert!(
+            (given.0 - expected.0).abs() < 1e-4 && (given.1 - expected.1).abs() < 1e-4,
+            "{given:?} != {expected:?}",
+        );
+    }
+
+    /// Rotates the point around the origin.
+    fn rotate((x, y): (f32, f32), degrees: f32) -> (f32, f32) {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        (x * cos - y * sin, x * sin + y * cos)
+    }
+
+    #[test]
+    fn hull_skips_the_inner_and_collinear_points() {
+        let points = vec![
+            (0.0, 0.0),
+            (2.0, 0.0),
+            (4.0, 0.0),
+            (4.0, 2.0),
+            (0.0, 2.0),
+            (1.0, 1.0),
+            (3.0, 1.0),
+            (4.0, 2.0),
+        ];
+        let hull = convex_hull(points);
+        assert_eq!(hull, [(0.0, 0.0), (4.0, 0.0), (4.0, 2.0), (0.0, 2.0)]);
+
+        // counter-clockwise, with the positive signed area
+        let area: f32 = (0..hull.len())
+            .map(|i| {
+                let (a, b) = (hull[i], hull[(i + 1) % hull.len()]);
+                a.0 * b.1 - b.0 * a.1
+            })
+            .sum();
+        assert_eq!(area / 2.0, 8.0);
+
+        assert_eq!(convex_hull(vec![(1.0, 1.0), (1.0, 1.0)]), [(1.0, 1.0)]);
+    }
+
+    #[test]
+    fn min_area_rect_follows_the_rotation() {
+        let corners = [(0.0, 0.0), (4.0, 0.0), (4.0, 2.0), (0.0, 2.0)];
+
+        let rect = Rect::min_area(&convex_hull(corners.to_vec())).unwrap();
+        assert_close((rect.area(), rect.short_side()), (8.0, 2.0));
+
+        // the axis-aligned bounding box would be larger
+        let rotated: Vec<_> = corners.iter().map(|&point| rotate(point, 30.0)).collect();
+        let rect = Rect::min_area(&convex_hull(rotated)).unwrap();
+        assert_close((rect.area(), rect.short_side()), (8.0, 2.0));
+
+        assert_eq!(Rect::min_area(&[(0.0, 0.0), (1.0, 1.0)]), None);
+    }
+
+    #[test]
+    fn unclip_expands_each_side() {
+        let rect = Rect {
+            axis: (1.0, 0.0),
+            u: (0.0, 4.0),
+            v: (0.0, 2.0),
+        };
+
+        // area * ratio / perimeter = 8 * 1.5 / 12
+        let rect = rect.unclip(1.5);
+        assert_close(rect.u, (-1.0, 5.0));
+        assert_close(rect.v, (-1.0, 3.0));
+    }
+
+    #[test]
+    fn corners_start_from_the_top_left() {
+        let expected = [(1.0, 2.0), (5.0, 2.0), (5.0, 4.0), (1.0, 4.0)];
+
+        // the rectangles may be found along any of their sides
+        for shift in 0..4 {
+            let hull: Vec<_> = convex_hull(expected.to_vec())
+                .into_iter()
+                .cycle()
+                .skip(shift)
+                .take(4)
+                .collect();
+            let rect = Rect::min_area(&hull).unwrap();
+
+            for (given, expected) in rect.corners().into_iter().zip(expected) {
+                assert_close(given, expected);
+            }
+        }
+
+        // the slightly rotated texts keep the top side first
+        let points: Vec<_> = expected.iter().map(|&point| rotate(point, 10.0)).collect();
+        let rect = Rect::min_area(&convex_hull(points.clone())).unwrap();
+        for (given, expected) in rect.corners().into_iter().zip(points) {
+            assert_close(given, expected);
+        }
+    }
+
+    #[test]
+    fn text_boxes_are_rescaled_into_the_image() {
+        let mut map = ndarray::Array2::zeros((32, 32));
+        map.slice_mut(ndarray::s![8..16, 4..28]).fill(1.0);
+        map.slice_mut(ndarray::s![20..22, 4..6]).fill(1.0);
+
+        let config = DetectionConfig::default();
+        let text_boxes = config.find_text_boxes(map.view(), 64, 64);
+
+        // the tiny region is dropped
+        assert_eq!(text_boxes.len(), 1);
+        assert_eq!(text_boxes[0].score, OrderedFloat(1.0));
+
+        // the region is expanded by (24 * 8 * 1.5) / 64 = 4.5 pixels on each side
+        assert_eq!(text_boxes[0].points, [(0, 7), (63, 7), (63, 41), (0, 41)]);
+    }
+
+    #[test]
+    fn crop_rectifies_the_region() {
+        let image = RgbImage::from_fn(16, 16, |x, y| match (x, y) {
+            (4..=11, 6..=9) => Rgb([255, 0, 0]),
+            _ => Rgb([0, 0, 255]),
+        });
+        let text_box = TextBox {
+            points: [(4, 6), (12, 6), (12, 10), (4, 10)],
+            score: OrderedFloat(1.0),
+        };
+        assert_eq!(text_box.bounding_box(), (4, 6, 9, 5));
+
+        let crop = text_box.crop(&image).to_rgb8();
+        assert_eq!(crop.dimensions(), (8, 4));
+        assert!(crop.pixels().all(|pixel| *pixel == Rgb([255, 0, 0])));
+
+        // the vertical texts are rotated
+        let text_box = TextBox {
+            points: [(4, 2), (6, 2), (6, 14), (4, 14)],
+            score: OrderedFloat(1.0),
+        };
+        assert_eq!(text_box.crop(&image).to_rgb8().dimensions(), (12, 2));
+    }
+}
@@ -0,0 +1,286 @@
+pub mod detection;
+pub mod recognition;
+
+use std::collections::HashMap;
+
+use ipis::{
+    async_trait::async_trait,
+    core::{
+        anyhow::{bail, Result},
+        ndarray,
+        ordered_float::OrderedFloat,
+        value::array::Array,
+    },
+};
+use ipnis_common::{
+    image::{imageops::FilterType, DynamicImage},
+    model::Model,
+    tensor::{dynamic::DynamicTensorData, shape::Shape, Tensor, TensorData, ToTensor},
+    vision::tensor::ImageTensorData,
+    Ipnis,
+};
+
+use crate::{
+    detection::{DetectionConfig, TextBox},
+    recognition::{Dictionary, RecognitionConfig, Recognized},
+};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OcrConfig {
+    pub detection: DetectionConfig,
+    pub recognition: RecognitionConfig,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outputs {
+    pub answers: Vec<Output>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Output {
+    pub regions: Vec<TextRegion>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextRegion {
+    pub text: String,
+    pub score: OrderedFloat<f32>,
+    pub text_box: TextBox,
+}
+
+#[async_trait]
+pub trait IpnisOcr: Ipnis {
+    async fn call_ocr(
+        &self,
+        detection: &Model,
+        recognition: &Model,
+        dictionary: &Dictionary,
+        config: &OcrConfig,
+        images: &[DynamicImage],
+    ) -> Result<Outputs> {
+        // detect the text regions
+        let text_boxes = self
+            .call_text_detection(detection, &config.detection, images)
+            .await?;
+
+        // crop and rectify the regions
+        let crops: Vec<_> = images
+            .iter()
+            .zip(text_boxes.iter())
+            .flat_map(|(image, text_boxes)| {
+                let image = image.to_rgb8();
+                text_boxes
+                    .iter()
+                    .map(|text_box| text_box.crop(&image))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        // recognize the texts
+        let mut texts = self
+            .call_text_recognition(recognition, dictionary, &config.recognition, &crops)
+            .await?
+            .into_iter();
+
+        // regroup the texts per image
+        Ok(Outputs {
+            answers: text_boxes
+                .into_iter()
+                .map(|text_boxes| Output {
+                    regions: text_boxes
+                        .into_iter()
+                        .zip(texts.by_ref())
+                        .filter(|(_, text)| {
+                            !text.text.is_empty()
+                                && text.score.0 >= config.recognition.score_threshold
+                        })
+                        .map(|(text_box, text)| TextRegion {
+                            text: text.text,
+                            score: text.score,
+                            text_box,
+                        })
+                        .collect(),
+                })
+                .collect(),
+        })
+    }
+
+    async fn call_text_detection(
+        &self,
+        model: &Model,
+        config: &DetectionConfig,
+        images: &[DynamicImage],
+    ) -> Result<Vec<Vec<TextBox>>> {
+        let shape = find_input(model)?;
+        let (channels, height, width) = get_image_dimensions(shape)?;
+
+        let mut answers = Vec::with_capacity(images.len());
+        for image in images {
+            // resize the image to fit the model
+            let (height, width) = match (height, width) {
+                (Some(height), Some(width)) => (height, width),
+                _ => config.fit(image.height() as usize, image.width() as usize),
+            };
+            let input = image_to_array(image, channels, height, width, &config.mean, &config.std)
+                .insert_axis(ndarray::Axis(0));
+
+            // perform the inference
+            let inputs = into_inputs(shape, input);
+            let mut outputs = self.call(model, &inputs).await?;
+            if outputs.is_empty() {
+                let outputs = outputs.len();
+                bail!("unexpected outputs: Expected 1, Given {outputs}");
+            }
+
+            // find the text boxes from the probability map
+            let map = into_array(outputs.remove(0))?;
+            let map = match *map.shape() {
+                [1, 1, map_height, map_width] | [1, map_height, map_width] => {
+                    map.into_shape((map_height, map_width))?
+                }
+                _ => {
+                    let shape = map.shape();
+                    bail!("unexpected probability map shape: {shape:?}")
+                }
+            };
+            answers.push(config.find_text_boxes(map.view(), image.width(), image.height()));
+        }
+        Ok(answers)
+    }
+
+    async fn call_text_recognition(
+        &self,
+        model: &Model,
+        dictionary: &Dictionary,
+        config: &RecognitionConfig,
+        images: &[DynamicImage],
+    ) -> Result<Vec<Recognized>> {
+        let shape = find_input(model)?;
+        let (channels, height, width) = get_image_dimensions(shape)?;
+        let height = height.unwrap_or(config.image_height);
+
+        let mut answers = Vec::with_capacity(images.len());
+        for images in images.chunks(config.batch_size.max(1)) {
+            // resize the images to the model height, keeping their aspect ratios
+            let widths: Vec<_> = images
+                .iter()
+                .map(|image| match width {
+                    Some(width) => width,
+                    None => {
+                        let ratio = image.width() as f32 / image.height().max(1) as f32;
+                        ((height as f32 * ratio).ceil() as usize).clamp(1, config.max_image_width)
+                    }
+                })
+                .collect();
+            let max_width = widths.iter().copied().max().unwrap_or(1);
+
+            // pad the images to the longest one
+            let mut input = ndarray::Array4::zeros((images.len(), channels, height, max_width));
+            for ((image, width), mut slot) in images.iter().zip(widths).zip(input.outer_iter_mut())
+            {
+                let image =
+                    image_to_array(image, channels, height, width, &config.mean, &config.std);
+                slot.slice_mut(ndarray::s![.., .., ..width]).assign(&image);
+            }
+
+            // perform the inference
+            let inputs = into_inputs(shape, input);
+            let mut outputs = self.call(model, &inputs).await?;
+            if outputs.is_empty() {
+                let outputs = outputs.len();
+                bail!("unexpected outputs: Expected 1, Given {outputs}");
+            }
+
+            // decode the texts
+            let probs = into_array(outputs.remove(0))?;
+            let probs = match *probs.shape() {
+                [batch_size, num_steps, num_classes] if batch_size == images.len() => {
+                    probs.into_shape((batch_size, num_steps, num_classes))?
+                }
+                _ => {
+                    let shape = probs.shape();
+                    bail!("unexpected recognition shape: {shape:?}")
+                }
+            };
+            answers.extend(
+                probs
+                    .outer_iter()
+                    .map(|probs| config.decode(probs, dictionary)),
+            );
+        }
+        Ok(answers)
+    }
+}
+
+impl<T: Ipnis + ?Sized> IpnisOcr for T {}
+
+fn find_input(model: &Model) -> Result<&Shape> {
+    match model.inputs.first() {
+        Some(shape) => Ok(shape),
+        None => bail!("the model has no inputs"),
+    }
+}
+
+/// Returns the `(channels, height, width)` of the `NCHW`-ordered image input.
+fn get_image_dimensions(shape: &Shape) -> Result<(usize, Option<usize>, Option<usize>)> {
+    match shape.to_vec()[..] {
+        [_, Some(channels @ (1 | 3)), height, width] => Ok((channels, height, width)),
+        _ => {
+            let name = &shape.name;
+            bail!("unsupported image input: {name}")
+        }
+    }
+}
+
+fn image_to_array(
+    image: &DynamicImage,
+    channels: usize,
+    height: usize,
+    width: usize,
+    mean: &[f32; 3],
+    std: &[f32; 3],
+) -> ndarray::Array3<f32> {
+    const RESIZE_FILTER: FilterType = FilterType::Triangle;
+
+    let image = image.resize_exact(width as u32, height as u32, RESIZE_FILTER);
+    let normalize = |value: u8, c: usize| (value as f32 / 255.0 - mean[c]) / std[c];
+
+    match channels {
+        1 => {
+            let image = image.to_luma8();
+            ndarray::Array3::from_shape_fn((1, height, width), |(c, y, x)| {
+                normalize(image.get_pixel(x as u32, y as u32)[0], c)
+            })
+        }
+        _ => {
+            let image = image.to_rgb8();
+            ndarray::Array3::from_shape_fn((3, height, width), |(c, y, x)| {
+                normalize(image.get_pixel(x as u32, y as u32)[c], c)
+            })
+        }
+    }
+}
+
+fn into_inputs(
+    shape: &Shape,
+    input: ndarray::Array4<f32>,
+) -> HashMap<String, Box<dyn ToTensor + Send + Sync>> {
+    let input = TensorData::from(ImageTensorData::F32(Array(input.into_shared())));
+
+    vec![(
+        shape.name.clone(),
+        Box::new(input) as Box<dyn ToTensor + Send + Sync>,
+    )]
+    .into_iter()
+    .collect()
+}
+
+fn into_array(tensor: Tensor) -> Result<ndarray::ArcArray<f32, ndarray::IxDyn>> {
+    match tensor.data {
+        TensorData::Dynamic(DynamicTensorData::F32(data)) => Ok(data.0),
+        _ => {
+            let shape = tensor.shape();
+            bail!("unexpected TensorData: {shape:?}")
+        }
+    }
+}
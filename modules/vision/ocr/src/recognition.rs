@@ -0,0 +1,115 @@
+pub use ipnis_common::ctc::CtcDecoder;
+
+use ipis::core::{anyhow::Result, ndarray, ordered_float::OrderedFloat};
+use ipnis_common::onnxruntime::tensor::ndarray_tensor::NdArrayTensor;
+
+/// The CTC blank is always placed at the first index.
+const BLANK: usize = 0;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Dictionary {
+    tokens: Vec<String>,
+}
+
+impl Dictionary {
+    pub fn new<I>(tokens: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: ToString,
+    {
+        Self {
+            tokens: ::core::iter::once(String::new())
+                .chain(tokens.into_iter().map(|token| token.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Loads a character dictionary, which has a token per line.
+    pub fn from_file(path: impl AsRef<::std::path::Path>) -> Result<Self> {
+        let tokens = ::std::fs::read_to_string(path)?;
+        Ok(Self::new(
+            tokens.lines().map(|token| token.trim_end_matches('\r')),
+        ))
+    }
+
+    /// Appends a space character, which is not listed in most dictionary files.
+    pub fn with_space(mut self) -> Self {
+        self.tokens.push(" ".into());
+        self
+    }
+
+    /// Returns the number of classes, including the blank.
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() <= 1
+    }
+
+    fn decode(&self, indices: &[usize]) -> String {
+        indices
+            .iter()
+            .filter_map(|&index| self.tokens.get(index))
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecognitionConfig {
+    /// Used when the model accepts dynamic heights.
+    pub image_height: usize,
+    /// Limits the width of the resized regions when the model accepts dynamic widths.
+    pub max_image_width: usize,
+    pub batch_size: usize,
+    pub decoder: CtcDecoder,
+    /// Applies softmax on the outputs, if the model returns logits.
+    pub softmax: bool,
+    /// Drops the texts whose confidence is lower than this threshold.
+    pub score_threshold: f32,
+    pub mean: [f32; 3],
+    pub std: [f32; 3],
+}
+
+impl Default for RecognitionConfig {
+    fn default() -> Self {
+        Self {
+            image_height: 48,
+            max_image_width: 1280,
+            batch_size: 8,
+            decoder: CtcDecoder::default(),
+            softmax: false,
+            score_threshold: 0.5,
+            mean: [0.5; 3],
+            std: [0.5; 3],
+        }
+    }
+}
+
+impl RecognitionConfig {
+    pub(crate) fn decode(
+        &self,
+        probs: ndarray::ArrayView2<f32>,
+        dictionary: &Dictionary,
+    ) -> Recognized {
+        let probs = if self.softmax {
+            probs.softmax(ndarray::Axis(1))
+        } else {
+            probs.to_owned()
+        };
+
+        let output = self.decoder.decode(probs.view(), BLANK, None);
+
+        Recognized {
+            text: dictionary.decode(&output.indices()),
+            score: OrderedFloat(output.score),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Recognized {
+    pub text: String,
+    pub score: OrderedFloat<f32>,
+}